@@ -92,6 +92,71 @@ impl Database {
             .await?;
         Ok(())
     }
+
+    pub async fn update_board_settings(
+        conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+        discrim: &str,
+        settings: BoardSettings,
+    ) -> Result<()> {
+        use database::boards::dsl::*;
+
+        let board = Self::get_raw_board(conn, discrim)
+            .await
+            .map_err(|_| anyhow!("Board not found"))?;
+
+        if let Some(new_text_previews) = settings.text_previews {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(text_previews.eq(new_text_previews))
+                .execute(conn)
+                .await?;
+        }
+
+        if let Some(new_hide_filenames) = settings.hide_filenames {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(hide_filenames.eq(new_hide_filenames))
+                .execute(conn)
+                .await?;
+            // cached files carry the names as the board showed them until now
+            crate::FILE_CACHE_INFO.lock().await.forget_board(board.id);
+        }
+
+        if let Some(new_quota_bytes) = settings.quota_bytes {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(quota_bytes.eq(Some(new_quota_bytes).filter(|q| *q > 0)))
+                .execute(conn)
+                .await?;
+        }
+
+        if let Some(new_quota_files) = settings.quota_files {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(quota_files.eq(Some(new_quota_files).filter(|q| *q > 0)))
+                .execute(conn)
+                .await?;
+        }
+
+        if let Some(new_bump_limit) = settings.bump_limit {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(bump_limit.eq(Some(new_bump_limit).filter(|l| *l > 0)))
+                .execute(conn)
+                .await?;
+        }
+
+        if let Some(new_max_threads) = settings.max_threads {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(max_threads.eq(Some(new_max_threads).filter(|m| *m > 0)))
+                .execute(conn)
+                .await?;
+        }
+
+        if let Some(new_overflow) = settings.overflow {
+            diesel::update(boards.filter(id.eq(board.id)))
+                .set(overflow.eq(new_overflow.as_str()))
+                .execute(conn)
+                .await?;
+        }
+
+        Ok(())
+    }
     pub async fn get_boards(
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         token: MemberToken,
@@ -283,7 +348,7 @@ impl Database {
     //     post.safe(conn).await
     // }
 
-    async fn get_raw_post(
+    pub async fn get_raw_post(
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         discriminator: &str,
         number: i64,
@@ -329,24 +394,73 @@ impl Database {
 
         let tthread = Self::get_raw_thread(conn, discriminator, number).await;
         let id = tpost.id;
-        match (at_least_mod, tauthor, tthread, tpost) {
+        let tthread_post_number = database::thread_post_number(tpost.thread, conn).await?;
+        // the author has to be found before the post is gone
+        let authors = if at_least_mod && !tauthor {
+            Self::get_post_authors(conn, discriminator, &[tpost.id], &token.database_hash())
+                .await
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let deleted_notice = crate::inbox::notice(
+            InboxKind::PostDeleted,
+            discriminator,
+            tthread_post_number,
+            number,
+            crate::inbox::summarize(&tpost.content),
+            None,
+        );
+        let message = match (at_least_mod, tauthor, tthread, tpost) {
             // if the user is an admin they can delete a post
             (true, _, Err(_), post) => {
                 Self::raw_delete_post(conn, post.id).await?;
+                PushMessage::PostDeleted(DeletedPost {
+                    board_discriminator: discriminator.to_owned(),
+                    thread_post_number: tthread_post_number,
+                    post_number: number,
+                })
             }
             // if the user is an admin they can delete a thread
             (true, _, Ok(thrd), _) => {
                 Self::raw_delete_thread(conn, thrd.id).await?;
+                PushMessage::ThreadStatusChanged(ThreadStatusChange {
+                    board_discriminator: discriminator.to_owned(),
+                    thread_post_number: number,
+                    status: ThreadStatus::Deleted,
+                })
             }
             // if the user is the author of the post and it is not a thread they can delete it
             (false, true, Err(_), post) => {
                 Self::raw_delete_post(conn, post.id).await?;
+                PushMessage::PostDeleted(DeletedPost {
+                    board_discriminator: discriminator.to_owned(),
+                    thread_post_number: tthread_post_number,
+                    post_number: number,
+                })
             }
             // otherwise they are not authorized to delete the post
             _ => {
                 return Err(anyhow::anyhow!("Not authorized to delete post"));
             }
+        };
+
+        crate::PUSH_NOTIFS.lock().await.send_to(
+            &[
+                &crate::push::board_ident(discriminator),
+                &crate::push::thread_ident(discriminator, tthread_post_number),
+            ],
+            message,
+        );
+
+        let author_hashes = authors
+            .iter()
+            .map(|x| x.token_hash.as_str())
+            .collect::<Vec<_>>();
+        if let Err(e) = crate::inbox::record(conn, &author_hashes, &deleted_notice).await {
+            log::error!("Error recording inbox notices: {}", e);
         }
+
         Ok(id)
     }
 
@@ -362,7 +476,7 @@ impl Database {
         Ok(())
     }
 
-    async fn raw_delete_thread(
+    pub async fn raw_delete_thread(
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         tid: i64,
     ) -> Result<()> {
@@ -400,6 +514,7 @@ impl Database {
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         file: ClaimedFileInfo,
         post_id: i64,
+        quota_key: &str,
     ) -> Result<database::File> {
         use database::files::dsl::*;
 
@@ -409,6 +524,18 @@ impl Database {
                 hash.eq(file.hash),
                 id.eq(post_id),
                 spoiler.eq(file.spoiler),
+                phash.eq(file.phash),
+                variants.eq(file
+                    .variants
+                    .iter()
+                    .map(|v| v.name().to_owned())
+                    .collect::<Vec<String>>()),
+                duration_ms.eq(file.media.duration_ms),
+                width.eq(file.media.width),
+                height.eq(file.media.height),
+                original_name.eq(file.original_name),
+                size.eq(file.size),
+                uploader.eq(quota_key),
             ))
             .get_result::<database::File>(conn)
             .await?;
@@ -513,7 +640,20 @@ impl Database {
             }
         };
         t.post_id = p.id;
-        t.with_posts(conn, &token.database_hash()).await
+        let created = t.with_posts(conn, &token.database_hash()).await?;
+
+        crate::PUSH_NOTIFS.lock().await.send_to(
+            &[&crate::push::board_ident(tboard)],
+            common::structs::PushMessage::NewThread(Arc::new(ThreadWithLazyPosts {
+                board: created.board,
+                thread_post: created.thread_post.clone(),
+                post_count: created.post_count,
+                posts: created.posts.clone(),
+                topic: created.topic.clone(),
+            })),
+        );
+
+        Ok(created)
     }
 
     pub async fn create_post(
@@ -540,15 +680,20 @@ impl Database {
         post.author = post.author.map(|string| {
             replace_possible_profanity(string, &crate::PROFANITY, || crate::QUOTES.random_quote())
         });
-        let this_post_number = Self::get_board(conn, discriminator, token.clone())
-            .await?
-            .post_count
-            + 1;
+        let this_board = Self::get_board(conn, discriminator, token.clone()).await?;
+        let this_post_number = this_board.post_count + 1;
         // THIS LINE, THE THREAD DOESNT EXIST LOOOL
         let thread_post_number = match thread_post_number(tthread, conn).await {
             Ok(v) => v,
             Err(_) => this_post_number,
         };
+        let this_thread = database::get_threads_by_id(conn, &[tthread]).await?.pop();
+        if this_thread
+            .as_ref()
+            .is_some_and(|t| t.archived_at.is_some())
+        {
+            return Err(anyhow::anyhow!("Thread is archived"));
+        }
 
         let replies = post
             .content
@@ -571,17 +716,39 @@ impl Database {
                 replieses.push(this_post);
             }
         }
+        // checked before the upload is claimed, a rejected post keeps its file pending
+        let at_least_mod = !database::permission_level(conn, tboard, &token.database_hash())
+            .await?
+            .is_none();
+        if post.silent && !at_least_mod {
+            return Err(anyhow::anyhow!("Only moderators can post silently"));
+        }
+
         let lock = crate::FS_LOCK.lock().await;
 
         let pending_file = if let Some(file) = post.file.clone() {
+            let pending_size = crate::UNCLAIMED_FILES
+                .lock()
+                .await
+                .pending_size(&file.id, token.clone());
+            if let Some(pending_size) = pending_size {
+                crate::quotas::check_board_quota(conn, &this_board, pending_size).await?;
+            }
+            let bans = database::get_file_bans(conn, tboard).await?;
             let f = crate::UNCLAIMED_FILES
                 .lock()
                 .await
-                .claim_file(&file, token.clone(), thread_post_number == this_post_number)
+                .claim_file(
+                    &file,
+                    token.clone(),
+                    thread_post_number == this_post_number,
+                    this_board.text_previews,
+                    &bans,
+                )
                 .await?;
 
             if let Some(files_check) = check_hash_against {
-                if files_check.iter().any(|x| x.claimed.hash == f.hash) {
+                if files_check.iter().any(|x| x.claimed.hash == f.0.hash) {
                     return Err(anyhow::anyhow!("File already exists"));
                 }
             }
@@ -591,9 +758,8 @@ impl Database {
             None
         };
 
-        let at_least_mod = !database::permission_level(conn, tboard, &token.database_hash())
-            .await?
-            .is_none();
+        // an opening post has nothing to hold back from bumping
+        let is_reply = thread_post_number != this_post_number;
 
         // if !Self::is_admin(conn, token.clone(), board).await? {
         //     post.moderator = false;
@@ -601,6 +767,15 @@ impl Database {
 
         let member_hash = token.database_hash();
 
+        // read under FS_LOCK, every post insert holds it so nothing else can bump the thread before ours lands
+        let previous_latest_post = match this_thread {
+            Some(_) => database::get_threads_by_id(conn, &[tthread])
+                .await?
+                .pop()
+                .map(|t| t.latest_post),
+            None => None,
+        };
+
         let t = insert_into(posts).values((
             post_number.eq(this_post_number),
             moderator.eq(post.moderator && at_least_mod),
@@ -608,18 +783,65 @@ impl Database {
             board.eq(tboard),
             author.eq(&post.author),
             content.eq(&post.content),
-            replies_to.eq(replieses),
+            replies_to.eq(&replieses),
+            sage.eq(post.sage && !post.silent && is_reply),
             timestamp.eq(now),
             actual_author.eq(&*member_hash),
         ));
         let p = t.get_result::<database::Post>(conn).await?;
-
-        if let Some(f) = pending_file {
-            Self::create_file(conn, f, p.id).await?;
+        database::add_post_replies(conn, p.id, &replieses).await?;
+        database::add_post_author(conn, p.id, &member_hash).await?;
+
+        // the bump_thread trigger moves every thread a post lands in to the top,
+        // saged and silent replies and any past the bump limit put it back where it was
+        let bumped = if !is_reply {
+            true
+        } else if post.sage || post.silent {
+            false
+        } else {
+            match (&this_thread, this_board.bump_limit) {
+                (Some(_), Some(limit)) => {
+                    let (_, replies) =
+                        database::thread_activity(conn, tthread, thread_post_number).await?;
+                    replies <= limit
+                }
+                _ => true,
+            }
+        };
+        if let (false, Some(previous_latest_post)) = (bumped, previous_latest_post) {
+            database::set_latest_post(conn, tthread, previous_latest_post).await?;
         }
 
+        let pending_encode = match pending_file {
+            Some((f, encode)) => {
+                let path = f.path.clone();
+                Self::create_file(conn, f, p.id, &token.quota_hash()).await?;
+                // registered while FS_LOCK is still held, so gc can't run in between
+                crate::ENCODING_FILES.lock().await.insert(path.clone());
+                Some((path, encode))
+            }
+            None => None,
+        };
+
         drop(lock);
 
+        if let Some((path, encode)) = pending_encode {
+            let file_id = p.id;
+            tokio::spawn(async move {
+                let (media, variants) = encode.run().await;
+                let result = match crate::POOL.get().await {
+                    Ok(mut conn) => {
+                        database::set_file_media(&mut conn, file_id, media, &variants).await
+                    }
+                    Err(e) => Err(anyhow::anyhow!("Error getting connection: {}", e)),
+                };
+                if let Err(e) = result {
+                    log::error!("Failed to record media for file {}: {:?}", file_id, e);
+                }
+                crate::ENCODING_FILES.lock().await.remove(&path);
+            });
+        }
+
         diesel::update(posts.filter(id.eq(p.id)))
             .set(actual_author.eq(&*token.post_hash(&p.id.to_string())))
             .execute(conn)
@@ -627,7 +849,8 @@ impl Database {
 
         let safe = p.safe(conn, &token.database_hash()).await?;
         tokio::spawn(async move {
-            Self::dispatch_push_notifications(safe, tthread, &member_hash).await;
+            Self::dispatch_push_notifications(safe, tthread, bumped, &replieses, &member_hash)
+                .await;
         });
 
         Ok(p)
@@ -636,6 +859,8 @@ impl Database {
     pub async fn dispatch_push_notifications(
         safe: common::structs::SafePost,
         thread: i64,
+        bumped: bool,
+        replied_to: &[i64],
         this_author: &str,
     ) {
         let mut conn = match crate::POOL.get().await {
@@ -645,27 +870,74 @@ impl Database {
                 return;
             }
         };
+        let reply_members = Self::get_post_authors(
+            &mut conn,
+            &safe.board_discriminator,
+            replied_to,
+            this_author,
+        )
+        .await
+        .unwrap_or_default();
         let mut sse = crate::PUSH_NOTIFS.lock().await;
         let raw_members = Self::get_subscribed_users(&mut conn, thread)
             .await
             .unwrap_or_default();
+        // people that were replied to hear about it once, as a reply
         let all_members = raw_members
             .iter()
             .filter(|x| x.token_hash != this_author)
+            .filter(|x| !reply_members.iter().any(|r| r.id == x.id))
             .collect::<Vec<_>>();
         let mut idents = all_members
             .iter()
             .map(|x| &x.token_hash)
             .collect::<Vec<&String>>();
-        let this_thing = format!(
-            "board: {} | thread: {}",
-            safe.board_discriminator, safe.thread_post_number
-        );
+        let this_thing =
+            crate::push::thread_ident(&safe.board_discriminator, safe.thread_post_number);
         idents.push(&this_thing);
+        let safe_arc = Arc::new(safe.clone());
         sse.send_to(
             idents.as_slice(),
-            common::structs::PushMessage::NewPost(Arc::new(safe.clone())),
+            common::structs::PushMessage::NewPost(Arc::clone(&safe_arc)),
         );
+        sse.send_to(
+            reply_members
+                .iter()
+                .map(|x| &x.token_hash)
+                .collect::<Vec<&String>>()
+                .as_slice(),
+            common::structs::PushMessage::ReplyToYou(Arc::clone(&safe_arc)),
+        );
+        // the thread post itself is announced by create_thread
+        if safe.post_number != safe.thread_post_number {
+            sse.send_to(
+                &[&crate::push::board_ident(&safe.board_discriminator)],
+                if bumped {
+                    common::structs::PushMessage::ThreadBumped(safe_arc)
+                } else {
+                    common::structs::PushMessage::ThreadReplied(safe_arc)
+                },
+            );
+        }
+        // recording sends on PUSH_NOTIFS too
+        drop(sse);
+
+        let watching_notice = crate::inbox::post_notice(InboxKind::WatchedPost, &safe);
+        let watching_hashes = all_members
+            .iter()
+            .map(|x| x.token_hash.as_str())
+            .collect::<Vec<_>>();
+        if let Err(e) = crate::inbox::record(&mut conn, &watching_hashes, &watching_notice).await {
+            log::error!("Error recording inbox notices: {}", e);
+        }
+        let reply_notice = crate::inbox::post_notice(InboxKind::Reply, &safe);
+        let reply_hashes = reply_members
+            .iter()
+            .map(|x| x.token_hash.as_str())
+            .collect::<Vec<_>>();
+        if let Err(e) = crate::inbox::record(&mut conn, &reply_hashes, &reply_notice).await {
+            log::error!("Error recording inbox notices: {}", e);
+        }
 
         let thread_topic = Self::get_raw_thread(
             &mut conn,
@@ -676,84 +948,100 @@ impl Database {
         .map(|x| x.topic)
         .unwrap_or_default();
 
-        use database::members::dsl::*;
+        let payload = |title: &str, kind: &str| {
+            serde_json::to_string(&serde_json::json!({
+                "title": title,
+                "body": serde_json::to_string(&serde_json::json!(
+                    {
+                        "kind": kind,
+                        "content": safe.content,
+                        "author": format!("{}", safe.author),
+                        "board_discriminator": safe.board_discriminator,
+                        "thread_post_number": safe.thread_post_number,
+                        "thread_topic": thread_topic,
+                        "post_number": safe.post_number,
+                        "thumbnail": safe.file.as_ref().map(|x| x.claimed.thumbnail.clone()),
+                    }
+                )).unwrap_or_default(),
+            }))
+        };
 
-        let payload = match serde_json::to_string(&serde_json::json!({
-            "title": "New post in a thread you're watching!",
-            "body": serde_json::to_string(&serde_json::json!(
-                {
-                    "content": safe.content,
-                    "author": format!("{}", safe.author),
-                    "board_discriminator": safe.board_discriminator,
-                    "thread_post_number": safe.thread_post_number,
-                    "thread_topic": thread_topic,
-                    "post_number": safe.post_number,
-                    "thumbnail": safe.file.map(|x| x.claimed.thumbnail),
-                }
-            )).unwrap_or_default(),
-        })) {
-            Ok(v) => v,
-            Err(e) => {
+        let (watching_payload, reply_payload) = match (
+            payload("New post in a thread you're watching!", "watching"),
+            payload("Someone replied to your post!", "reply"),
+        ) {
+            (Ok(w), Ok(r)) => (w, r),
+            (Err(e), _) | (_, Err(e)) => {
                 log::error!("Error serializing push payload: {}", e);
                 return;
             }
         };
 
-        for member in &all_members {
-            let mut pushy = vec![];
-            let changed = match member.parse_push_data() {
-                Ok(v) => {
-                    let mut changed = false;
-                    for push in v {
-                        match Self::push(&push, payload.as_bytes()).await {
-                            Ok(_) => {
-                                pushy.push(push);
-                            }
-                            Err(e) => {
-                                log::error!("Error sending push notification: {}", e);
-                                changed = true;
-                            }
-                        }
-                    }
-                    changed
-                }
-                Err(e) => {
-                    log::error!("Error parsing push data: {}", e);
-                    continue;
-                }
-            };
-            if changed {
-                if let Ok(v) = serde_json::to_value(pushy) {
-                    if let Err(e) = diesel::update(members.filter(id.eq(member.id)))
-                        .set(push_data.eq(&v))
-                        .execute(&mut conn)
-                        .await
-                    {
-                        log::error!("Error updating push data: {}", e);
-                    }
-                }
-            }
+        // delivery happens in the outbox workers, this only has to get them into the table
+        let watching_pushes = all_members
+            .iter()
+            .filter(|x| crate::preferences::wants_push(x, thread, false))
+            .copied()
+            .collect::<Vec<_>>();
+        if let Err(e) = crate::outbox::enqueue(&mut conn, &watching_pushes, &watching_payload).await
+        {
+            log::error!("Error queueing push notifications: {}", e);
+        }
+        if let Err(e) = crate::outbox::enqueue(
+            &mut conn,
+            &reply_members
+                .iter()
+                .filter(|x| crate::preferences::wants_push(x, thread, true))
+                .collect::<Vec<_>>(),
+            &reply_payload,
+        )
+        .await
+        {
+            log::error!("Error queueing reply notifications: {}", e);
         }
+
         log::trace!(
-            "Push notifications dispatched for {} users on thread {}",
+            "Push notifications queued for {} users and {} replied to authors on thread {}",
             all_members.len(),
+            reply_members.len(),
             thread
         );
     }
 
-    pub async fn get_all_files(
+    // members that wrote any of the given posts and can still see the board, other than this_author
+    pub async fn get_post_authors(
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
-    ) -> Result<Vec<FileInfo>> {
-        use database::files::dsl::*;
-        let filelist = files.load::<database::File>(conn).await?;
-        let mut newlist = vec![];
-        for file in filelist {
-            newlist.push(
-                file.info(conn, Some(env!("SUPER_SECRET_CODE").to_owned()))
-                    .await?,
-            );
+        board_discriminator: &str,
+        replied_to: &[i64],
+        this_author: &str,
+    ) -> Result<Vec<database::Member>> {
+        if replied_to.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let board = {
+            use database::boards::dsl::*;
+            boards
+                .filter(discriminator.eq(board_discriminator))
+                .first::<database::Board>(conn)
+                .await?
+        };
+
+        let mut recipients = vec![];
+        for member in database::get_post_author_members(conn, replied_to).await? {
+            if member.token_hash == this_author {
+                continue;
+            }
+            if board.private
+                && !database::check_access(conn, &Arc::new(member.token_hash.clone()), board.id)
+                    .await
+                    .unwrap_or(false)
+            {
+                continue;
+            }
+            recipients.push(member);
         }
-        Ok(newlist)
+        Ok(recipients)
     }
 
     pub async fn get_file(
@@ -999,21 +1287,23 @@ impl Database {
 
         match (set_watching, user.watching.contains(&watching_id)) {
             (true, false) => {
-                let mut twatching = user.watching;
+                let mut twatching = user.watching.clone();
                 twatching.push(watching_id);
                 diesel::update(members.filter(token_hash.eq(&*token.database_hash())))
                     .set(watching.eq(twatching))
                     .execute(conn)
                     .await?;
+                crate::watching::track_seen(conn, &user, watching_id, set_watching).await?;
                 Ok(true)
             }
             (false, true) => {
-                let mut twatching = user.watching;
+                let mut twatching = user.watching.clone();
                 twatching.retain(|x| *x != watching_id);
                 diesel::update(members.filter(token_hash.eq(&*token.database_hash())))
                     .set(watching.eq(twatching))
                     .execute(conn)
                     .await?;
+                crate::watching::track_seen(conn, &user, watching_id, set_watching).await?;
                 Ok(false)
             }
             _ => Ok(set_watching),
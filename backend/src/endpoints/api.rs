@@ -1,7 +1,8 @@
-use crate::filters::{valid_token, MemberToken, Ratelimited, Token};
+use crate::filters::{last_event_id, valid_token, MemberToken, Ratelimited, Token};
 use crate::unclaimedfiles::File;
 use common::structs::{
-    CreateBoard, CreatePost, CreateThread, FileInfo, SafeBoard, SubscriptionData,
+    BoardSettings, CreateBoard, CreatePost, CreateShareLink, CreateThread, FileInfo,
+    NotificationPreferences, SafeBoard, SubscriptionData, ThreadRef,
 };
 use serde::{Deserialize, Serialize};
 use warp::{Filter, Rejection, Reply};
@@ -39,6 +40,34 @@ pub fn priveleged_api_endpoints(
             }
         });
 
+    // PATCH /board/{discriminator} - changes a board's settings, leaving out a setting leaves it as is
+    let patchboard = warp::path!("api" / "v1" / "board" / String)
+        .and(warp::patch())
+        .and(warp::body::json::<BoardSettings>())
+        .and_then(
+            |discriminator: String, settings: BoardSettings| async move {
+                let mut conn = crate::POOL
+                    .get()
+                    .await
+                    .map_err(|_| warp::reject::reject())?;
+
+                match crate::database_bindings::Database::update_board_settings(
+                    &mut conn,
+                    &discriminator,
+                    settings,
+                )
+                .await
+                {
+                    Ok(_) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &"OK".to_owned(),
+                    )),
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            },
+        );
+
     let adduser = warp::path!("api" / "add" / "user")
         .and(warp::post())
         .and(warp::body::json::<UserSafe>())
@@ -119,7 +148,304 @@ pub fn priveleged_api_endpoints(
             }
         });
 
-    priveleged_endpoint().and(adduser.or(removeuser).or(setusers).or(putboard))
+    // POST /admin/gc?dry_run=bool - runs the file garbage collector, reporting what was (or would be) removed
+    let collectgarbage = warp::path!("api" / "v1" / "admin" / "gc")
+        .and(warp::post())
+        .and(warp::query::<GcQuery>())
+        .and_then(|query: GcQuery| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::garbage::collect_garbage(&mut conn, query.dry_run).await {
+                Ok(report) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&report))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/trash - lists every file currently in the trash and when it will be purged
+    let listtrash = warp::path!("api" / "v1" / "admin" / "trash")
+        .and(warp::get())
+        .and_then(|| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::garbage::list_trash(&mut conn).await {
+                Ok(trash) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&trash))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /admin/trash/restore/{file_path} - moves a trashed file back into storage
+    let restorefile = warp::path!("api" / "v1" / "admin" / "trash" / "restore" / "files" / ..)
+        .and(warp::path::tail())
+        .and(warp::post())
+        .and_then(|tail: warp::path::Tail| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            let file = format!("/files/{}", tail.as_str());
+            match crate::garbage::restore_file(&mut conn, &file).await {
+                Ok(_) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &"OK".to_owned(),
+                )),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // PUT /admin/bans/files - bans a stored file by hash and perceptual hash, globally or for a single board
+    let banfile = warp::path!("api" / "v1" / "admin" / "bans" / "files")
+        .and(warp::put())
+        .and(warp::body::json::<CreateFileBan>())
+        .and_then(|ban: CreateFileBan| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::filebans::ban_file(&mut conn, &ban.path, ban.board.as_deref(), ban.reason)
+                .await
+            {
+                Ok(ban) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&ban))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/bans/files - lists every file ban
+    let listfilebans = warp::path!("api" / "v1" / "admin" / "bans" / "files")
+        .and(warp::get())
+        .and_then(|| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::filebans::list_bans(&mut conn).await {
+                Ok(bans) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&bans))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // DELETE /admin/bans/files/{id} - lifts a file ban
+    let removefileban = warp::path!("api" / "v1" / "admin" / "bans" / "files" / i64)
+        .and(warp::delete())
+        .and_then(|id: i64| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::filebans::remove_ban(&mut conn, id).await {
+                Ok(_) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &"OK".to_owned(),
+                )),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/keys - lists the share link signing keys and when each can be retired
+    let listkeys = warp::path!("api" / "v1" / "admin" / "keys")
+        .and(warp::get())
+        .and_then(|| async move {
+            Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                &crate::statics::HMAC_KEYRING.list().await,
+            ))
+        });
+
+    // POST /admin/keys/rotate - starts signing with a new key and retires keys whose links have all expired
+    let rotatekeys = warp::path!("api" / "v1" / "admin" / "keys" / "rotate")
+        .and(warp::post())
+        .and_then(|| async move {
+            match crate::statics::HMAC_KEYRING.rotate().await {
+                Ok(report) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&report))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/usage - reports stored bytes and file counts by board, mimetype and the heaviest uploaders
+    let usage = warp::path!("api" / "v1" / "admin" / "usage")
+        .and(warp::get())
+        .and_then(|| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::quotas::usage_report(&mut conn).await {
+                Ok(report) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&report))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /admin/usage/backfill - fills in sizes and quota keys on file rows from before quotas
+    let usagebackfill = warp::path!("api" / "v1" / "admin" / "usage" / "backfill")
+        .and(warp::post())
+        .and_then(|| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::quotas::backfill(&mut conn).await {
+                Ok(report) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&report))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/push - web push delivery counters and the state of the outbox
+    let pushstats = warp::path!("api" / "v1" / "admin" / "push")
+        .and(warp::get())
+        .and_then(|| async move {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::outbox::report(&mut conn).await {
+                Ok(report) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&report))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /admin/export/{board_discriminator}/{thread_id} - exports any thread, private boards included
+    let adminexportthread = warp::path!("api" / "v1" / "admin" / "export" / String / i64)
+        .and(warp::post())
+        .and_then(|discriminator: String, number: i64| async move {
+            let conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::exports::start(
+                conn,
+                crate::exports::ExportTarget::Thread(discriminator, number),
+                None,
+            )
+            .await
+            {
+                Ok(status) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&status))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /admin/export/{board_discriminator} - exports any board with all of its threads
+    let adminexportboard = warp::path!("api" / "v1" / "admin" / "export" / String)
+        .and(warp::post())
+        .and_then(|discriminator: String| async move {
+            let conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| warp::reject::reject())?;
+
+            match crate::exports::start(
+                conn,
+                crate::exports::ExportTarget::Board(discriminator),
+                None,
+            )
+            .await
+            {
+                Ok(status) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&status))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/exports/{id} - the state of any export, whoever started it
+    let adminexportstatus = warp::path!("api" / "v1" / "admin" / "exports" / String)
+        .and(warp::get())
+        .and_then(|id: String| async move {
+            match crate::exports::status(&id, None).await {
+                Ok(status) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&status))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /admin/exports/{id}/download - the finished zip of any export
+    let adminexportdownload = warp::path!("api" / "v1" / "admin" / "exports" / String / "download")
+        .and(warp::get())
+        .and_then(|id: String| async move {
+            match crate::exports::download(&id, None).await {
+                Ok(resp) => Ok::<warp::reply::Response, warp::reject::Rejection>(resp),
+                Err(e) => Ok::<warp::reply::Response, warp::reject::Rejection>(
+                    warp::reply::json(&e.to_string()).into_response(),
+                ),
+            }
+        });
+
+    priveleged_endpoint().and(
+        adduser
+            .or(removeuser)
+            .or(setusers)
+            .or(putboard)
+            .or(patchboard)
+            .or(collectgarbage)
+            .or(listtrash)
+            .or(restorefile)
+            .or(banfile)
+            .or(listfilebans)
+            .or(removefileban)
+            .or(usage)
+            .or(usagebackfill)
+            .or(listkeys)
+            .or(rotatekeys)
+            .or(pushstats)
+            .or(adminexportthread)
+            .or(adminexportboard)
+            .or(adminexportstatus)
+            .or(adminexportdownload),
+    )
 }
 
 pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone
@@ -157,13 +483,14 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
-    // GET /board/{discriminator} - returns info about the board including a list of threads
+    // GET /board/{discriminator}?page=i64&page_size=i64 - returns info about the board including a page of threads
 
     let getboard = warp::path!("api" / "v1" / "board" / String)
         .and(warp::get())
         .and(valid_token())
+        .and(warp::query::<BoardQuery>())
         .and_then({
-            |disc: String, mut token: Token| async move {
+            |disc: String, mut token: Token, query: BoardQuery| async move {
                 let mut conn = match crate::POOL.get().await {
                     Ok(pool) => pool,
                     Err(e) => {
@@ -173,6 +500,10 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
                         );
                     }
                 };
+                let page_size = query
+                    .page_size
+                    .unwrap_or(crate::statics::BOARD_PAGE_SIZE)
+                    .clamp(1, crate::statics::MAX_BOARD_PAGE_SIZE);
                 match crate::database_bindings::Database::get_board(
                     &mut conn,
                     &disc,
@@ -181,7 +512,12 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
                 .await
                 {
                     Ok(board) => match board
-                        .with_threads(&mut conn, &token.member_hash().database_hash())
+                        .with_threads(
+                            &mut conn,
+                            &token.member_hash().database_hash(),
+                            query.page.unwrap_or(0).max(0),
+                            page_size,
+                        )
                         .await
                     {
                         Ok(board) => Ok::<warp::reply::Json, warp::reject::Rejection>(
@@ -198,6 +534,44 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
+    // GET /board/{discriminator}/catalog - returns a summary of every thread on the board in bump order
+
+    let getcatalog = warp::path!("api" / "v1" / "board" / String / "catalog")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then({
+            |disc: String, mut token: Token| async move {
+                let mut conn = match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                };
+                match crate::database_bindings::Database::get_board(
+                    &mut conn,
+                    &disc,
+                    token.member_hash(),
+                )
+                .await
+                {
+                    Ok(board) => match board.catalog(&mut conn).await {
+                        Ok(catalog) => Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&catalog),
+                        ),
+                        Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&e.to_string()),
+                        ),
+                    },
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            }
+        });
+
     // POST /board/{discriminator}/thread - creates a new thread
 
     let postthread = warp::path!("api" / "v1" / "board" / String / "thread")
@@ -443,6 +817,41 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
+    // PUT /{discriminator}/post/{post_number}/ban_file - bans the file attached to a post from being uploaded to this board again
+
+    let banpostfile = warp::path!("api" / "v1" / "board" / String / "post" / i64 / "ban_file")
+        .and(warp::put())
+        .and(warp::body::json::<BanReason>())
+        .and(valid_token())
+        .and_then({
+            |disc: String, post: i64, ban: BanReason, mut token: Token| async move {
+                match crate::filebans::ban_post_file(
+                    &mut match crate::POOL.get().await {
+                        Ok(pool) => pool,
+                        Err(e) => {
+                            log::error!("error connecting to backend: {}", e);
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&"error connecting to backend"),
+                            );
+                        }
+                    },
+                    &disc,
+                    post,
+                    token.member_hash(),
+                    ban.reason,
+                )
+                .await
+                {
+                    Ok(ban) => {
+                        Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&ban))
+                    }
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            }
+        });
+
     // POST /file - uploads a file and returns the ID so you can claim it for a post
 
     let uploadfile = warp::path!("api" / "v1" / "file")
@@ -452,22 +861,12 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
         .and_then({
             |mut form: warp::multipart::FormData, mut token: Token| async move {
                 use futures::TryStreamExt;
-                {
-                    if crate::UNCLAIMED_FILES
-                        .lock()
-                        .await
-                        .has_pending(token.member_hash())
-                    {
-                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
-                            warp::reply::json(&"You already have a file pending"),
-                        );
-                    }
-                }
                 while let Ok(Some(p)) = form.try_next().await {
                     if p.name() == "file" {
                         use bytes::BufMut;
                         let fct;
                         let fext;
+                        let fname = p.filename().map(|s| s.to_string());
                         {
                             let content_type = p.content_type().map(|s| s.to_string());
                             match content_type {
@@ -505,16 +904,38 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
                             }
                         };
 
-                        return match crate::UNCLAIMED_FILES
-                            .lock()
-                            .await
+                        let mut conn = match crate::POOL.get().await {
+                            Ok(pool) => pool,
+                            Err(e) => {
+                                log::error!("error connecting to backend: {}", e);
+                                return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                    warp::reply::json(&"error connecting to backend"),
+                                );
+                            }
+                        };
+                        let mut unclaimed = crate::UNCLAIMED_FILES.lock().await;
+                        if let Err(e) = crate::quotas::check_member_quota(
+                            &mut conn,
+                            &unclaimed,
+                            token.member_hash(),
+                            value.len() as i64,
+                        )
+                        .await
+                        {
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&e.to_string()),
+                            );
+                        }
+                        drop(conn);
+
+                        let mut builder = File::builder().extension(fext).mimetype(fct).data(value);
+                        if let Some(fname) = fname {
+                            builder = builder.filename(&fname);
+                        }
+
+                        return match unclaimed
                             .add_file(
-                                File::builder()
-                                    .extension(fext)
-                                    .mimetype(fct)
-                                    .data(value)
-                                    .build()
-                                    .expect("failed to build file response"),
+                                builder.build().expect("failed to build file response"),
                                 token.member_hash(),
                             )
                             .await
@@ -533,53 +954,134 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
-    // PUT /share/{file_path} (will continue as something like /files/mimetype/id.type which we want to capture) - generates a path for a file to be shared that includes the FileSig query parameters
+    // GET /file - lists the user's uploaded files that haven't been claimed by a post yet
 
-    let sharefile = warp::path!("api" / "v1" / "share" / "files" / ..)
-        .and(warp::path::tail())
-        .and(warp::put())
+    let pendingfiles = warp::path!("api" / "v1" / "file")
+        .and(warp::get())
         .and(valid_token())
-        .and_then({
-            |tail: warp::path::Tail, _: Token| async move {
+        .and_then(|mut token: Token| async move {
+            match crate::UNCLAIMED_FILES
+                .lock()
+                .await
+                .pending_uploads(token.member_hash())
+            {
+                Ok(uploads) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&uploads))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // DELETE /file/{id} - discards one of the user's pending uploads
+
+    let cancelfile = warp::path!("api" / "v1" / "file" / String)
+        .and(warp::delete())
+        .and(valid_token())
+        .and_then(|id: String, mut token: Token| async move {
+            match crate::UNCLAIMED_FILES
+                .lock()
+                .await
+                .cancel_upload(&id, token.member_hash())
+            {
+                Ok(_) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &"OK".to_owned(),
+                )),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // PUT /share/{file_path} (will continue as something like /files/mimetype/id.type which we want to capture) - creates a share link for the file and returns the path including the FileSig query parameters
+
+    let sharefile = warp::path!("api" / "v1" / "share" / "files" / ..)
+        .and(warp::path::tail())
+        .and(warp::put())
+        .and(warp::body::json::<Option<CreateShareLink>>())
+        .and(valid_token())
+        .and_then({
+            |tail: warp::path::Tail, options: Option<CreateShareLink>, mut token: Token| async move {
                 let file = format!("/files/{}", tail.as_str());
-                // ensure the file exists
                 log::trace!("file: {}", file);
-                let fileinfo = match crate::database_bindings::Database::get_file(
-                    &file,
-                    &mut match crate::POOL.get().await {
-                        Ok(pool) => pool,
-                        Err(e) => {
-                            log::error!("error connecting to backend: {}", e);
-                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
-                                warp::reply::json(&"error connecting to backend"),
-                            );
-                        }
-                    },
-                )
-                .await
-                {
-                    Ok(file) => file,
-                    Err(_) => {
+                let mut conn = match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
                         return Ok::<warp::reply::Json, warp::reject::Rejection>(
-                            warp::reply::json(&"File not found"),
+                            warp::reply::json(&"error connecting to backend"),
                         );
                     }
                 };
 
-                if fileinfo.board.private {
+                match crate::sharelinks::create_share_link(
+                    &mut conn,
+                    &file,
+                    token.member_hash(),
+                    options.unwrap_or_default(),
+                )
+                .await
+                {
+                    Ok(link) => Ok::<warp::reply::Json, warp::reject::Rejection>(
+                        warp::reply::json(&link.url),
+                    ),
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(
+                        warp::reply::json(&e.to_string()),
+                    ),
+                }
+            }
+        });
+
+    // GET /share - lists the share links the user has created that haven't expired
+
+    let listsharelinks = warp::path!("api" / "v1" / "share")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|mut token: Token| async move {
+            let mut conn = match crate::POOL.get().await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    log::error!("error connecting to backend: {}", e);
                     return Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
-                        &"Cannot share private file",
+                        &"error connecting to backend",
                     ));
                 }
+            };
 
-                // generate a share link
-                let crate::filters::FileSig { bgn, exp, sig } =
-                    crate::filters::FileSig::generate(&file).await;
+            match crate::sharelinks::list_share_links(&mut conn, token.member_hash()).await {
+                Ok(links) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&links))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
 
-                Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&format!(
-                    "{}?bgn={}&exp={}&sig={}",
-                    file, bgn, exp, sig
-                )))
+    // DELETE /share/{id} - revokes one of the user's share links
+
+    let revokesharelink = warp::path!("api" / "v1" / "share" / i64)
+        .and(warp::delete())
+        .and(valid_token())
+        .and_then(|id: i64, mut token: Token| async move {
+            let mut conn = match crate::POOL.get().await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    log::error!("error connecting to backend: {}", e);
+                    return Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &"error connecting to backend",
+                    ));
+                }
+            };
+
+            match crate::sharelinks::revoke_share_link(&mut conn, id, token.member_hash()).await {
+                Ok(()) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &"OK".to_owned(),
+                )),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
             }
         });
 
@@ -694,6 +1196,364 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
+    // GET /api/v1/watching - returns every thread the user watches with how many posts they haven't seen
+
+    let list_watching = warp::path!("api" / "v1" / "watching")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|mut token: Token| async move {
+            match crate::watching::list(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /api/v1/watching/unwatch - stops watching every thread in the body, returns the remaining ones
+
+    let bulk_unwatch = warp::path!("api" / "v1" / "watching" / "unwatch")
+        .and(warp::post())
+        .and(valid_token())
+        .and(warp::body::json::<Vec<ThreadRef>>())
+        .and_then(|mut token: Token, threads: Vec<ThreadRef>| async move {
+            match crate::watching::unwatch(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                threads,
+                token.member_hash(),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /api/v1/board/{board_discriminator}/post/{post_number}/seen - marks a watched thread as read up to its newest post
+
+    let mark_seen = warp::path!("api" / "v1" / "board" / String / "post" / i64 / "seen")
+        .and(warp::post())
+        .and(valid_token())
+        .and_then(|disc: String, post: i64, mut token: Token| async move {
+            match crate::watching::mark_seen(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                &disc,
+                post,
+                token.member_hash(),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /api/v1/board/{board_discriminator}/post/{post_number}/muted - returns true if the thread is watched without web pushes
+
+    let get_muted = warp::path!("api" / "v1" / "board" / String / "post" / i64 / "muted")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then({
+            |disc: String, post: i64, mut token: Token| async move {
+                match crate::preferences::get_muted(
+                    &mut match crate::POOL.get().await {
+                        Ok(pool) => pool,
+                        Err(e) => {
+                            log::error!("error connecting to backend: {}", e);
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&"error connecting to backend"),
+                            );
+                        }
+                    },
+                    &disc,
+                    post,
+                    token.member_hash(),
+                )
+                .await
+                {
+                    Ok(muted) => {
+                        Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&muted))
+                    }
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            }
+        });
+
+    // PUT /api/v1/board/{board_discriminator}/post/{post_number}/muted - mutes or unmutes web pushes for the thread
+
+    let put_muted = warp::path!("api" / "v1" / "board" / String / "post" / i64 / "muted")
+        .and(warp::put())
+        .and(valid_token())
+        .and(warp::body::json::<bool>())
+        .and_then({
+            |disc: String, post: i64, mut token: Token, muted: bool| async move {
+                match crate::preferences::set_muted(
+                    &mut match crate::POOL.get().await {
+                        Ok(pool) => pool,
+                        Err(e) => {
+                            log::error!("error connecting to backend: {}", e);
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&"error connecting to backend"),
+                            );
+                        }
+                    },
+                    &disc,
+                    post,
+                    token.member_hash(),
+                    muted,
+                )
+                .await
+                {
+                    Ok(v) => {
+                        Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v))
+                    }
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            }
+        });
+
+    // GET /api/v1/preferences/notifications - returns the user's notification preferences
+
+    let get_preferences = warp::path!("api" / "v1" / "preferences" / "notifications")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|mut token: Token| async move {
+            match crate::preferences::get_preferences(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+            )
+            .await
+            {
+                Ok(prefs) => {
+                    Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&prefs))
+                }
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // PUT /api/v1/preferences/notifications - replaces the user's notification preferences
+
+    let put_preferences = warp::path!("api" / "v1" / "preferences" / "notifications")
+        .and(warp::put())
+        .and(valid_token())
+        .and(warp::body::json::<NotificationPreferences>())
+        .and_then(
+            |mut token: Token, prefs: NotificationPreferences| async move {
+                match crate::preferences::set_preferences(
+                    &mut match crate::POOL.get().await {
+                        Ok(pool) => pool,
+                        Err(e) => {
+                            log::error!("error connecting to backend: {}", e);
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&"error connecting to backend"),
+                            );
+                        }
+                    },
+                    token.member_hash(),
+                    prefs,
+                )
+                .await
+                {
+                    Ok(prefs) => {
+                        Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&prefs))
+                    }
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            },
+        );
+
+    // GET /api/v1/inbox?before=i64&limit=i64&unread_only=bool - returns the user's notifications, newest first
+
+    let get_inbox = warp::path!("api" / "v1" / "inbox")
+        .and(warp::get())
+        .and(valid_token())
+        .and(warp::query::<InboxQuery>())
+        .and_then(|mut token: Token, query: InboxQuery| async move {
+            match crate::inbox::get_inbox(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+                query.before,
+                query.limit,
+                query.unread_only,
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /api/v1/search?q=String&board=String&after=i64&before=i64&has_file=bool&author=String&threads_only=bool&page=i64 - searches posts and topics on every board the user can see, newest first
+
+    let search = warp::path!("api" / "v1" / "search")
+        .and(warp::get())
+        .and(valid_token())
+        .and(warp::query::<SearchQuery>())
+        .and_then(|mut token: Token, query: SearchQuery| async move {
+            match crate::search::search(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+                query,
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /api/v1/inbox/unread - returns how many of the user's notifications are unread
+
+    let get_unread = warp::path!("api" / "v1" / "inbox" / "unread")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|mut token: Token| async move {
+            match crate::inbox::unread(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /api/v1/inbox/{id}/read - marks one notification as read, returns the unread count
+
+    let mark_read = warp::path!("api" / "v1" / "inbox" / i64 / "read")
+        .and(warp::post())
+        .and(valid_token())
+        .and_then(|id: i64, mut token: Token| async move {
+            match crate::inbox::mark_read(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+                Some(id),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // POST /api/v1/inbox/read - marks all of the user's notifications as read, returns the unread count
+
+    let mark_all_read = warp::path!("api" / "v1" / "inbox" / "read")
+        .and(warp::post())
+        .and(valid_token())
+        .and_then(|mut token: Token| async move {
+            match crate::inbox::mark_read(
+                &mut match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                token.member_hash(),
+                None,
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
     // PUT /api/v1/board/{board_discriminator}/invite?info=string - creates an invite code for the board
 
     let create_invite = warp::path!("api" / "v1" / "board" / String / "invite")
@@ -834,24 +1694,136 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             }
         });
 
+    // POST /api/v1/export/{board_discriminator}/{thread_id} - exports a thread as a zip, waiting a few seconds for it to finish
+
+    let exportthread = warp::path!("api" / "v1" / "export" / String / i64)
+        .and(warp::post())
+        .and(valid_token())
+        .and_then(
+            |discriminator: String, number: i64, mut token: Token| async move {
+                match crate::exports::start(
+                    match crate::POOL.get().await {
+                        Ok(pool) => pool,
+                        Err(e) => {
+                            log::error!("error connecting to backend: {}", e);
+                            return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                                warp::reply::json(&"error connecting to backend"),
+                            );
+                        }
+                    },
+                    crate::exports::ExportTarget::Thread(discriminator, number),
+                    Some(token.member_hash().database_hash().to_string()),
+                )
+                .await
+                {
+                    Ok(v) => {
+                        Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v))
+                    }
+                    Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                        &e.to_string(),
+                    )),
+                }
+            },
+        );
+
+    // POST /api/v1/export/{board_discriminator} - exports every thread on a board, including archived ones, as a zip (moderators only)
+
+    let exportboard = warp::path!("api" / "v1" / "export" / String)
+        .and(warp::post())
+        .and(valid_token())
+        .and_then(|discriminator: String, mut token: Token| async move {
+            match crate::exports::start(
+                match crate::POOL.get().await {
+                    Ok(pool) => pool,
+                    Err(e) => {
+                        log::error!("error connecting to backend: {}", e);
+                        return Ok::<warp::reply::Json, warp::reject::Rejection>(
+                            warp::reply::json(&"error connecting to backend"),
+                        );
+                    }
+                },
+                crate::exports::ExportTarget::Board(discriminator),
+                Some(token.member_hash().database_hash().to_string()),
+            )
+            .await
+            {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /api/v1/exports/{id} - how an export is getting on, with a download link once it's ready
+
+    let exportstatus = warp::path!("api" / "v1" / "exports" / String)
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|id: String, mut token: Token| async move {
+            let owner = token.member_hash().database_hash();
+            match crate::exports::status(&id, Some(owner.as_str())).await {
+                Ok(v) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(&v)),
+                Err(e) => Ok::<warp::reply::Json, warp::reject::Rejection>(warp::reply::json(
+                    &e.to_string(),
+                )),
+            }
+        });
+
+    // GET /api/v1/exports/{id}/download - the finished zip
+
+    let exportdownload = warp::path!("api" / "v1" / "exports" / String / "download")
+        .and(warp::get())
+        .and(valid_token())
+        .and_then(|id: String, mut token: Token| async move {
+            let owner = token.member_hash().database_hash();
+            match crate::exports::download(&id, Some(owner.as_str())).await {
+                Ok(resp) => Ok::<warp::reply::Response, warp::reject::Rejection>(resp),
+                Err(e) => Ok::<warp::reply::Response, warp::reject::Rejection>(
+                    warp::reply::json(&e.to_string()).into_response(),
+                ),
+            }
+        });
+
     crate::filters::ratelimit()
         .and(
             getpost
                 .or(getbanner)
                 .or(deletepost)
+                .or(banpostfile)
                 .or(postinthread)
                 .or(getthread)
                 .or(postthread)
                 .or(getboard)
+                .or(getcatalog)
                 .or(getboards)
                 .or(uploadfile)
+                .or(pendingfiles)
+                .or(cancelfile)
                 .or(sharefile)
+                .or(listsharelinks)
+                .or(revokesharelink)
                 .or(gettoken)
                 .or(get_watching)
                 .or(put_watching)
+                .or(list_watching)
+                .or(bulk_unwatch)
+                .or(mark_seen)
+                .or(get_muted)
+                .or(put_muted)
+                .or(get_preferences)
+                .or(put_preferences)
+                .or(get_inbox)
+                .or(get_unread)
+                .or(mark_read)
+                .or(mark_all_read)
+                .or(search)
                 .or(create_invite)
                 .or(create_moderator)
                 .or(consume_code)
+                .or(exportthread)
+                .or(exportboard)
+                .or(exportstatus)
+                .or(exportdownload)
                 .or(notifications()),
         )
         .recover(|err: Rejection| async move {
@@ -877,6 +1849,53 @@ pub fn api_endpoints() -> impl Filter<Extract = (impl warp::Reply,), Error = war
         })
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateFileBan {
+    pub path: String,
+    // omit to ban the file on every board
+    pub board: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanReason {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardQuery {
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboxQuery {
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub unread_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub board: Option<String>,
+    // unix timestamps in seconds
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+    pub has_file: Option<bool>,
+    pub author: Option<String>,
+    #[serde(default)]
+    pub threads_only: bool,
+    pub page: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InviteCodeHolder {
     pub info: String,
@@ -888,30 +1907,87 @@ pub fn notifications() -> impl Filter<Extract = (impl warp::Reply,), Error = war
 
     let pushnotifs = warp::path!("api" / "v1" / "notifications")
         .and(valid_token())
-        .and_then(|mut token: Token| async move {
+        .and(last_event_id())
+        .and_then(|mut token: Token, last_event_id: Option<u64>| async move {
             Ok::<_, warp::reject::Rejection>(warp::sse::reply(warp::sse::keep_alive().stream({
                 crate::PUSH_NOTIFS
                     .lock()
                     .await
-                    .subscribe(&token.member_hash().database_hash())
+                    .subscribe(&token.member_hash().database_hash(), last_event_id)
                     .await
             })))
         });
 
-    // GET /push/thread/{number} - temporary SSE endpoint to listen for specific thread updates
+    // GET /board/{discriminator}/thread/{number}/notifications - SSE endpoint to listen for specific thread updates
 
     let threadnotifs = warp::path!(
         "api" / "v1" / "board" / String / "thread" / i64 / "notifications"
     )
-    .and_then(|board: String, thread: i64| async move {
-        Ok::<_, warp::reject::Rejection>(warp::sse::reply(warp::sse::keep_alive().stream({
-            crate::PUSH_NOTIFS
-                .lock()
+    .and(valid_token())
+    .and(last_event_id())
+    .and_then(
+        |board: String, thread: i64, mut token: Token, last_event_id: Option<u64>| async move {
+            let mut conn = crate::POOL
+                .get()
                 .await
-                .subscribe(&format!("board: {} | thread: {}", board, thread))
+                .map_err(|_| warp::reject::reject())?;
+            // private boards only stream to members that could read the board anyway
+            crate::database_bindings::Database::get_board(&mut conn, &board, token.member_hash())
                 .await
-        })))
-    });
+                .map_err(|_| warp::reject::reject())?;
+            drop(conn);
+
+            Ok::<_, warp::reject::Rejection>(warp::sse::reply(warp::sse::keep_alive().stream({
+                crate::PUSH_NOTIFS
+                    .lock()
+                    .await
+                    .subscribe(&crate::push::thread_ident(&board, thread), last_event_id)
+                    .await
+            })))
+        },
+    );
+
+    // GET /board/{discriminator}/notifications - SSE endpoint to listen for new threads, bumps, deletions and thread status changes on a board
+
+    let boardnotifs = warp::path!("api" / "v1" / "board" / String / "notifications")
+        .and(valid_token())
+        .and(last_event_id())
+        .and_then(
+            |board: String, mut token: Token, last_event_id: Option<u64>| async move {
+                let mut conn = crate::POOL
+                    .get()
+                    .await
+                    .map_err(|_| warp::reject::reject())?;
+                crate::database_bindings::Database::get_board(
+                    &mut conn,
+                    &board,
+                    token.member_hash(),
+                )
+                .await
+                .map_err(|_| warp::reject::reject())?;
+                drop(conn);
+
+                Ok::<_, warp::reject::Rejection>(warp::sse::reply(warp::sse::keep_alive().stream(
+                    {
+                        crate::PUSH_NOTIFS
+                            .lock()
+                            .await
+                            .subscribe(&crate::push::board_ident(&board), last_event_id)
+                            .await
+                    },
+                )))
+            },
+        );
+
+    // GET /ws - websocket carrying the same events as the SSE endpoints, streams are picked with subscribe messages
+
+    let websocket = warp::path!("api" / "v1" / "ws")
+        .and(warp::ws())
+        .and(valid_token())
+        .map(|ws: warp::ws::Ws, mut token: Token| {
+            let member = token.member_hash();
+            ws.on_upgrade(move |socket| crate::websocket::handle(socket, member))
+        });
 
     // POST /subscribe - sets the user's push notification url
 
@@ -948,6 +2024,7 @@ pub fn notifications() -> impl Filter<Extract = (impl warp::Reply,), Error = war
 
     pushnotifs
         .or(threadnotifs)
+        .or(boardnotifs)
         .map(|reply| {
             warp::reply::with_header(
                 warp::reply::with_header(
@@ -960,6 +2037,7 @@ pub fn notifications() -> impl Filter<Extract = (impl warp::Reply,), Error = war
             )
         })
         .or(subscribble)
+        .or(websocket)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
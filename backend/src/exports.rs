@@ -0,0 +1,593 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write as _,
+};
+
+use anyhow::{anyhow, Result};
+use async_stream::stream;
+use common::structs::{ExportState, ExportStatus, SafeBoard, SafePost, ThreadWithPosts, User};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use tokio::io::AsyncReadExt as _;
+use warp::{http::HeaderValue, Reply as _};
+
+use crate::database_bindings::Database;
+
+// a thread or a whole board packed into a zip: the json the api would return, every file and thumbnail
+// the posts show, and html pages that work without the site. jobs are only kept in memory, so archives
+// left behind by a previous run are cleared the first time exports are touched
+
+pub enum ExportTarget {
+    Thread(String, i64),
+    Board(String),
+}
+
+struct ExportJob {
+    status: ExportStatus,
+    // the member's database hash, None for exports started through the admin api
+    owner: Option<String>,
+}
+
+pub struct Exports {
+    jobs: HashMap<String, ExportJob>,
+}
+
+impl Exports {
+    pub fn new() -> Self {
+        // EXPORT_PATH can point anywhere, so only our own archives are removed
+        match std::fs::read_dir(&*crate::statics::EXPORT_PATH) {
+            Ok(entries) => {
+                for path in entries.flatten().map(|entry| entry.path()) {
+                    let name = path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or_default();
+                    if !is_archive_name(name) {
+                        continue;
+                    }
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        log::error!("Error clearing old export {}: {e}", path.display());
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::error!("Error clearing old exports: {e}"),
+        }
+        Self {
+            jobs: HashMap::new(),
+        }
+    }
+
+    // admins (no owner) can see every export, members only their own
+    fn get(&self, id: &str, owner: Option<&str>) -> Result<&ExportJob> {
+        self.jobs
+            .get(id)
+            .filter(|job| owner.is_none() || job.owner.as_deref() == owner)
+            .ok_or_else(|| anyhow!("Export not found"))
+    }
+}
+
+impl Default for Exports {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// takes the connection so it can go back to the pool before waiting on the job
+pub async fn start(
+    mut conn: Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    target: ExportTarget,
+    owner: Option<String>,
+) -> Result<ExportStatus> {
+    let hash = owner
+        .clone()
+        .unwrap_or_else(|| env!("SUPER_SECRET_CODE").to_owned());
+
+    // the same checks get_thread and get_board make, before anything is queued
+    let (board, threads, name, whole_board) = match target {
+        ExportTarget::Thread(disc, number) => {
+            let board = allowed_board(&mut conn, &disc, &hash).await?;
+            let thread = Database::get_raw_thread(&mut conn, &disc, number).await?;
+            (board, vec![thread], format!("{disc}-{number}.zip"), false)
+        }
+        ExportTarget::Board(disc) => {
+            let board = allowed_board(&mut conn, &disc, &hash).await?;
+            // a whole board is a lot of disk and work, so it's left to the people running it
+            if owner.is_some()
+                && database::permission_level(&mut conn, board.id, &hash)
+                    .await?
+                    .is_none()
+            {
+                return Err(anyhow!("Only moderators can export a whole board"));
+            }
+            let threads = database::get_board_threads(&mut conn, board.id).await?;
+            (board, threads, format!("{disc}.zip"), true)
+        }
+    };
+    drop(conn);
+
+    let id = nanoid::nanoid!();
+    let now = unix_now();
+    {
+        let mut exports = crate::EXPORTS.lock().await;
+        if owner.is_some()
+            && exports
+                .jobs
+                .values()
+                .any(|job| job.owner == owner && job.status.state == ExportState::Pending)
+        {
+            return Err(anyhow!("You already have an export running"));
+        }
+        if exports
+            .jobs
+            .values()
+            .filter(|job| job.status.state == ExportState::Pending)
+            .count()
+            >= crate::statics::MAX_RUNNING_EXPORTS
+        {
+            return Err(anyhow!("Too many exports are running, try again later"));
+        }
+        exports.jobs.insert(
+            id.clone(),
+            ExportJob {
+                status: ExportStatus {
+                    id: id.clone(),
+                    state: ExportState::Pending,
+                    name,
+                    download: None,
+                    error: None,
+                    size: None,
+                    created_at: now,
+                    expires_at: now + *crate::statics::EXPORT_RETENTION,
+                },
+                owner: owner.clone(),
+            },
+        );
+    }
+
+    let job = {
+        let id = id.clone();
+        tokio::spawn(async move {
+            let result = build(&id, board, threads, whole_board, &hash).await;
+            finish(&id, result).await;
+        })
+    };
+    // a single thread is usually done by now, anything bigger keeps going and is polled for
+    let _ = tokio::time::timeout(
+        std::time::Duration::from_secs(crate::statics::EXPORT_WAIT_SECONDS),
+        job,
+    )
+    .await;
+
+    status(&id, owner.as_deref()).await
+}
+
+pub async fn status(id: &str, owner: Option<&str>) -> Result<ExportStatus> {
+    let exports = crate::EXPORTS.lock().await;
+    let job = exports.get(id, owner)?;
+    let mut status = job.status.clone();
+    if status.state == ExportState::Ready {
+        // the link goes through whichever api asked, an admin can't use a member's route
+        status.download = Some(match owner {
+            Some(_) => format!("/api/v1/exports/{id}/download"),
+            None => format!("/api/v1/admin/exports/{id}/download"),
+        });
+    }
+    Ok(status)
+}
+
+pub async fn download(id: &str, owner: Option<&str>) -> Result<warp::reply::Response> {
+    let name = {
+        let exports = crate::EXPORTS.lock().await;
+        let job = exports.get(id, owner)?;
+        if job.status.state != ExportState::Ready {
+            return Err(anyhow!("Export is not ready"));
+        }
+        job.status.name.clone()
+    };
+
+    let mut file = tokio::fs::File::open(archive_path(id)).await?;
+    let size = file.metadata().await?.len();
+    // streamed in chunks, a whole board can be far too big to hold in memory
+    let body = stream! {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => yield Ok(bytes::Bytes::copy_from_slice(&buf[..n])),
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    };
+
+    let mut resp = warp::hyper::Body::wrap_stream(body).into_response();
+    let headers = resp.headers_mut();
+    headers.insert(
+        warp::http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(warp::http::header::CONTENT_LENGTH, HeaderValue::from(size));
+    headers.insert(
+        warp::http::header::CONTENT_DISPOSITION,
+        crate::content_disposition("attachment", Some(&name)),
+    );
+    Ok(resp)
+}
+
+// drops finished exports once they're past EXPORT_RETENTION
+pub async fn expire() {
+    let now = unix_now();
+    let expired = {
+        let mut exports = crate::EXPORTS.lock().await;
+        let expired = exports
+            .jobs
+            .iter()
+            .filter(|(_, job)| {
+                job.status.state != ExportState::Pending && job.status.expires_at <= now
+            })
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<String>>();
+        for id in &expired {
+            exports.jobs.remove(id);
+        }
+        expired
+    };
+
+    for id in expired {
+        match tokio::fs::remove_file(archive_path(&id)).await {
+            Ok(_) => log::trace!("Removed expired export {id}"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::error!("Error removing expired export {id}: {e}"),
+        }
+    }
+}
+
+async fn allowed_board(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    disc: &str,
+    hash: &str,
+) -> Result<database::Board> {
+    let board = Database::get_raw_board(conn, disc).await?;
+    if !database::check_access(conn, hash, board.id).await? {
+        return Err(anyhow!("Not authorized to view board"));
+    }
+    Ok(board)
+}
+
+async fn finish(id: &str, result: Result<u64>) {
+    let now = unix_now();
+    let mut exports = crate::EXPORTS.lock().await;
+    let Some(job) = exports.jobs.get_mut(id) else {
+        return;
+    };
+    match result {
+        Ok(size) => {
+            job.status.state = ExportState::Ready;
+            job.status.size = Some(size);
+        }
+        Err(e) => {
+            log::error!("Error exporting {}: {e}", job.status.name);
+            job.status.state = ExportState::Failed;
+            job.status.error = Some(e.to_string());
+        }
+    }
+    // kept for the full retention from when it could first be downloaded
+    job.status.expires_at = now + *crate::statics::EXPORT_RETENTION;
+}
+
+async fn build(
+    id: &str,
+    board: database::Board,
+    threads: Vec<database::Thread>,
+    whole_board: bool,
+    hash: &str,
+) -> Result<u64> {
+    let mut conn = crate::POOL
+        .get()
+        .await
+        .map_err(|e| anyhow!("error connecting to backend: {e}"))?;
+    let mut loaded = Vec::with_capacity(threads.len());
+    for thread in threads {
+        loaded.push(thread.with_posts(&mut conn, hash).await?);
+    }
+    drop(conn);
+
+    let info = SafeBoard {
+        name: board.name,
+        discriminator: board.discriminator,
+        private: board.private,
+    };
+
+    // a thread export is just the thread, a board export lists its threads and keeps each in threads/
+    let mut entries = vec![];
+    if whole_board {
+        entries.push(("board.json".to_owned(), serde_json::to_vec_pretty(&info)?));
+        entries.push((
+            "index.html".to_owned(),
+            render_board(&info, &loaded).into_bytes(),
+        ));
+        for thread in &loaded {
+            let number = thread.thread_post.post_number;
+            entries.push((
+                format!("threads/{number}.json"),
+                serde_json::to_vec_pretty(thread)?,
+            ));
+            entries.push((
+                format!("threads/{number}.html"),
+                render_thread(&info, thread, "../", Some("../index.html")).into_bytes(),
+            ));
+        }
+    } else {
+        for thread in &loaded {
+            entries.push(("thread.json".to_owned(), serde_json::to_vec_pretty(thread)?));
+            entries.push((
+                "index.html".to_owned(),
+                render_thread(&info, thread, "", None).into_bytes(),
+            ));
+        }
+    }
+
+    let files = loaded
+        .iter()
+        .flat_map(|thread| std::iter::once(&thread.thread_post).chain(thread.posts.iter()))
+        .filter_map(|post| post.file.as_ref())
+        .flat_map(|file| [file.claimed.path.clone(), file.claimed.thumbnail.clone()])
+        .filter(|path| !path.is_empty())
+        .collect::<BTreeSet<String>>();
+
+    let target = archive_path(id);
+    tokio::task::spawn_blocking(move || write_archive(&target, entries, files)).await?
+}
+
+fn write_archive(
+    target: &str,
+    entries: Vec<(String, Vec<u8>)>,
+    files: BTreeSet<String>,
+) -> Result<u64> {
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    std::fs::create_dir_all(&*crate::statics::EXPORT_PATH)?;
+    // written under another name first so a half written archive is never served
+    let partial = format!("{target}.part");
+    let mut zip = ZipWriter::new(std::fs::File::create(&partial)?);
+    let text = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // uploads are mostly compressed already, and can be bigger than plain zip allows
+    let media = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+
+    for (name, data) in entries {
+        zip.start_file(name, text)?;
+        zip.write_all(&data)?;
+    }
+
+    for path in files {
+        if path.contains("..") {
+            log::warn!("Export skipped suspicious path {path}");
+            continue;
+        }
+        // spoiler images can come with the frontend rather than from uploads
+        let Some(mut file) = [env!("FILE_STORAGE_PATH"), env!("DISTRIBUTION_PATH")]
+            .iter()
+            .find_map(|dir| std::fs::File::open(format!("{dir}{path}")).ok())
+        else {
+            log::warn!("File {path} is referenced in an export but missing from disk");
+            continue;
+        };
+        zip.start_file(path.trim_start_matches('/'), media)?;
+        std::io::copy(&mut file, &mut zip)?;
+    }
+
+    zip.finish()?;
+    std::fs::rename(&partial, target)?;
+    Ok(std::fs::metadata(target)?.len())
+}
+
+// {nanoid}.zip or {nanoid}.zip.part, anything else in EXPORT_PATH isn't ours
+fn is_archive_name(name: &str) -> bool {
+    let id = name
+        .strip_suffix(".zip.part")
+        .or_else(|| name.strip_suffix(".zip"))
+        .unwrap_or_default();
+    id.len() == 21
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn archive_path(id: &str) -> String {
+    format!("{}/{id}.zip", *crate::statics::EXPORT_PATH)
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+const STYLE: &str = "body{font-family:sans-serif;background:#1d1f21;color:#c5c8c6;margin:1rem}\
+a{color:#81a2be}.post{border:1px solid #373b41;margin:0.5rem 0;padding:0.5rem;overflow:auto}\
+.post-header{font-size:0.9em;margin-bottom:0.3rem}.author{font-weight:bold}.mod{color:#cc6666}\
+.sage{opacity:0.7;font-size:0.8em}.file{float:left;margin:0 0.8rem 0.3rem 0}.file img{max-width:200px;max-height:200px}\
+.file-name{display:block;font-size:0.8em}.content{white-space:pre-wrap;word-wrap:break-word}\
+.greentext{color:#b5bd68}.replies{clear:both;font-size:0.8em;margin-top:0.3rem}.archived{color:#f0c674}";
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+fn render_board(board: &SafeBoard, threads: &[ThreadWithPosts]) -> String {
+    let mut body = format!(
+        "<h1>/{}/ - {}</h1>\n<ul>\n",
+        escape(&board.discriminator),
+        escape(&board.name)
+    );
+    for thread in threads {
+        let number = thread.thread_post.post_number;
+        body.push_str(&format!(
+            "<li><a href=\"threads/{number}.html\">{}</a> - {} replies{}</li>\n",
+            escape(&thread.topic),
+            thread.post_count,
+            if thread.archived { " (archived)" } else { "" }
+        ));
+    }
+    body.push_str("</ul>\n");
+    page(
+        &format!("/{}/ - {}", board.discriminator, board.name),
+        &body,
+    )
+}
+
+fn render_thread(
+    board: &SafeBoard,
+    thread: &ThreadWithPosts,
+    root: &str,
+    back: Option<&str>,
+) -> String {
+    let numbers = std::iter::once(&thread.thread_post)
+        .chain(thread.posts.iter())
+        .map(|post| post.post_number)
+        .collect::<HashSet<i64>>();
+
+    let mut body = String::new();
+    if let Some(back) = back {
+        body.push_str(&format!(
+            "<a href=\"{back}\">Back to /{}/</a>\n",
+            escape(&board.discriminator)
+        ));
+    }
+    body.push_str(&format!(
+        "<h1>/{}/ - {}</h1>\n",
+        escape(&board.discriminator),
+        escape(&thread.topic)
+    ));
+    if thread.archived {
+        body.push_str("<p class=\"archived\">This thread is archived</p>\n");
+    }
+    for post in std::iter::once(&thread.thread_post).chain(thread.posts.iter()) {
+        body.push_str(&render_post(post, thread, &numbers, root));
+    }
+    page(
+        &format!("/{}/ - {}", board.discriminator, thread.topic),
+        &body,
+    )
+}
+
+fn render_post(
+    post: &SafePost,
+    thread: &ThreadWithPosts,
+    numbers: &HashSet<i64>,
+    root: &str,
+) -> String {
+    let number = post.post_number;
+    let author = match &post.author {
+        User::Anonymous => "<span class=\"author\">Anonymous</span>".to_owned(),
+        User::Named(name) => format!("<span class=\"author\">{}</span>", escape(name)),
+        User::Mod(name) => format!("<span class=\"author mod\">{} ## MOD</span>", escape(name)),
+    };
+
+    let mut html = format!(
+        "<div class=\"post\" id=\"p{number}\">\n<div class=\"post-header\">{author} {} <a href=\"#p{number}\">No. {number}</a>{}</div>\n",
+        post.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+        if post.sage { " <span class=\"sage\">SAGE</span>" } else { "" }
+    );
+
+    if let Some(file) = &post.file {
+        let path = escape(file.claimed.path.trim_start_matches('/'));
+        let name = file.claimed.original_name.clone().unwrap_or_else(|| {
+            file.claimed
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_owned()
+        });
+        html.push_str(&format!(
+            "<div class=\"file\"><a href=\"{root}{path}\"><img src=\"{root}{}\" alt=\"\" loading=\"lazy\"></a><a class=\"file-name\" href=\"{root}{path}\">{}</a></div>\n",
+            escape(file.claimed.thumbnail.trim_start_matches('/')),
+            escape(&name)
+        ));
+    }
+
+    html.push_str("<div class=\"content\">");
+    let lines = post
+        .content
+        .lines()
+        .map(|line| render_line(line, numbers))
+        .collect::<Vec<String>>();
+    html.push_str(&lines.join("\n"));
+    html.push_str("</div>\n");
+
+    if !post.replies.is_empty() {
+        let replies = post
+            .replies
+            .iter()
+            .map(|reply| {
+                let in_thread = !reply.external
+                    && reply.thread_post_number.as_deref()
+                        == Some(thread.thread_post.post_number.to_string().as_str());
+                if in_thread {
+                    format!(
+                        "<a href=\"#p{0}\">&gt;&gt;{0}</a>",
+                        escape(&reply.post_number)
+                    )
+                } else {
+                    format!(
+                        "&gt;&gt;&gt;/{}/{}",
+                        escape(&reply.board_discriminator),
+                        escape(&reply.post_number)
+                    )
+                }
+            })
+            .collect::<Vec<String>>();
+        html.push_str(&format!(
+            "<div class=\"replies\">Replies: {}</div>\n",
+            replies.join(" ")
+        ));
+    }
+
+    html.push_str("</div>\n");
+    html
+}
+
+// quotes of posts in the same thread stay clickable, everything else is kept as plain text
+fn render_line(line: &str, numbers: &HashSet<i64>) -> String {
+    let words = line
+        .split(' ')
+        .map(|word| {
+            match word
+                .strip_prefix(">>")
+                .and_then(|n| n.parse::<i64>().ok())
+                .filter(|n| numbers.contains(n))
+            {
+                Some(n) => format!("<a href=\"#p{n}\">&gt;&gt;{n}</a>"),
+                None => escape(word),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    if line.starts_with('>') && !line.starts_with(">>") {
+        format!("<span class=\"greentext\">{words}</span>")
+    } else {
+        words
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Result};
+use common::structs::InboxKind;
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use image::DynamicImage;
+use serde::Serialize;
+
+use crate::filters::MemberToken;
+
+#[derive(Debug, Serialize)]
+pub struct FileBanInfo {
+    pub id: i64,
+    pub hash: String,
+    pub phash: Option<String>,
+    // None means the ban applies to every board
+    pub board: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<database::FileBan> for FileBanInfo {
+    fn from(ban: database::FileBan) -> Self {
+        Self {
+            id: ban.id,
+            hash: ban.hash,
+            phash: ban.phash.map(|p| format!("{:016x}", p as u64)),
+            board: ban.board,
+            reason: ban.reason,
+            created_at: ban.created_at.and_utc(),
+        }
+    }
+}
+
+// difference hash, shrinks the image to 9x8 greyscale and records whether each pixel is brighter than its right neighbour
+// survives re-encoding and resizing, which is all we need to catch reuploads
+pub fn perceptual_hash(img: &DynamicImage) -> i64 {
+    let small = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash as i64
+}
+
+fn distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub fn check_hash(bans: &[database::FileBan], hash: &str) -> Result<()> {
+    match bans.iter().find(|ban| ban.hash == hash) {
+        Some(ban) => Err(banned(ban)),
+        None => Ok(()),
+    }
+}
+
+pub fn check_phash(bans: &[database::FileBan], phash: i64) -> Result<()> {
+    match bans.iter().find(|ban| {
+        ban.phash
+            .is_some_and(|p| distance(p, phash) <= *crate::statics::PHASH_DISTANCE)
+    }) {
+        Some(ban) => Err(banned(ban)),
+        None => Ok(()),
+    }
+}
+
+fn banned(ban: &database::FileBan) -> anyhow::Error {
+    match &ban.reason {
+        Some(reason) => anyhow!("This file is banned: {reason}"),
+        None => anyhow!("This file is banned"),
+    }
+}
+
+// bans the stored file at `path` by both of its hashes
+pub async fn ban_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+    board_discriminator: Option<&str>,
+    reason: Option<String>,
+) -> Result<FileBanInfo> {
+    let file = database::get_file_from_path(conn, Some(env!("SUPER_SECRET_CODE").to_owned()), path)
+        .await?
+        .ok_or_else(|| anyhow!("File not found"))?;
+
+    let board = match board_discriminator {
+        Some(discriminator) => Some(
+            crate::database_bindings::Database::get_raw_board(conn, discriminator)
+                .await
+                .map_err(|_| anyhow!("Board not found"))?
+                .id,
+        ),
+        None => None,
+    };
+
+    let ban =
+        database::add_file_ban(conn, &file.claimed.hash, file.claimed.phash, board, reason).await?;
+
+    Ok(ban.into())
+}
+
+// lets board moderators ban whatever file is attached to a post, the ban only applies to their board
+pub async fn ban_post_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    discriminator: &str,
+    number: i64,
+    token: MemberToken,
+    reason: Option<String>,
+) -> Result<FileBanInfo> {
+    let board =
+        crate::database_bindings::Database::get_board(conn, discriminator, token.clone()).await?;
+
+    if database::permission_level(conn, board.id, &token.database_hash())
+        .await?
+        .is_none()
+    {
+        return Err(anyhow!("Not authorized to ban files"));
+    }
+
+    let post =
+        crate::database_bindings::Database::get_raw_post(conn, discriminator, number).await?;
+    let file = database::get_file(conn, Some(env!("SUPER_SECRET_CODE").to_owned()), post.id)
+        .await?
+        .ok_or_else(|| anyhow!("Post has no file"))?;
+
+    let ban = database::add_file_ban(
+        conn,
+        &file.claimed.hash,
+        file.claimed.phash,
+        Some(board.id),
+        reason.clone(),
+    )
+    .await?;
+
+    // the author hears about it unless they banned it themselves
+    let authors = crate::database_bindings::Database::get_post_authors(
+        conn,
+        discriminator,
+        &[post.id],
+        &token.database_hash(),
+    )
+    .await
+    .unwrap_or_default();
+    let notice = crate::inbox::notice(
+        InboxKind::FileBanned,
+        discriminator,
+        database::thread_post_number(post.thread, conn).await?,
+        number,
+        reason.unwrap_or_else(|| crate::inbox::summarize(&post.content)),
+        None,
+    );
+    let author_hashes = authors
+        .iter()
+        .map(|x| x.token_hash.as_str())
+        .collect::<Vec<_>>();
+    if let Err(e) = crate::inbox::record(conn, &author_hashes, &notice).await {
+        log::error!("Error recording inbox notices: {}", e);
+    }
+
+    Ok(ban.into())
+}
+
+pub async fn list_bans(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<FileBanInfo>> {
+    Ok(database::get_all_file_bans(conn)
+        .await?
+        .into_iter()
+        .map(FileBanInfo::from)
+        .collect())
+}
+
+pub async fn remove_ban(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    id: i64,
+) -> Result<()> {
+    database::remove_file_ban(conn, id).await
+}
@@ -0,0 +1,69 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::statics::FILE_CACHE_ENTRIES;
+
+// what answering a request for a stored file needs, by path. the hash never changes for a path, the board
+// settings baked into an entry do, so entries are dropped by gc when it trashes the path and by
+// forget_board when a board's settings change. the oldest entries make room once it's full
+
+pub struct FileCache {
+    // the number is when the path was inserted, so stale spots in `order` can be told apart
+    entries: HashMap<String, (u64, Arc<database::FileCacheInfo>)>,
+    order: VecDeque<(u64, String)>,
+    inserted: u64,
+}
+
+impl FileCache {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            inserted: 0,
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Arc<database::FileCacheInfo>> {
+        self.entries.get(path).map(|(_, info)| Arc::clone(info))
+    }
+
+    pub fn insert(&mut self, path: String, info: Arc<database::FileCacheInfo>) {
+        self.inserted += 1;
+        self.order.push_back((self.inserted, path.clone()));
+        self.entries.insert(path, (self.inserted, info));
+
+        while self.entries.len() > FILE_CACHE_ENTRIES {
+            let Some((inserted, oldest)) = self.order.pop_front() else {
+                break;
+            };
+            if self
+                .entries
+                .get(&oldest)
+                .is_some_and(|(i, _)| *i == inserted)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        // removed and reinserted paths leave spots behind, they're swept once they pile up
+        if self.order.len() > FILE_CACHE_ENTRIES * 2 {
+            let entries = &self.entries;
+            self.order
+                .retain(|(inserted, path)| entries.get(path).is_some_and(|(i, _)| i == inserted));
+        }
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.entries.remove(path);
+    }
+
+    pub fn forget_board(&mut self, board: i64) {
+        self.entries.retain(|_, (_, info)| info.board != board);
+    }
+}
+
+impl Default for FileCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
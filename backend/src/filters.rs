@@ -166,16 +166,40 @@ pub fn optional_file_sig(
             if let MaybeFileSig {
                 bgn: Some(bgn),
                 exp: Some(exp),
+                lnk: Some(lnk),
+                kid: Some(kid),
                 sig: Some(sig),
             } = file_sig
             {
-                Some(FileSig { bgn, exp, sig })
+                Some(FileSig {
+                    bgn,
+                    exp,
+                    lnk,
+                    kid,
+                    sig,
+                })
             } else {
                 None
             }
         })
 }
 
+pub fn last_event_id() -> impl Filter<Extract = (Option<u64>,), Error = warp::Rejection> + Clone {
+    // browsers send the header when they reconnect on their own, the frontend hook uses the query parameter when it has to start a new connection
+    warp::header::optional::<String>("last-event-id")
+        .and(warp::query::query::<MaybeLastEventId>())
+        .map(|header: Option<String>, query: MaybeLastEventId| {
+            header
+                .and_then(|h| h.parse::<u64>().ok())
+                .or(query.last_event_id)
+        })
+}
+
+#[derive(Deserialize)]
+pub struct MaybeLastEventId {
+    pub last_event_id: Option<u64>,
+}
+
 pub fn optional_file(
 ) -> impl Filter<Extract = (Option<warp::fs::File>,), Error = warp::Rejection> + Clone {
     warp::any()
@@ -185,15 +209,42 @@ pub fn optional_file(
         .unify()
 }
 
+// no range at all is a request for the whole file
+fn covers_first_byte(range: Option<&str>) -> bool {
+    range.is_none_or(|range| {
+        range
+            .trim()
+            .strip_prefix("bytes=")
+            .and_then(|ranges| ranges.split(',').next())
+            .and_then(|first| first.trim().split_once('-'))
+            .is_some_and(|(start, _)| start.trim() == "0")
+    })
+}
+
 pub struct FileSig {
     pub bgn: u64,
     pub exp: u64,
+    // id of the share link this signature was issued for, so it can be revoked
+    pub lnk: i64,
+    // which key in the keyring the signature was made with
+    pub kid: u32,
     pub sig: String,
 }
 
 impl FileSig {
     pub async fn validates(&self, path: &str) -> bool {
-        let Self { bgn, exp, sig } = self;
+        self.check(path, true).await
+    }
+
+    // everything but the view limit is still checked for a session that already counted its view
+    async fn check(&self, path: &str, views_left: bool) -> bool {
+        let Self {
+            bgn,
+            exp,
+            lnk,
+            kid,
+            sig,
+        } = self;
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .expect("Time went backwards")
@@ -207,40 +258,115 @@ impl FileSig {
             return false;
         }
 
-        let val = format!("{}|{}|{}", path, bgn, exp);
-
-        let new_sig = crate::statics::BASE64_ENGINE.encode(hmac_sha512::HMAC::mac(
-            val,
-            crate::statics::HMAC_KEY_GENERATOR.get_key().await,
-        ));
+        // a retired or unknown key id never validates
+        if Self::sign(path, *bgn, *exp, *lnk, *kid).await.as_ref() != Some(sig) {
+            return false;
+        }
 
-        new_sig == *sig
+        let mut conn = match crate::POOL.get().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("error connecting to backend: {}", e);
+                return false;
+            }
+        };
+
+        match database::get_share_link(&mut conn, *lnk).await {
+            Ok(Some(link)) => {
+                link.filepath == path
+                    && link.kid as u32 == *kid
+                    && !link.revoked
+                    && !(views_left && link.max_views.is_some_and(|max| link.views >= max))
+            }
+            Ok(None) => false,
+            Err(e) => {
+                log::error!("Error getting share link: {e}");
+                false
+            }
+        }
     }
 
-    #[allow(
-        clippy::panic,
-        reason = "Panics only if the generated FileSig fails to validate, which should never happen"
-    )]
-    pub async fn generate(path: &str) -> Self {
-        let bgn = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs();
-        let exp = bgn + crate::statics::FILE_SHARE_DURATION;
-        let val = format!("{}|{}|{}", path, bgn, exp);
+    // validates and counts a view against the link's view limit
+    pub async fn consume(&self, path: &str) -> bool {
+        if !self.validates(path).await {
+            return false;
+        }
+
+        let mut conn = match crate::POOL.get().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                log::error!("error connecting to backend: {}", e);
+                return false;
+            }
+        };
 
-        let sig = crate::statics::BASE64_ENGINE.encode(hmac_sha512::HMAC::mac(
-            val,
-            crate::statics::HMAC_KEY_GENERATOR.get_key().await,
-        ));
+        database::use_share_link(&mut conn, self.lnk).await.is_ok()
+    }
 
-        let s = Self { bgn, exp, sig };
+    // a view is counted once per link for each client session, the rest of that session's requests
+    // (seeking through a video, reloading the page) ride on it. a session has to start at the first byte,
+    // otherwise ranges that skip it could fetch the whole file without ever using a view
+    // returns the session to hand back to the client, None if the request isn't allowed
+    pub async fn view(
+        &self,
+        path: &str,
+        range: Option<&str>,
+        session: Option<String>,
+    ) -> Option<String> {
+        if let Some(session) = &session {
+            let viewing = crate::SHARE_SESSIONS
+                .lock()
+                .await
+                .get(&(session.clone(), self.lnk))
+                .is_some_and(|started| {
+                    started.elapsed().as_secs() <= crate::statics::SHARE_SESSION_SECONDS
+                });
+            if viewing {
+                return self.check(path, false).await.then(|| session.clone());
+            }
+        }
 
-        if s.validates(path).await {
-            s
-        } else {
-            panic!("Failed to validate generated FileSig");
+        if !covers_first_byte(range) || !self.consume(path).await {
+            return None;
         }
+
+        let session = session.unwrap_or_else(|| nanoid::nanoid!(32));
+        crate::SHARE_SESSIONS
+            .lock()
+            .await
+            .insert((session.clone(), self.lnk), tokio::time::Instant::now());
+        Some(session)
+    }
+
+    async fn sign(path: &str, bgn: u64, exp: u64, lnk: i64, kid: u32) -> Option<String> {
+        let val = format!("{}|{}|{}|{}", path, bgn, exp, lnk);
+        let key = crate::statics::HMAC_KEYRING.get_key(kid).await?;
+
+        Some(crate::statics::BASE64_ENGINE.encode(hmac_sha512::HMAC::mac(val, key)))
+    }
+
+    // signatures are derived from the stored link, so the same link always produces the same url
+    // None once the key the link was signed with has been retired
+    pub async fn for_link(link: &database::ShareLink) -> Option<Self> {
+        let bgn = link.created_at.and_utc().timestamp().max(0) as u64;
+        let exp = link.expires_at.and_utc().timestamp().max(0) as u64;
+        let kid = link.kid as u32;
+        let sig = Self::sign(&link.filepath, bgn, exp, link.id, kid).await?;
+
+        Some(Self {
+            bgn,
+            exp,
+            lnk: link.id,
+            kid,
+            sig,
+        })
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!(
+            "{}?bgn={}&exp={}&lnk={}&kid={}&sig={}",
+            path, self.bgn, self.exp, self.lnk, self.kid, self.sig
+        )
     }
 }
 
@@ -248,6 +374,8 @@ impl FileSig {
 pub struct MaybeFileSig {
     pub bgn: Option<u64>,
     pub exp: Option<u64>,
+    pub lnk: Option<i64>,
+    pub kid: Option<u32>,
     pub sig: Option<String>,
 }
 
@@ -544,4 +672,8 @@ impl MemberToken {
     pub fn database_hash(&self) -> Arc<String> {
         Arc::clone(&self.token)
     }
+    // kept on file rows for upload quotas, it can't be matched to members.token_hash without the salt
+    pub fn quota_hash(&self) -> String {
+        crate::quotas::quota_key(&self.token)
+    }
 }
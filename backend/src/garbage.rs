@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use serde::Serialize;
+
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    // file rows whose post no longer exists, these are removed along with their files
+    pub orphaned_rows: Vec<String>,
+    // files on disk that nothing in the database points at
+    pub unreferenced_paths: Vec<String>,
+    // rows pointing at files that are no longer on disk
+    pub missing_paths: Vec<String>,
+    // trashed files older than the retention period
+    pub purged: Vec<String>,
+    // share links past their expiry, these are only removed outside of a dry run
+    pub expired_share_links: usize,
+    // inbox entries older than INBOX_RETENTION, read or not
+    pub expired_inbox_entries: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrashEntry {
+    pub path: String,
+    pub trashed_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<database::TrashedFile> for TrashEntry {
+    fn from(file: database::TrashedFile) -> Self {
+        let trashed_at = file.trashed_at.and_utc();
+        Self {
+            path: file.filepath,
+            trashed_at,
+            expires_at: trashed_at + retention(),
+        }
+    }
+}
+
+fn retention() -> chrono::Duration {
+    chrono::Duration::seconds(*crate::statics::TRASH_RETENTION as i64)
+}
+
+pub async fn collect_garbage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    dry_run: bool,
+) -> Result<GcReport> {
+    let _lock = crate::FS_LOCK.lock().await;
+    let dir = env!("FILE_STORAGE_PATH");
+
+    let mut report = GcReport {
+        dry_run,
+        ..Default::default()
+    };
+
+    let orphans = database::get_orphaned_files(conn).await?;
+    if !dry_run && !orphans.is_empty() {
+        database::delete_files(conn, orphans.iter().map(|f| f.id).collect()).await?;
+    }
+
+    let mut referenced = database::get_referenced_paths(conn).await?;
+    if dry_run {
+        // the orphaned rows are still in the table during a dry run, so pretend they aren't
+        for file in &orphans {
+            for variant in file
+                .variants
+                .iter()
+                .flat_map(|v| common::structs::FileVariant::from_name(v))
+            {
+                referenced.remove(&format!("{}{}", file.filepath, variant.suffix()));
+            }
+            referenced.remove(&format!("{}-thumb.jpg", file.filepath));
+            referenced.remove(&file.filepath);
+        }
+    }
+    report.orphaned_rows = orphans.into_iter().map(|f| f.filepath).collect();
+
+    let on_disk = get_all_entries(dir)
+        .await?
+        .iter()
+        .flat_map(|x| x.path().to_str().map(|s| s.replacen(dir, "", 1)))
+        .collect::<HashSet<String>>();
+
+    // variants of a file that's still being encoded aren't recorded yet
+    let encoding = crate::ENCODING_FILES.lock().await;
+    report.unreferenced_paths = on_disk
+        .difference(&referenced)
+        .filter(|path| {
+            !common::structs::FileVariant::ALL.iter().any(|variant| {
+                path.strip_suffix(variant.suffix())
+                    .is_some_and(|original| encoding.contains(original))
+            })
+        })
+        .cloned()
+        .collect();
+    drop(encoding);
+    report.unreferenced_paths.sort();
+
+    report.missing_paths = referenced
+        .iter()
+        .filter(|p| p.starts_with("/files/") && !on_disk.contains(*p))
+        .cloned()
+        .collect();
+    report.missing_paths.sort();
+
+    if !dry_run {
+        for path in &report.unreferenced_paths {
+            log::trace!("Trashing: {path}");
+            if let Err(e) = move_to_trash(conn, path).await {
+                log::error!("Error moving {path} to trash: {e}");
+            }
+        }
+    }
+
+    report.purged = purge_trash(conn, dry_run).await?;
+
+    if !dry_run {
+        report.expired_share_links = database::delete_expired_share_links(conn).await?;
+        report.expired_inbox_entries = database::delete_old_inbox_entries(
+            conn,
+            chrono::Utc::now().naive_utc()
+                - chrono::Duration::seconds(*crate::statics::INBOX_RETENTION as i64),
+        )
+        .await?;
+    }
+
+    Ok(report)
+}
+
+async fn move_to_trash(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<()> {
+    let trash_path = format!("{}{}", env!("TRASH_STORAGE_PATH"), path);
+    if let Some(parent) = std::path::Path::new(&trash_path).parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(
+        format!("{}{}", env!("FILE_STORAGE_PATH"), path),
+        &trash_path,
+    )
+    .await?;
+    // the path can be given to a new upload now, which mustn't inherit the old etag
+    crate::FILE_CACHE_INFO.lock().await.remove(path);
+    database::add_trashed_file(conn, path).await?;
+    Ok(())
+}
+
+// permanently deletes everything that has been in the trash for longer than TRASH_RETENTION
+async fn purge_trash(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let cutoff = chrono::Utc::now().naive_utc() - retention();
+    let expired = database::get_expired_trash(conn, cutoff).await?;
+
+    let mut purged = Vec::new();
+    for file in expired {
+        if !dry_run {
+            let trash_path = format!("{}{}", env!("TRASH_STORAGE_PATH"), file.filepath);
+            match tokio::fs::remove_file(&trash_path).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    log::warn!("Trashed file {trash_path} was already gone");
+                }
+                Err(e) => {
+                    log::error!("Error purging {trash_path}: {e}");
+                    continue;
+                }
+            }
+            database::remove_trashed_file(conn, file.id).await?;
+        }
+        purged.push(file.filepath);
+    }
+    Ok(purged)
+}
+
+pub async fn list_trash(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<TrashEntry>> {
+    Ok(database::get_trashed_files(conn)
+        .await?
+        .into_iter()
+        .map(TrashEntry::from)
+        .collect())
+}
+
+pub async fn restore_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<()> {
+    let _lock = crate::FS_LOCK.lock().await;
+
+    let trashed = database::get_trashed_file(conn, path)
+        .await?
+        .ok_or_else(|| anyhow!("File is not in the trash"))?;
+
+    let destination = format!("{}{}", env!("FILE_STORAGE_PATH"), trashed.filepath);
+    if tokio::fs::metadata(&destination).await.is_ok() {
+        return Err(anyhow!("A file already exists at {}", trashed.filepath));
+    }
+    if let Some(parent) = std::path::Path::new(&destination).parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    tokio::fs::rename(
+        format!("{}{}", env!("TRASH_STORAGE_PATH"), trashed.filepath),
+        &destination,
+    )
+    .await?;
+    database::remove_trashed_file(conn, trashed.id).await?;
+    // its files row is gone, without this the next collection would trash it again
+    database::add_restored_file(conn, &trashed.filepath).await?;
+
+    Ok(())
+}
+
+#[async_recursion::async_recursion]
+pub(crate) async fn get_all_entries(dir: &str) -> anyhow::Result<Vec<tokio::fs::DirEntry>> {
+    let mut return_files = Vec::new();
+    let mut files = tokio::fs::read_dir(dir).await?;
+    while let Some(file) = files.next_entry().await? {
+        if file.file_type().await?.is_dir() {
+            return_files
+                .append(&mut get_all_entries(file.path().to_str().unwrap_or_default()).await?);
+        } else {
+            return_files.push(file);
+        }
+    }
+    Ok(return_files)
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use common::structs::{Inbox, InboxItem, InboxKind, PushMessage, SafePost};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::filters::MemberToken;
+
+const SUMMARY_LENGTH: usize = 200;
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+// everything a member gets notified about is kept here, so a fresh page load can show what they missed
+
+pub fn summarize(content: &str) -> String {
+    common::excerpt(content, SUMMARY_LENGTH)
+}
+
+// the id and timestamp are filled in by the database
+pub fn notice(
+    kind: InboxKind,
+    board_discriminator: &str,
+    thread_post_number: i64,
+    post_number: i64,
+    summary: String,
+    thumbnail: Option<String>,
+) -> InboxItem {
+    InboxItem {
+        id: 0,
+        kind,
+        board_discriminator: board_discriminator.to_owned(),
+        thread_post_number,
+        post_number,
+        summary,
+        thumbnail,
+        created_at: 0,
+        read: false,
+    }
+}
+
+pub fn post_notice(kind: InboxKind, post: &SafePost) -> InboxItem {
+    notice(
+        kind,
+        &post.board_discriminator,
+        post.thread_post_number,
+        post.post_number,
+        summarize(&post.content),
+        post.file.as_ref().map(|x| x.claimed.thumbnail.clone()),
+    )
+}
+
+// stores the notice for every recipient and passes it on to the ones that are connected
+pub async fn record(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    recipients: &[&str],
+    notice: &InboxItem,
+) -> Result<()> {
+    let entries = database::add_inbox_entries(conn, recipients, notice).await?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut sse = crate::PUSH_NOTIFS.lock().await;
+    for entry in entries {
+        let recipient = entry.recipient.clone();
+        if let Some(item) = into_item(entry) {
+            sse.send_to(&[&recipient], PushMessage::Inbox(Arc::new(item)));
+        }
+    }
+    Ok(())
+}
+
+pub async fn get_inbox(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+    before: Option<i64>,
+    limit: Option<i64>,
+    unread_only: bool,
+) -> Result<Inbox> {
+    let member_hash = token.database_hash();
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let entries = database::get_inbox(conn, &member_hash, before, limit, unread_only).await?;
+    Ok(Inbox {
+        items: entries.into_iter().flat_map(into_item).collect(),
+        unread: database::count_unread_inbox(conn, &member_hash).await?,
+    })
+}
+
+// returns how many unread entries are left
+pub async fn mark_read(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+    entry: Option<i64>,
+) -> Result<i64> {
+    let member_hash = token.database_hash();
+    database::mark_inbox_read(conn, &member_hash, entry).await?;
+    database::count_unread_inbox(conn, &member_hash).await
+}
+
+pub async fn unread(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+) -> Result<i64> {
+    database::count_unread_inbox(conn, &token.database_hash()).await
+}
+
+// entries with a kind this version doesn't know are left out rather than failing the whole inbox
+fn into_item(entry: database::InboxEntry) -> Option<InboxItem> {
+    Some(InboxItem {
+        id: entry.id,
+        kind: InboxKind::from_name(&entry.kind)?,
+        board_discriminator: entry.board_discriminator,
+        thread_post_number: entry.thread_post_number,
+        post_number: entry.post_number,
+        summary: entry.summary,
+        thumbnail: entry.thumbnail,
+        created_at: entry.created_at.and_utc().timestamp() as u64,
+        read: entry.read,
+    })
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use common::structs::{OverflowPolicy, PushMessage, ThreadStatus, ThreadStatusChange};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::database_bindings::Database;
+
+// bump limits are applied as posts come in, board capacity is only enforced here on LIFECYCLE_TIME
+
+#[derive(Debug, Default)]
+pub struct LifecycleReport {
+    pub archived: usize,
+    pub deleted: usize,
+}
+
+pub async fn enforce(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<LifecycleReport> {
+    let mut report = LifecycleReport::default();
+
+    for board in database::get_boards_with_capacity(conn).await? {
+        let Some(max_threads) = board.max_threads else {
+            continue;
+        };
+        let policy = board.overflow_policy();
+
+        for thread in database::get_overflowing_threads(conn, board.id, max_threads).await? {
+            let thread_post_number = database::post_number(thread.post_id, conn).await?;
+            let status = match policy {
+                OverflowPolicy::Archive => {
+                    database::archive_thread(conn, thread.id).await?;
+                    report.archived += 1;
+                    ThreadStatus::Archived
+                }
+                OverflowPolicy::Delete => {
+                    Database::raw_delete_thread(conn, thread.id).await?;
+                    report.deleted += 1;
+                    ThreadStatus::Deleted
+                }
+            };
+
+            crate::PUSH_NOTIFS.lock().await.send_to(
+                &[
+                    &crate::push::board_ident(&board.discriminator),
+                    &crate::push::thread_ident(&board.discriminator, thread_post_number),
+                ],
+                PushMessage::ThreadStatusChanged(ThreadStatusChange {
+                    board_discriminator: board.discriminator.clone(),
+                    thread_post_number,
+                    status,
+                }),
+            );
+        }
+    }
+
+    Ok(report)
+}
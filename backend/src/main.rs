@@ -33,16 +33,29 @@ use warp::{http::HeaderValue, Filter, Reply};
 
 mod database_bindings;
 mod endpoints;
+mod exports;
+mod filebans;
+mod filecache;
 mod filters;
+mod garbage;
+mod inbox;
+mod lifecycle;
+mod outbox;
+mod preferences;
 mod push;
+mod quotas;
+mod search;
+mod sharelinks;
 // pub mod schema;
 mod statics;
 mod unclaimedfiles;
+mod watching;
+mod websocket;
 use unclaimedfiles::UnclaimedFiles;
 pub mod quotes;
 use quotes::Quotes;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 // use crate::database_bindings::Users;
 use profanity::Profanity;
@@ -61,6 +74,12 @@ lazy_static::lazy_static! {
     pub static ref QUOTES: Arc<Quotes> = Arc::new(Quotes::load(env!("QUOTES_PATH")).expect("Failed to load quotes"));
     pub static ref RATELIMIT: Arc<Mutex<HashMap<String, tokio::time::Instant>>> = Arc::new(Mutex::new(HashMap::new()));
     pub static ref PUSH_NOTIFS: Arc<Mutex<push::PushHolder>> = Arc::new(Mutex::new(push::PushHolder::new()));
+    pub static ref EXPORTS: Arc<Mutex<exports::Exports>> = Arc::new(Mutex::new(exports::Exports::new()));
+    // paths of files whose variants are still being encoded, gc leaves their half written variants alone
+    pub static ref ENCODING_FILES: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+    pub static ref FILE_CACHE_INFO: Arc<Mutex<filecache::FileCache>> = Arc::new(Mutex::new(filecache::FileCache::new()));
+    // (session, share link id) of anonymous viewers that already used up a view, see FileSig::view
+    pub static ref SHARE_SESSIONS: Arc<Mutex<HashMap<(String, i64), tokio::time::Instant>>> = Arc::new(Mutex::new(HashMap::new()));
 }
 
 fn is_safe_mimetype(mimetype: &str) -> bool {
@@ -82,6 +101,90 @@ fn is_safe_mimetype(mimetype: &str) -> bool {
     })
 }
 
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+async fn file_cache_info(path: &str) -> anyhow::Result<Option<Arc<database::FileCacheInfo>>> {
+    if let Some(info) = FILE_CACHE_INFO.lock().await.get(path) {
+        return Ok(Some(info));
+    }
+
+    let mut conn = POOL
+        .get()
+        .await
+        .map_err(|e| anyhow::anyhow!("error connecting to backend: {e}"))?;
+    let info = database::get_file_cache_info(&mut conn, path)
+        .await?
+        .map(Arc::new);
+
+    // misses aren't remembered, an upload can still be claimed at that path
+    if let Some(info) = &info {
+        FILE_CACHE_INFO
+            .lock()
+            .await
+            .insert(path.to_owned(), Arc::clone(info));
+    }
+    Ok(info)
+}
+
+// `filename` for old clients, `filename*` with the exact utf-8 name for everyone else
+fn content_disposition(disposition: &'static str, filename: Option<&str>) -> HeaderValue {
+    let Some(filename) = filename else {
+        return HeaderValue::from_static(disposition);
+    };
+
+    let fallback = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let encoded = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect::<String>();
+
+    HeaderValue::from_str(&format!(
+        "{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static(disposition))
+}
+
+fn insert_cache_headers(resp: &mut warp::reply::Response, etag: &str, cache_control: &'static str) {
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        resp.headers_mut().insert(warp::http::header::ETAG, etag);
+    }
+    resp.headers_mut().insert(
+        warp::http::header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+}
+
+fn insert_share_session(resp: &mut warp::reply::Response, session: &str) {
+    let cookie = format!(
+        "share_session={session}; Path=/files/; HttpOnly; SameSite=Lax; Max-Age={}",
+        statics::SHARE_SESSION_SECONDS
+    );
+    if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+        resp.headers_mut()
+            .insert(warp::http::header::SET_COOKIE, cookie);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     // env_logger::init();
@@ -155,14 +258,94 @@ async fn main() {
                     .and(warp::path::full())
                     .and(optional_token())
                     .and(optional_file_sig())
+                    .and(warp::header::optional::<String>("if-none-match"))
+                    .and(warp::header::optional::<String>("range"))
+                    .and(warp::cookie::optional::<String>("share_session"))
                     .and_then(
                         |reply: warp::filters::fs::File,
                          path: warp::path::FullPath,
-                         mut token: Option<Token>,
-                         file_sig: Option<FileSig>| async move {
+                         token: Option<Token>,
+                         file_sig: Option<FileSig>,
+                         if_none_match: Option<String>,
+                         range: Option<String>,
+                         share_session: Option<String>| async move {
                             let path = path.as_str().to_string();
+                            let is_thumbnail = path.ends_with("-thumb.jpg");
+                            let variant = common::structs::FileVariant::ALL
+                                .into_iter()
+                                .find(|v| path.ends_with(v.suffix()));
+                            let original_path = match variant {
+                                Some(variant) => path.trim_end_matches(variant.suffix()),
+                                None => path.trim_end_matches("-thumb.jpg"),
+                            };
+
+                            // files and thumbnails never change once written, so the stored hash doubles as an etag
+                            let cache_info = if path.contains("/files/") {
+                                file_cache_info(original_path).await.map_err(|e| {
+                                    log::error!("Error getting file: {e}");
+                                    warp::reject::reject()
+                                })?
+                            } else {
+                                None
+                            };
+
+                            // set when this request started a share link session
+                            let mut new_session = None;
+                            if !(is_thumbnail || token.is_some()) {
+                                // without a token the file has to be on a public board and come with a valid signature
+                                let public = cache_info.as_ref().is_some_and(|info| !info.private);
+                                let session = match file_sig {
+                                    Some(file_sig) if public => {
+                                        file_sig
+                                            .view(&path, range.as_deref(), share_session.clone())
+                                            .await
+                                    }
+                                    _ => None,
+                                };
+                                match session {
+                                    Some(session) if share_session.as_ref() != Some(&session) => {
+                                        new_session = Some(session);
+                                    }
+                                    Some(_) => {}
+                                    None => return Err(warp::reject::reject()),
+                                }
+                            }
+
+                            let caching = cache_info.as_ref().map(|info| {
+                                let hash = &info.hash;
+                                (
+                                    match (is_thumbnail, variant) {
+                                        (true, _) => format!("\"{hash}-thumb\""),
+                                        (false, Some(variant)) => {
+                                            format!("\"{hash}-{}\"", variant.name())
+                                        }
+                                        (false, None) => format!("\"{hash}\""),
+                                    },
+                                    if info.private {
+                                        "private, max-age=31536000, immutable"
+                                    } else {
+                                        "public, max-age=31536000, immutable"
+                                    },
+                                )
+                            });
 
+                            if let (Some((etag, cache_control)), Some(if_none_match)) =
+                                (&caching, &if_none_match)
+                            {
+                                if etag_matches(if_none_match, etag) {
+                                    let mut resp =
+                                        warp::http::StatusCode::NOT_MODIFIED.into_response();
+                                    insert_cache_headers(&mut resp, etag, cache_control);
+                                    if let Some(session) = &new_session {
+                                        insert_share_session(&mut resp, session);
+                                    }
+                                    return Ok(resp);
+                                }
+                            }
+
+                            // range requests are answered by warp::fs itself, which is what lets video and audio seek
                             let mut resp = reply.into_response();
+                            let mut disposition = None;
                             if let Some(content_type) =
                                 resp.headers().get(warp::http::header::CONTENT_TYPE)
                             {
@@ -172,42 +355,31 @@ async fn main() {
                                         "Forcing download of file with mimetype {}",
                                         content_type
                                     );
-                                    resp.headers_mut().insert(
-                                        warp::http::header::CONTENT_DISPOSITION,
-                                        HeaderValue::from_static("attachment"),
-                                    );
+                                    disposition = Some("attachment");
                                 }
                             }
 
-                            if path.ends_with("-thumb.jpg") || token.is_some() {
-                                return Ok(resp);
+                            // thumbnails and variants keep their generated names, only the original gets the uploaded one
+                            let filename = cache_info
+                                .as_ref()
+                                .and_then(|info| info.original_name.as_deref())
+                                .filter(|_| !is_thumbnail && variant.is_none());
+
+                            if disposition.is_some() || filename.is_some() {
+                                resp.headers_mut().insert(
+                                    warp::http::header::CONTENT_DISPOSITION,
+                                    content_disposition(disposition.unwrap_or("inline"), filename),
+                                );
                             }
 
-                            if path.contains("/files/") {
-                                let mut conn =
-                                    POOL.get().await.map_err(|_| warp::reject::reject())?;
-
-                                let _file = database::get_file_from_path(
-                                    &mut conn,
-                                    token
-                                        .as_mut()
-                                        .map(|x| x.member_hash().database_hash().to_string()),
-                                    path.trim_end_matches("-thumb.jpg"),
-                                )
-                                .await
-                                .map_err(|e| {
-                                    log::error!("Error getting file: {e}");
-                                    warp::reject::reject()
-                                })?;
-
-                                if let Some(file_sig) = file_sig {
-                                    if file_sig.validates(&path).await {
-                                        return Ok(resp);
-                                    }
-                                }
+                            if let Some((etag, cache_control)) = &caching {
+                                insert_cache_headers(&mut resp, etag, cache_control);
+                            }
+                            if let Some(session) = &new_session {
+                                insert_share_session(&mut resp, session);
                             }
 
-                            Err(warp::reject::reject())
+                            Ok(resp)
                         },
                     )
                     .or(valid_token_always_allow_res()
@@ -403,9 +575,12 @@ async fn main() {
         },
     );
     tokio::spawn(server);
+    tokio::spawn(outbox::run_workers());
 
     let mut trim_files = tokio::time::interval(std::time::Duration::from_secs(*statics::TRIM_TIME));
     let mut delete_old_files = tokio::time::interval(std::time::Duration::from_secs(1));
+    let mut enforce_lifecycle =
+        tokio::time::interval(std::time::Duration::from_secs(*statics::LIFECYCLE_TIME));
     let mut auto_delete = tokio::time::Instant::now();
     loop {
         tokio::select! {
@@ -413,11 +588,8 @@ async fn main() {
 
                 if *MANUAL_FILE_TRIM.lock().await || auto_delete.elapsed() >= std::time::Duration::from_secs(*statics::DELETE_TIME) {
 
-                    let lock = FS_LOCK.lock().await;
                     auto_delete = tokio::time::Instant::now();
                     *MANUAL_FILE_TRIM.lock().await = false;
-                    let dir = env!("FILE_STORAGE_PATH");
-                    let files = get_all_entries(dir).await.unwrap_or_default().iter().flat_map(|x| x.path().to_str().map(|s| s.replace(dir, ""))).collect::<Vec<String>>();
                     let mut db = match POOL.get().await {
                         Ok(x) => x,
                         Err(e) => {
@@ -425,58 +597,52 @@ async fn main() {
                             continue;
                         }
                     };
-                    let files_in_db = match database_bindings::Database::get_all_files(&mut db).await {
-                        Ok(x) => x,
-                        Err(e) => {
-                            log::error!("Error getting files from database: {e}");
-                            continue;
-                        }
-                    };
-
-                    drop(lock);
-                    let files_to_delete = files.iter().filter(|x| !files_in_db.iter().any(|v| {
-                        // if &&v.claimed.path == x || &&v.claimed.thumbnail == x {
-                        //     true
-                        // } else {
-                        //     println!("{x} is not in {} or {}", v.claimed.path, v.claimed.thumbnail);
-                        //     false
-                        // }
-                        if v.claimed.path.replace(*x, "").is_empty() || v.claimed.path.replace(x.trim_end_matches("-thumb.jpg"), "").is_empty() {
-                            log::trace!("`{x}` is `{}` or `{}`", v.claimed.path.replace(*x, ""), v.claimed.path.replace(x.trim_end_matches("-thumb.jpg"), ""));
-                            true
-                        } else {
-                            false
-                        }
-                    })).cloned().collect::<Vec<String>>().iter().map(|x| format!("{dir}{x}")).collect::<Vec<String>>();
-
-                    for file in files_to_delete {
-                        // until i'm confident in the file deletion code, i'm gonna move them to a trash folder instead of deleting them
-                        // if let Err(e) = tokio::fs::remove_file(file.clone()).await {
-                        //     println!("Error deleting file {file}: {e}");
-                        // }
-                        log::trace!("Deleting: {file}");
-                        let trash_path = format!("{}{}", env!("TRASH_STORAGE_PATH"), file.replace(dir, ""));
-                        // remove the last element after the last slash
-                        let trash = {
-                            let trash_vec = trash_path.split('/').collect::<Vec<&str>>();
-                            trash_vec[..trash_vec.len() - 1].join("/")
-                        };
-
-                        if let Err(e) = tokio::fs::create_dir_all(trash).await {
-                            log::error!("Error creating trash folder: {e}");
-                            continue;
+                    match garbage::collect_garbage(&mut db, false).await {
+                        Ok(report) => {
+                            log::info!(
+                                "Garbage collection removed {} orphaned rows, trashed {} files and purged {} files",
+                                report.orphaned_rows.len(),
+                                report.unreferenced_paths.len(),
+                                report.purged.len()
+                            );
+                            for path in report.missing_paths {
+                                log::warn!("File {path} is referenced in the database but missing from disk");
+                            }
                         }
-
-                        if let Err(e) = tokio::fs::rename(file.clone(), trash_path).await {
-                            log::error!("Error moving file to trash: {e}");
+                        Err(e) => log::error!("Error collecting garbage: {e}"),
+                    }
+                }
+            }
+            _ = enforce_lifecycle.tick() => {
+                let mut db = match POOL.get().await {
+                    Ok(x) => x,
+                    Err(e) => {
+                        log::error!("Error getting database connection: {e}");
+                        continue;
+                    }
+                };
+                match lifecycle::enforce(&mut db).await {
+                    Ok(report) => {
+                        if report.archived + report.deleted > 0 {
+                            log::info!(
+                                "Board capacity archived {} threads and deleted {} threads",
+                                report.archived,
+                                report.deleted
+                            );
                         }
                     }
+                    Err(e) => log::error!("Error enforcing board capacity: {e}"),
                 }
             }
             _ = trim_files.tick() => {
                 if let Err(e) = UNCLAIMED_FILES.lock().await.trim_files().await {
                     log::error!("Error trimming files: {e}");
                 }
+                SHARE_SESSIONS
+                    .lock()
+                    .await
+                    .retain(|_, started| started.elapsed().as_secs() <= statics::SHARE_SESSION_SECONDS);
+                exports::expire().await;
             }
             _ = tokio::signal::ctrl_c() => {
                 log::trace!("Received SIGINT");
@@ -491,21 +657,6 @@ async fn main() {
     // DATA.lock().await.close().await.unwrap();
 }
 
-#[async_recursion::async_recursion]
-async fn get_all_entries(dir: &str) -> anyhow::Result<Vec<tokio::fs::DirEntry>> {
-    let mut return_files = Vec::new();
-    let mut files = tokio::fs::read_dir(dir).await?;
-    while let Some(file) = files.next_entry().await? {
-        if file.file_type().await?.is_dir() {
-            return_files
-                .append(&mut get_all_entries(file.path().to_str().unwrap_or_default()).await?);
-        } else {
-            return_files.push(file);
-        }
-    }
-    Ok(return_files)
-}
-
 fn encode_checksum_str(s: &str) -> anyhow::Result<String> {
     // encode the string with a checksum and encrypt it using our secret key (generated at startup, does not persist)
     // random key is at crate::statics::RANDOM_KEY;
@@ -0,0 +1,497 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use common::structs::SubscriptionData;
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::sync::Notify;
+use web_push::WebPushError;
+
+// web pushes are written to the push_outbox table first and delivered from there, so a restart or an outage
+// on the push service's side only delays them. subscriptions are plain urls, pointing one at a local http
+// server is enough to watch the workers retry and expire things
+
+struct PushMetrics {
+    queued: AtomicU64,
+    delivered: AtomicU64,
+    retried: AtomicU64,
+    failed: AtomicU64,
+    expired_subscriptions: AtomicU64,
+}
+
+// counted since the server started
+static METRICS: PushMetrics = PushMetrics {
+    queued: AtomicU64::new(0),
+    delivered: AtomicU64::new(0),
+    retried: AtomicU64::new(0),
+    failed: AtomicU64::new(0),
+    expired_subscriptions: AtomicU64::new(0),
+};
+
+// lets freshly queued jobs go out without waiting for the next poll
+static WAKE: Notify = Notify::const_new();
+
+#[derive(Debug, Serialize)]
+pub struct PushReport {
+    pub queued: u64,
+    pub delivered: u64,
+    pub retried: u64,
+    pub failed: u64,
+    pub expired_subscriptions: u64,
+    pub pending: i64,
+    pub oldest_pending_seconds: Option<i64>,
+}
+
+enum Outcome {
+    Delivered,
+    // 404 and 410, the browser unsubscribed or the subscription expired
+    Gone,
+    // retrying won't make the push service accept it
+    Rejected(String),
+    Retry(String, Option<std::time::Duration>),
+}
+
+pub async fn enqueue(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    to: &[&database::Member],
+    payload: &str,
+) -> Result<()> {
+    let now = chrono::Utc::now();
+    let mut jobs = vec![];
+    for member in to {
+        let (send_at, digest) =
+            crate::preferences::schedule(&member.notification_preferences(), now);
+        match member.parse_push_data() {
+            Ok(subscriptions) => {
+                jobs.extend(
+                    subscriptions
+                        .into_iter()
+                        .map(|subscription| database::QueuedPush {
+                            member: member.id,
+                            subscription,
+                            send_at: send_at.naive_utc(),
+                            digest,
+                        }),
+                )
+            }
+            Err(e) => log::error!("Error parsing push data for member {}: {}", member.id, e),
+        }
+    }
+    if jobs.is_empty() {
+        return Ok(());
+    }
+
+    let queued = database::enqueue_pushes(conn, jobs, payload).await?;
+    METRICS.queued.fetch_add(queued as u64, Ordering::Relaxed);
+    WAKE.notify_one();
+    Ok(())
+}
+
+pub async fn report(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<PushReport> {
+    let (pending, oldest) = database::pending_pushes(conn).await?;
+    let now = chrono::Utc::now().naive_utc();
+    Ok(PushReport {
+        queued: METRICS.queued.load(Ordering::Relaxed),
+        delivered: METRICS.delivered.load(Ordering::Relaxed),
+        retried: METRICS.retried.load(Ordering::Relaxed),
+        failed: METRICS.failed.load(Ordering::Relaxed),
+        expired_subscriptions: METRICS.expired_subscriptions.load(Ordering::Relaxed),
+        pending,
+        oldest_pending_seconds: oldest.map(|oldest| (now - oldest).num_seconds()),
+    })
+}
+
+// runs forever, claims due jobs and delivers up to PUSH_WORKERS of them at once
+pub async fn run_workers() {
+    loop {
+        let jobs = match claim().await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                log::error!("Error claiming push jobs: {}", e);
+                vec![]
+            }
+        };
+
+        if jobs.is_empty() {
+            tokio::select! {
+                _ = WAKE.notified() => {}
+                _ = tokio::time::sleep(std::time::Duration::from_secs(crate::statics::PUSH_POLL_SECONDS)) => {}
+            }
+            continue;
+        }
+
+        futures::stream::iter(group(jobs))
+            .for_each_concurrent(*crate::statics::PUSH_WORKERS, |jobs| async move {
+                if let Err(e) = deliver(jobs).await {
+                    log::error!("Error delivering push job: {}", e);
+                }
+            })
+            .await;
+    }
+}
+
+async fn claim() -> Result<Vec<database::PushJob>> {
+    let mut conn = crate::POOL.get().await?;
+    let lease_until = chrono::Utc::now().naive_utc()
+        + chrono::Duration::seconds(crate::statics::PUSH_LEASE_SECONDS);
+    database::claim_pushes(
+        &mut conn,
+        (*crate::statics::PUSH_WORKERS * 4) as i64,
+        lease_until,
+    )
+    .await
+}
+
+// digest jobs for the same subscription are sent as one push, everything else on its own
+fn group(jobs: Vec<database::PushJob>) -> Vec<Vec<database::PushJob>> {
+    let mut groups: Vec<Vec<database::PushJob>> = vec![];
+    for job in jobs {
+        if job.digest {
+            if let Some(group) = groups
+                .iter_mut()
+                .find(|group| group[0].digest && group[0].endpoint == job.endpoint)
+            {
+                group.push(job);
+                continue;
+            }
+        }
+        groups.push(vec![job]);
+    }
+    groups
+}
+
+fn digest_payload(jobs: &[database::PushJob]) -> String {
+    let bodies = jobs
+        .iter()
+        .flat_map(|job| serde_json::from_str::<serde_json::Value>(&job.payload).ok())
+        .flat_map(|payload| {
+            payload["body"]
+                .as_str()
+                .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
+        })
+        .collect::<Vec<_>>();
+
+    // each thread once, in the order they first came up
+    let mut seen = HashSet::new();
+    let topics = bodies
+        .iter()
+        .flat_map(|body| body["thread_topic"].as_str())
+        .filter(|topic| seen.insert(*topic))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let replies = bodies.iter().filter(|body| body["kind"] == "reply").count();
+
+    // opening it goes to the newest thread
+    let latest = bodies.last().cloned().unwrap_or_default();
+    serde_json::json!({
+        "title": format!("{} new notifications", jobs.len()),
+        "body": serde_json::json!({
+            "kind": "digest",
+            "content": match replies {
+                0 => format!("New posts in {}", topics.join(", ")),
+                n => format!("{} replies to you, new posts in {}", n, topics.join(", ")),
+            },
+            "board_discriminator": latest["board_discriminator"],
+            "thread_post_number": latest["thread_post_number"],
+            "thumbnail": serde_json::Value::Null,
+        })
+        .to_string(),
+    })
+    .to_string()
+}
+
+// how a push leaves the server, attempt takes any sender so the tests can script what the push service says
+trait PushSender {
+    async fn send(&self, sub: &SubscriptionData, payload: &str) -> Outcome;
+}
+
+struct WebPush;
+
+impl PushSender for WebPush {
+    async fn send(&self, sub: &SubscriptionData, payload: &str) -> Outcome {
+        let e = match crate::database_bindings::Database::push(sub, payload.as_bytes()).await {
+            Ok(_) => return Outcome::Delivered,
+            Err(e) => e,
+        };
+
+        match e.downcast_ref::<WebPushError>() {
+            Some(WebPushError::EndpointNotValid { .. } | WebPushError::EndpointNotFound { .. }) => {
+                Outcome::Gone
+            }
+            Some(
+                WebPushError::InvalidUri
+                | WebPushError::PayloadTooLarge
+                | WebPushError::MissingCryptoKeys
+                | WebPushError::InvalidCryptoKeys,
+            ) => Outcome::Rejected(e.to_string()),
+            Some(WebPushError::ServerError { retry_after, .. }) => {
+                Outcome::Retry(e.to_string(), *retry_after)
+            }
+            _ => Outcome::Retry(e.to_string(), None),
+        }
+    }
+}
+
+// what happens to a group of jobs once the push service answered
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Finish(Vec<i64>),
+    RemoveSubscription(i64, String),
+    Retry(Vec<i64>, chrono::NaiveDateTime, String),
+}
+
+async fn attempt(sender: &impl PushSender, jobs: &[database::PushJob]) -> Outcome {
+    let Some(job) = jobs.first() else {
+        return Outcome::Delivered;
+    };
+    let payload = match jobs.len() {
+        1 => job.payload.clone(),
+        _ => digest_payload(jobs),
+    };
+    match serde_json::from_value::<SubscriptionData>(job.subscription.clone()) {
+        Ok(sub) => sender.send(&sub, &payload).await,
+        Err(e) => Outcome::Rejected(format!("invalid subscription: {}", e)),
+    }
+}
+
+fn resolve(jobs: &[database::PushJob], outcome: Outcome, now: chrono::NaiveDateTime) -> Resolution {
+    let ids = jobs.iter().map(|job| job.id).collect::<Vec<i64>>();
+    let Some(job) = jobs.first() else {
+        return Resolution::Finish(ids);
+    };
+    match outcome {
+        Outcome::Delivered => {
+            METRICS.delivered.fetch_add(1, Ordering::Relaxed);
+            Resolution::Finish(ids)
+        }
+        Outcome::Gone => {
+            METRICS
+                .expired_subscriptions
+                .fetch_add(1, Ordering::Relaxed);
+            log::info!(
+                "Push subscription for member {} is gone, removing it",
+                job.member
+            );
+            Resolution::RemoveSubscription(job.member, job.endpoint.clone())
+        }
+        Outcome::Rejected(error) => {
+            METRICS.failed.fetch_add(1, Ordering::Relaxed);
+            log::error!("Push to member {} was rejected: {}", job.member, error);
+            Resolution::Finish(ids)
+        }
+        Outcome::Retry(error, _) if job.attempts + 1 >= crate::statics::PUSH_MAX_ATTEMPTS => {
+            METRICS.failed.fetch_add(1, Ordering::Relaxed);
+            log::error!(
+                "Giving up on push to member {} after {} attempts: {}",
+                job.member,
+                job.attempts + 1,
+                error
+            );
+            Resolution::Finish(ids)
+        }
+        Outcome::Retry(error, retry_after) => {
+            METRICS.retried.fetch_add(1, Ordering::Relaxed);
+            let backoff = crate::statics::PUSH_RETRY_BASE_SECONDS
+                .saturating_mul(1 << job.attempts.clamp(0, 20))
+                .min(crate::statics::PUSH_RETRY_MAX_SECONDS);
+            // the push service knows best when it'll be back
+            let backoff = retry_after
+                .map(|after| after.as_secs() as i64)
+                .unwrap_or(0)
+                .max(backoff);
+            Resolution::Retry(ids, now + chrono::Duration::seconds(backoff), error)
+        }
+    }
+}
+
+async fn deliver(jobs: Vec<database::PushJob>) -> Result<()> {
+    let outcome = attempt(&WebPush, &jobs).await;
+
+    let mut conn = crate::POOL.get().await?;
+    match resolve(&jobs, outcome, chrono::Utc::now().naive_utc()) {
+        Resolution::Finish(ids) => {
+            for id in ids {
+                database::finish_push(&mut conn, id).await?;
+            }
+        }
+        Resolution::RemoveSubscription(member, endpoint) => {
+            database::remove_push_subscription(&mut conn, member, &endpoint).await?;
+        }
+        Resolution::Retry(ids, retry_at, error) => {
+            for id in ids {
+                database::retry_push(&mut conn, id, retry_at, &error).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    // stands in for the push service, answers with the scripted outcomes in order and remembers what it sent
+    struct FakeSender {
+        outcomes: Mutex<VecDeque<Outcome>>,
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    impl FakeSender {
+        fn answering(outcomes: Vec<Outcome>) -> Self {
+            Self {
+                outcomes: Mutex::new(outcomes.into()),
+                sent: Mutex::new(vec![]),
+            }
+        }
+
+        fn sent(&self) -> Vec<(String, String)> {
+            self.sent.lock().expect("sent poisoned").clone()
+        }
+    }
+
+    impl PushSender for FakeSender {
+        async fn send(&self, sub: &SubscriptionData, payload: &str) -> Outcome {
+            self.sent
+                .lock()
+                .expect("sent poisoned")
+                .push((sub.endpoint.clone(), payload.to_owned()));
+            self.outcomes
+                .lock()
+                .expect("outcomes poisoned")
+                .pop_front()
+                .expect("no outcome left to answer with")
+        }
+    }
+
+    fn job(
+        id: i64,
+        endpoint: &str,
+        digest: bool,
+        attempts: i32,
+        body: serde_json::Value,
+    ) -> database::PushJob {
+        let now = chrono::Utc::now().naive_utc();
+        database::PushJob {
+            id,
+            member: 7,
+            endpoint: endpoint.to_owned(),
+            subscription: serde_json::json!({
+                "endpoint": endpoint,
+                "keys": { "auth": "auth", "p256dh": "p256dh" },
+            }),
+            payload: serde_json::json!({ "title": "new post", "body": body.to_string() })
+                .to_string(),
+            attempts,
+            next_attempt: now,
+            created_at: now,
+            last_error: None,
+            digest,
+        }
+    }
+
+    fn post(topic: &str, kind: &str) -> serde_json::Value {
+        serde_json::json!({
+            "kind": kind,
+            "thread_topic": topic,
+            "board_discriminator": "b",
+            "thread_post_number": 1,
+        })
+    }
+
+    #[tokio::test]
+    async fn unavailable_service_is_retried_after_its_retry_after() {
+        let sender = FakeSender::answering(vec![Outcome::Retry(
+            "unavailable".to_owned(),
+            Some(std::time::Duration::from_secs(600)),
+        )]);
+        let jobs = vec![job(
+            1,
+            "https://push.test/busy",
+            false,
+            0,
+            post("a", "post"),
+        )];
+        let now = chrono::Utc::now().naive_utc();
+
+        match resolve(&jobs, attempt(&sender, &jobs).await, now) {
+            Resolution::Retry(ids, retry_at, _) => {
+                assert_eq!(ids, vec![1]);
+                assert_eq!(retry_at, now + chrono::Duration::seconds(600));
+            }
+            other => panic!("expected a retry, got {other:?}"),
+        }
+        assert_eq!(sender.sent().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_the_attempt_limit() {
+        let sender = FakeSender::answering(vec![Outcome::Retry("unavailable".to_owned(), None)]);
+        let attempts = crate::statics::PUSH_MAX_ATTEMPTS - 1;
+        let jobs = vec![job(
+            1,
+            "https://push.test/busy",
+            false,
+            attempts,
+            post("a", "post"),
+        )];
+
+        let resolution = resolve(
+            &jobs,
+            attempt(&sender, &jobs).await,
+            chrono::Utc::now().naive_utc(),
+        );
+        assert_eq!(resolution, Resolution::Finish(vec![1]));
+    }
+
+    #[tokio::test]
+    async fn gone_subscription_is_removed() {
+        let sender = FakeSender::answering(vec![Outcome::Gone]);
+        let endpoint = "https://push.test/gone";
+        let jobs = vec![job(1, endpoint, false, 0, post("a", "post"))];
+
+        let resolution = resolve(
+            &jobs,
+            attempt(&sender, &jobs).await,
+            chrono::Utc::now().naive_utc(),
+        );
+        assert_eq!(
+            resolution,
+            Resolution::RemoveSubscription(7, endpoint.to_owned())
+        );
+    }
+
+    #[tokio::test]
+    async fn digest_jobs_go_out_as_one_push() {
+        let sender = FakeSender::answering(vec![Outcome::Delivered]);
+        let endpoint = "https://push.test/ok";
+        let groups = group(vec![
+            job(1, endpoint, true, 0, post("cats", "post")),
+            job(2, endpoint, true, 0, post("dogs", "reply")),
+            job(3, endpoint, false, 0, post("birds", "post")),
+            job(4, endpoint, true, 0, post("cats", "post")),
+        ]);
+        assert_eq!(groups.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 1]);
+
+        let digest = &groups[0];
+        let payload = serde_json::from_str::<serde_json::Value>(&digest_payload(digest)).unwrap();
+        assert_eq!(payload["title"], "3 new notifications");
+        let body =
+            serde_json::from_str::<serde_json::Value>(payload["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["content"], "1 replies to you, new posts in cats, dogs");
+
+        let resolution = resolve(
+            digest,
+            attempt(&sender, digest).await,
+            chrono::Utc::now().naive_utc(),
+        );
+        assert_eq!(resolution, Resolution::Finish(vec![1, 2, 4]));
+        assert_eq!(sender.sent().len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, TimeZone, Timelike, Utc};
+use common::structs::{NotificationPreferences, QuietHours};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::filters::MemberToken;
+
+pub async fn get_preferences(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+) -> Result<NotificationPreferences> {
+    let member = database::get_member(conn, &token.database_hash()).await?;
+    Ok(member.notification_preferences())
+}
+
+pub async fn set_preferences(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+    prefs: NotificationPreferences,
+) -> Result<NotificationPreferences> {
+    if let Some(quiet) = &prefs.quiet_hours {
+        if quiet.start > 23 || quiet.end > 23 {
+            return Err(anyhow!("Quiet hours need to be between 0 and 23"));
+        }
+        if chrono_tz::Tz::from_str(&quiet.timezone).is_err() {
+            return Err(anyhow!("Unknown timezone: {}", quiet.timezone));
+        }
+    }
+    if prefs
+        .digest_minutes
+        .is_some_and(|minutes| minutes == 0 || minutes > 60 * 24)
+    {
+        return Err(anyhow!(
+            "Digests can be sent at most once a minute and at least once a day"
+        ));
+    }
+
+    database::set_notification_preferences(conn, &token.database_hash(), &prefs).await?;
+    Ok(prefs)
+}
+
+pub async fn get_muted(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    disc: &str,
+    post: i64,
+    token: MemberToken,
+) -> Result<bool> {
+    let thread = crate::database_bindings::Database::get_raw_thread(conn, disc, post).await?;
+    let member = database::get_member(conn, &token.database_hash()).await?;
+    Ok(member.muted.contains(&thread.id))
+}
+
+pub async fn set_muted(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    disc: &str,
+    post: i64,
+    token: MemberToken,
+    mute: bool,
+) -> Result<bool> {
+    let thread = crate::database_bindings::Database::get_raw_thread(conn, disc, post).await?;
+    let member = database::get_member(conn, &token.database_hash()).await?;
+
+    let mut muted = member.muted;
+    muted.retain(|x| *x != thread.id);
+    if mute {
+        muted.push(thread.id);
+    }
+    database::set_muted_threads(conn, &token.database_hash(), muted).await?;
+    Ok(mute)
+}
+
+// replies to the member's own posts get through a muted thread, everything else has to pass every setting
+pub fn wants_push(member: &database::Member, thread: i64, reply: bool) -> bool {
+    let prefs = member.notification_preferences();
+    if !prefs.push {
+        return false;
+    }
+    reply || (!prefs.mentions_only && !member.muted.contains(&thread))
+}
+
+// when a push should go out and whether it should be folded into a digest
+pub fn schedule(prefs: &NotificationPreferences, now: DateTime<Utc>) -> (DateTime<Utc>, bool) {
+    let mut send_at = now;
+    let mut digest = false;
+
+    if let Some(minutes) = prefs.digest_minutes.filter(|minutes| *minutes > 0) {
+        // lined up on the clock so every push in the same window lands on the same time
+        let period = minutes as i64 * 60;
+        let next = (now.timestamp() / period + 1) * period;
+        send_at = DateTime::from_timestamp(next, 0).unwrap_or(now);
+        digest = true;
+    }
+
+    // whatever piles up during quiet hours goes out as one digest when they end
+    if let Some(until) = prefs
+        .quiet_hours
+        .as_ref()
+        .and_then(|quiet| quiet_until(quiet, send_at))
+    {
+        send_at = until;
+        digest = true;
+    }
+
+    (send_at, digest)
+}
+
+fn quiet_until(quiet: &QuietHours, at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if quiet.start == quiet.end {
+        return None;
+    }
+    let tz = chrono_tz::Tz::from_str(&quiet.timezone).ok()?;
+    let local = at.with_timezone(&tz);
+    let hour = local.hour();
+
+    let quiet_now = if quiet.start < quiet.end {
+        quiet.start <= hour && hour < quiet.end
+    } else {
+        hour >= quiet.start || hour < quiet.end
+    };
+    if !quiet_now {
+        return None;
+    }
+
+    // before midnight in a wrapping range, the end is tomorrow
+    let mut date = local.date_naive();
+    if hour >= quiet.end {
+        date = date.succ_opt()?;
+    }
+    let end = date.and_hms_opt(quiet.end, 0, 0)?;
+    tz.from_local_datetime(&end)
+        .earliest()
+        .map(|end| end.with_timezone(&Utc))
+}
@@ -1,31 +1,97 @@
-use std::{collections::HashMap, convert::Infallible};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+};
 
 use async_stream::stream;
-use common::structs::PushMessage;
+use common::structs::{PushMessage, PushStream};
 use futures::Stream;
+use tokio::time::Instant;
 use warp::sse::Event;
 
 pub struct PushHolder {
     pushes: HashMap<String, Vec<Push>>,
+    // recent events per ident, oldest first, replayed to clients that reconnect
+    history: HashMap<String, VecDeque<(Instant, Delivery)>>,
+    next_id: u64,
 }
 
-pub type Push = tokio::sync::mpsc::UnboundedSender<PushMessage>;
-pub type Recv = tokio::sync::mpsc::UnboundedReceiver<PushMessage>;
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    // None for connection events that aren't worth replaying
+    pub id: Option<u64>,
+    pub message: PushMessage,
+}
+
+pub type Push = tokio::sync::mpsc::UnboundedSender<Delivery>;
+pub type Recv = tokio::sync::mpsc::UnboundedReceiver<Delivery>;
+
+// event name and json data, shared by the SSE and websocket transports
+pub fn event_parts(message: &PushMessage) -> anyhow::Result<(&'static str, Option<String>)> {
+    Ok(match message {
+        PushMessage::Open => ("open", None),
+        PushMessage::NewPost(post) => ("new_post", Some(serde_json::to_string(post.as_ref())?)),
+        PushMessage::ReplyToYou(post) => ("reply", Some(serde_json::to_string(post.as_ref())?)),
+        PushMessage::Inbox(item) => ("inbox", Some(serde_json::to_string(item.as_ref())?)),
+        PushMessage::NewThread(thread) => {
+            ("new_thread", Some(serde_json::to_string(thread.as_ref())?))
+        }
+        PushMessage::PostDeleted(deleted) => {
+            ("post_deleted", Some(serde_json::to_string(deleted)?))
+        }
+        PushMessage::ThreadBumped(post) => {
+            ("thread_bumped", Some(serde_json::to_string(post.as_ref())?))
+        }
+        PushMessage::ThreadReplied(post) => (
+            "thread_replied",
+            Some(serde_json::to_string(post.as_ref())?),
+        ),
+        PushMessage::ThreadStatusChanged(change) => {
+            ("thread_status", Some(serde_json::to_string(change)?))
+        }
+        PushMessage::Close => ("close", None),
+    })
+}
 
-fn into_event(msg: PushMessage) -> anyhow::Result<Event> {
-    Ok(match msg {
-        PushMessage::Open => Event::default().event("open"),
-        PushMessage::NewPost(post) => Event::default()
-            .event("new_post")
-            .json_data(post.as_ref())?,
-        PushMessage::Close => Event::default().event("close"),
+fn into_event(delivery: Delivery) -> anyhow::Result<Event> {
+    let event = match delivery.id {
+        Some(id) => Event::default().id(id.to_string()),
+        None => Event::default(),
+    };
+    let (name, data) = event_parts(&delivery.message)?;
+    let event = event.event(name);
+    Ok(match data {
+        Some(data) => event.data(data),
+        None => event,
     })
 }
 
+pub fn board_ident(discriminator: &str) -> String {
+    format!("board: {}", discriminator)
+}
+
+pub fn thread_ident(discriminator: &str, thread: i64) -> String {
+    format!("board: {} | thread: {}", discriminator, thread)
+}
+
+pub fn stream_ident(stream: &PushStream, member_hash: &str) -> String {
+    match stream {
+        PushStream::Member => member_hash.to_owned(),
+        PushStream::Board(discriminator) => board_ident(discriminator),
+        PushStream::Thread(discriminator, thread) => thread_ident(discriminator, *thread),
+    }
+}
+
 impl PushHolder {
     pub fn new() -> Self {
         Self {
             pushes: HashMap::new(),
+            history: HashMap::new(),
+            // seeded from the clock so ids keep increasing across restarts
+            next_id: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
         }
     }
 
@@ -40,20 +106,42 @@ impl PushHolder {
         rx
     }
 
+    // everything sent to `ident` after `last_event_id` that is still in the history
+    pub fn replay(&self, ident: &String, last_event_id: u64) -> Vec<Delivery> {
+        self.history
+            .get(ident)
+            .map(|history| {
+                history
+                    .iter()
+                    .map(|(_, delivery)| delivery)
+                    .filter(|delivery| delivery.id.is_some_and(|id| id > last_event_id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // the missed events and a receiver for live ones, taken under the same lock so nothing can slip in between
+    pub fn attach(&mut self, ident: &String, last_event_id: Option<u64>) -> (Vec<Delivery>, Recv) {
+        let missed = last_event_id
+            .map(|last| self.replay(ident, last))
+            .unwrap_or_default();
+        (missed, self.add_push(ident.clone()))
+    }
+
     pub async fn subscribe(
         &mut self,
         ident: &String,
+        last_event_id: Option<u64>,
     ) -> impl Stream<Item = Result<Event, Infallible>> {
-        let mut rx = self.add_push(ident.clone());
+        let (missed, mut rx) = self.attach(ident, last_event_id);
         self.send_to(&[ident], PushMessage::Open);
         stream! {
-            while let Some(message) = rx.recv().await {
-                yield Ok(match into_event(message) {
-                    Ok(event) => event,
-                    Err(e) => {
-                        Event::default().event("error").data(format!("{:?}", e))
-                    }
-                })
+            for delivery in missed.into_iter() {
+                yield Ok(into_event_or_error(delivery));
+            }
+            while let Some(delivery) = rx.recv().await {
+                yield Ok(into_event_or_error(delivery));
             }
         }
     }
@@ -63,13 +151,56 @@ impl PushHolder {
     }
 
     pub fn send_to(&mut self, idents: &[&String], message: PushMessage) {
+        let delivery = match message {
+            PushMessage::Open | PushMessage::Close => Delivery { id: None, message },
+            message => {
+                self.next_id += 1;
+                Delivery {
+                    id: Some(self.next_id),
+                    message,
+                }
+            }
+        };
+
         // attempt to get the list of pushes for each ident (mutable)
         idents.iter().for_each(|ident| {
             if let Some(push) = self.pushes.get_mut(*ident) {
                 // attempt to send the message to each push, if sending fails, remove the push from the list
-                push.retain(|p| p.send(message.clone()).is_ok());
+                push.retain(|p| p.send(delivery.clone()).is_ok());
+            }
+        });
+
+        if delivery.id.is_some() {
+            let now = Instant::now();
+            for ident in idents {
+                let history = self.history.entry((*ident).clone()).or_default();
+                history.push_back((now, delivery.clone()));
+                if history.len() > crate::statics::PUSH_HISTORY_LEN {
+                    history.pop_front();
+                }
+            }
+            self.trim_history(now);
+        }
+    }
+
+    fn trim_history(&mut self, now: Instant) {
+        let retention = std::time::Duration::from_secs(crate::statics::PUSH_HISTORY_SECONDS);
+        self.history.retain(|_, history| {
+            while history
+                .front()
+                .is_some_and(|(sent, _)| now.duration_since(*sent) > retention)
+            {
+                history.pop_front();
             }
-        })
+            !history.is_empty()
+        });
+    }
+}
+
+fn into_event_or_error(delivery: Delivery) -> Event {
+    match into_event(delivery) {
+        Ok(event) => event,
+        Err(e) => Event::default().event("error").data(format!("{:?}", e)),
     }
 }
 
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, Result};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+use serde::Serialize;
+
+use crate::filters::MemberToken;
+use crate::unclaimedfiles::UnclaimedFiles;
+
+// how many uploaders the usage report lists
+const TOP_UPLOADERS: i64 = 50;
+
+#[derive(Debug, Default, Serialize)]
+pub struct UsageReport {
+    pub total: Usage,
+    pub by_board: Vec<(String, Usage)>,
+    pub by_mimetype: Vec<(String, Usage)>,
+    // the heaviest uploaders, largest first, by quota key. the key can't be matched to a member
+    pub top_uploaders: Vec<(String, Usage)>,
+    pub trash_bytes: u64,
+    pub trash_files: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Usage {
+    pub bytes: i64,
+    pub files: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BackfillReport {
+    pub sized: usize,
+    pub missing: usize,
+    pub rekeyed: usize,
+}
+
+// what file rows are keyed by for member quotas, from the member's database hash
+pub fn quota_key(database_hash: &str) -> String {
+    common::hash_with_salt(
+        database_hash,
+        &format!("quota{}", *crate::statics::TOKEN_SALT),
+    )
+}
+
+fn window_start() -> chrono::NaiveDateTime {
+    chrono::Utc::now().naive_utc() - chrono::Duration::seconds(*crate::statics::QUOTA_WINDOW as i64)
+}
+
+// checked when the file is uploaded, pending uploads count against the member too
+// the caller keeps `unclaimed` locked until the file is added so two uploads can't both slip under
+pub async fn check_member_quota(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    unclaimed: &UnclaimedFiles,
+    token: MemberToken,
+    size: i64,
+) -> Result<()> {
+    let (stored_bytes, stored_files) =
+        database::get_member_upload_usage(conn, &token.quota_hash(), window_start()).await?;
+    let (pending_bytes, pending_files) = unclaimed.pending_usage(&token.database_hash());
+
+    let bytes = stored_bytes + pending_bytes + size;
+    let files = stored_files + pending_files + 1;
+
+    if bytes > *crate::statics::MEMBER_QUOTA_BYTES {
+        return Err(anyhow!(
+            "You have uploaded too much recently, try again later"
+        ));
+    }
+    if files > *crate::statics::MEMBER_QUOTA_FILES {
+        return Err(anyhow!(
+            "You have uploaded too many files recently, try again later"
+        ));
+    }
+    Ok(())
+}
+
+// checked when the file is claimed, that's the first point we know which board it's going to
+pub async fn check_board_quota(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    board: &database::Board,
+    size: i64,
+) -> Result<()> {
+    if board.quota_bytes.is_none() && board.quota_files.is_none() {
+        return Ok(());
+    }
+
+    let (stored_bytes, stored_files) =
+        database::get_board_upload_usage(conn, board.id, window_start()).await?;
+
+    if board
+        .quota_bytes
+        .is_some_and(|quota| stored_bytes + size > quota)
+    {
+        return Err(anyhow!(
+            "This board has reached its upload limit, try again later"
+        ));
+    }
+    if board
+        .quota_files
+        .is_some_and(|quota| stored_files + 1 > quota)
+    {
+        return Err(anyhow!(
+            "This board has reached its file limit, try again later"
+        ));
+    }
+    Ok(())
+}
+
+pub async fn usage_report(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<UsageReport> {
+    let mut report = UsageReport::default();
+
+    report.by_board = keyed(database::get_board_usage(conn).await?);
+    report.by_mimetype = keyed(database::get_mimetype_usage(conn).await?);
+    for (_, usage) in &report.by_board {
+        report.total.bytes += usage.bytes;
+        report.total.files += usage.files;
+    }
+    report.top_uploaders = keyed(database::get_uploader_usage(conn, TOP_UPLOADERS).await?);
+
+    // the trash directory only exists once something has been trashed
+    let trash = crate::garbage::get_all_entries(env!("TRASH_STORAGE_PATH"))
+        .await
+        .unwrap_or_default();
+    for entry in trash {
+        report.trash_bytes += entry.metadata().await?.len();
+        report.trash_files += 1;
+    }
+
+    Ok(report)
+}
+
+fn keyed(usage: Vec<(String, i64, i64)>) -> Vec<(String, Usage)> {
+    usage
+        .into_iter()
+        .map(|(key, bytes, files)| (key, Usage { bytes, files }))
+        .collect()
+}
+
+// brings rows from before quotas up to date: sizes are read back from disk and uploaders still keyed by
+// the member's token hash get their quota key. safe to run more than once
+pub async fn backfill(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<BackfillReport> {
+    let mut report = BackfillReport::default();
+
+    for (id, path) in database::get_unsized_files(conn).await? {
+        match tokio::fs::metadata(format!("{}{}", env!("FILE_STORAGE_PATH"), path)).await {
+            Ok(metadata) => {
+                database::set_file_size(conn, id, metadata.len() as i64).await?;
+                report.sized += 1;
+            }
+            Err(_) => report.missing += 1,
+        }
+    }
+
+    for token_hash in database::get_unsalted_uploaders(conn).await? {
+        report.rekeyed +=
+            database::rekey_uploader(conn, &token_hash, &quota_key(&token_hash)).await?;
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,91 @@
+use anyhow::{anyhow, Result};
+use common::structs::{SearchResult, SearchResults};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::{database_bindings::Database, endpoints::api::SearchQuery, filters::MemberToken};
+
+const PAGE_SIZE: i64 = 25;
+const MAX_QUERY_LENGTH: usize = 200;
+// deep pages make postgres rank and skip everything before them, narrowing the search is the way further
+const MAX_PAGE: i64 = 100;
+
+// searches go through postgres full text search, queries use the websearch syntax ("quoted phrases", -excluded, or)
+
+pub async fn search(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+    query: SearchQuery,
+) -> Result<SearchResults> {
+    let words = query.q.trim();
+    if words.is_empty() {
+        return Err(anyhow!("Search is empty"));
+    }
+    if words.len() > MAX_QUERY_LENGTH {
+        return Err(anyhow!("Search too long"));
+    }
+
+    // private boards the member can't see are never searched, naming one is an error like viewing it would be
+    let boards = match query.board {
+        Some(disc) => vec![Database::get_board(conn, &disc, token.clone()).await?],
+        None => Database::get_boards(conn, token.clone()).await?,
+    };
+
+    let page = query.page.unwrap_or_default();
+    let offset = page
+        .checked_mul(PAGE_SIZE)
+        .filter(|_| (0..=MAX_PAGE).contains(&page))
+        .ok_or_else(|| anyhow!("Page out of range"))?;
+    let search = database::PostSearch {
+        query: words.to_owned(),
+        boards: boards.iter().map(|b| b.id).collect(),
+        after: query.after.and_then(from_unix),
+        before: query.before.and_then(from_unix),
+        has_file: query.has_file,
+        author: query
+            .author
+            .map(|a| a.trim().to_owned())
+            .filter(|a| !a.is_empty()),
+        threads_only: query.threads_only,
+    };
+
+    // one extra to know whether there's another page
+    let mut found = database::search_posts(conn, &search, offset, PAGE_SIZE + 1).await?;
+    let has_more = found.len() as i64 > PAGE_SIZE && page < MAX_PAGE;
+    found.truncate(PAGE_SIZE as usize);
+
+    let (found, details) = found
+        .into_iter()
+        .map(|(post, topic, is_thread)| (post, (topic, is_thread)))
+        .unzip::<_, _, Vec<database::Post>, Vec<(String, bool)>>();
+    let results = database::safe_posts(conn, &found, &token.database_hash())
+        .await?
+        .into_iter()
+        .zip(details)
+        .map(|(post, (topic, is_thread))| SearchResult {
+            post,
+            topic,
+            is_thread,
+        })
+        .collect();
+
+    Ok(SearchResults {
+        results,
+        terms: database::search_terms(conn, &wanted_words(words)).await?,
+        page,
+        has_more,
+    })
+}
+
+// excluded words never show up in the results, so there's nothing to highlight for them
+fn wanted_words(words: &str) -> String {
+    words
+        .split_whitespace()
+        .filter(|w| !w.starts_with('-') && !w.eq_ignore_ascii_case("or"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn from_unix(seconds: i64) -> Option<chrono::NaiveDateTime> {
+    chrono::DateTime::from_timestamp(seconds, 0).map(|t| t.naive_utc())
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Result};
+use common::structs::{CreateShareLink, ShareLinkInfo};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::filters::{FileSig, MemberToken};
+
+async fn info(link: database::ShareLink) -> Option<ShareLinkInfo> {
+    let sig = FileSig::for_link(&link).await?;
+    Some(ShareLinkInfo {
+        id: link.id,
+        url: sig.url(&link.filepath),
+        path: link.filepath,
+        created_at: sig.bgn,
+        expires_at: sig.exp,
+        max_views: link.max_views,
+        views: link.views,
+        revoked: link.revoked,
+    })
+}
+
+pub async fn create_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+    token: MemberToken,
+    options: CreateShareLink,
+) -> Result<ShareLinkInfo> {
+    if options.max_views.is_some_and(|max| max < 1) {
+        return Err(anyhow!("A share link needs to allow at least one view"));
+    }
+
+    let fileinfo = crate::database_bindings::Database::get_file(path, conn)
+        .await
+        .map_err(|_| anyhow!("File not found"))?;
+
+    if fileinfo.board.private {
+        return Err(anyhow!("Cannot share private file"));
+    }
+
+    let expires = chrono::Utc::now().naive_utc()
+        + chrono::Duration::seconds(options.duration.seconds() as i64);
+
+    let link = database::add_share_link(
+        conn,
+        path,
+        &token.database_hash(),
+        expires,
+        options.max_views,
+        crate::statics::HMAC_KEYRING.newest_kid().await as i32,
+    )
+    .await?;
+
+    info(link)
+        .await
+        .ok_or_else(|| anyhow!("Signing key was retired while creating the link"))
+}
+
+pub async fn list_share_links(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+) -> Result<Vec<ShareLinkInfo>> {
+    let mut links = Vec::new();
+    for link in database::get_member_share_links(conn, &token.database_hash()).await? {
+        // links signed with a retired key can't be used anymore, so there's nothing to show
+        if let Some(link) = info(link).await {
+            links.push(link);
+        }
+    }
+    Ok(links)
+}
+
+pub async fn revoke_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    id: i64,
+    token: MemberToken,
+) -> Result<()> {
+    database::revoke_share_link(conn, id, &token.database_hash()).await
+}
@@ -1,8 +1,8 @@
 use std::{str::FromStr, sync::Arc};
 
 use notify::Watcher as _;
-use rand::rngs::ThreadRng;
-use rsa::traits::PrivateKeyParts;
+use rand::{rngs::ThreadRng, RngCore as _};
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 lazy_static::lazy_static!(
@@ -10,13 +10,25 @@ lazy_static::lazy_static!(
     pub static ref TOKEN: String = env!("TOKEN").to_owned();
     pub static ref HASH_SALT: String = env!("HASH_SALT").to_owned();
     pub static ref DELETE_TIME: u64 = env!("DELETE_TIME").parse::<u64>().expect("DELETE_TIME must be a valid u64");
+    pub static ref TRASH_RETENTION: u64 = option_env!("TRASH_RETENTION").map(|v| v.parse::<u64>().expect("TRASH_RETENTION must be a valid u64")).unwrap_or(60 * 60 * 24 * 30); // 30 days in seconds
+    pub static ref INBOX_RETENTION: u64 = option_env!("INBOX_RETENTION").map(|v| v.parse::<u64>().expect("INBOX_RETENTION must be a valid u64")).unwrap_or(60 * 60 * 24 * 30); // 30 days in seconds
+    pub static ref EXPORT_RETENTION: u64 = option_env!("EXPORT_RETENTION").map(|v| v.parse::<u64>().expect("EXPORT_RETENTION must be a valid u64")).unwrap_or(60 * 60 * 24); // 1 day in seconds
+    pub static ref EXPORT_PATH: String = option_env!("EXPORT_PATH").map(str::to_owned).unwrap_or_else(|| std::env::temp_dir().join("pchan-exports").to_string_lossy().into_owned()); // kept out of FILE_STORAGE_PATH so archives are only served to whoever asked for them
+    pub static ref LIFECYCLE_TIME: u64 = option_env!("LIFECYCLE_TIME").map(|v| v.parse::<u64>().expect("LIFECYCLE_TIME must be a valid u64")).unwrap_or(60); // seconds between board capacity checks
+    pub static ref PHASH_DISTANCE: u32 = option_env!("PHASH_DISTANCE").map(|v| v.parse::<u32>().expect("PHASH_DISTANCE must be a valid u32")).unwrap_or(8); // max differing bits out of 64
+    pub static ref MAX_PENDING_UPLOADS: usize = option_env!("MAX_PENDING_UPLOADS").map(|v| v.parse::<usize>().expect("MAX_PENDING_UPLOADS must be a valid usize")).unwrap_or(4);
+    pub static ref QUOTA_WINDOW: u64 = option_env!("QUOTA_WINDOW").map(|v| v.parse::<u64>().expect("QUOTA_WINDOW must be a valid u64")).unwrap_or(60 * 60 * 24); // 1 day in seconds
+    pub static ref MEMBER_QUOTA_BYTES: i64 = option_env!("MEMBER_QUOTA_BYTES").map(|v| v.parse::<i64>().expect("MEMBER_QUOTA_BYTES must be a valid i64")).unwrap_or(1024 * 1024 * 1024); // 1 GiB per window
+    pub static ref MEMBER_QUOTA_FILES: i64 = option_env!("MEMBER_QUOTA_FILES").map(|v| v.parse::<i64>().expect("MEMBER_QUOTA_FILES must be a valid i64")).unwrap_or(200); // per window
+    pub static ref PUSH_WORKERS: usize = option_env!("PUSH_WORKERS").map(|v| v.parse::<usize>().expect("PUSH_WORKERS must be a valid usize")).unwrap_or(4); // web pushes delivered at once
+    pub static ref GENERATE_AVIF: bool = option_env!("GENERATE_AVIF").is_some_and(|v| v == "true");
     pub static ref TOKEN_SALT: String = env!("TOKEN_SALT").to_owned();
     pub static ref KNOWN_SCRAPERS: Vec<&'static str> = vec!["Mozilla/5.0 (compatible; Discordbot/2.0; +https://discordapp.com)"];
     pub static ref BASE_THUMBNAIL: &'static [u8] = include_bytes!(env!("BASE_THUMBNAIL_PATH"));
     pub static ref BASE_THUMBNAIL_LARGE: &'static [u8] = include_bytes!(env!("BASE_THUMBNAIL_LARGE_PATH"));
     pub static ref FONT: &'static [u8] = include_bytes!(env!("FONT_PATH"));
     pub static ref RANDOM_KEY: &'static [u8] = Box::leak(Box::new(nanoid::nanoid!(32))).as_bytes();
-    pub static ref HMAC_KEY_GENERATOR: HmacKeyGenerator = HmacKeyGenerator::new(env!("HMAC_KEY_PATH"));
+    pub static ref HMAC_KEYRING: HmacKeyring = HmacKeyring::new(env!("HMAC_KEY_PATH"));
 );
 
 #[cfg(feature = "base64_no_pad")]
@@ -26,74 +38,270 @@ pub const BASE64_ENGINE: base64::engine::GeneralPurpose =
 #[cfg(not(feature = "base64_no_pad"))]
 pub const BASE64_ENGINE: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE;
 
-pub const FILE_SHARE_DURATION: u64 = 60 * 60 * 24 * 7; // 1 week in seconds
+pub const VARIANT_MIN_SIZE: usize = 1024 * 1024; // still images smaller than this are served as is
+pub const PREVIEW_SECONDS: u64 = 4;
+pub const BOARD_PAGE_SIZE: i64 = 15; // threads per board page when the client doesn't ask for a size
+pub const MAX_BOARD_PAGE_SIZE: i64 = 100;
+pub const FILE_CACHE_ENTRIES: usize = 100_000; // remembered files before the oldest are dropped
+pub const SHARE_SESSION_SECONDS: u64 = 60 * 60 * 6; // how long one share link view covers a client
 
-pub struct HmacKeyGenerator {
+// an export that finishes within this is answered right away, anything longer is polled for
+pub const EXPORT_WAIT_SECONDS: u64 = 5;
+pub const MAX_RUNNING_EXPORTS: usize = 4; // across everyone, more are refused until one finishes
+
+// how much of each event stream is kept around for clients reconnecting with Last-Event-ID
+pub const PUSH_HISTORY_LEN: usize = 256;
+pub const PUSH_HISTORY_SECONDS: u64 = 60 * 10;
+
+// websocket connections are pinged this often and dropped when nothing comes back for the timeout
+pub const WS_HEARTBEAT_SECONDS: u64 = 30;
+pub const WS_TIMEOUT_SECONDS: u64 = 90;
+
+// failed web pushes are retried after base * 2^attempts seconds, up to the max, and given up on after PUSH_MAX_ATTEMPTS
+pub const PUSH_MAX_ATTEMPTS: i32 = 8;
+pub const PUSH_RETRY_BASE_SECONDS: i64 = 30;
+pub const PUSH_RETRY_MAX_SECONDS: i64 = 60 * 60;
+// how long a worker holds on to a job before someone else may pick it up
+pub const PUSH_LEASE_SECONDS: i64 = 120;
+pub const PUSH_POLL_SECONDS: u64 = 30;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HmacKey {
+    pub kid: u32,
+    #[serde(with = "base64_key")]
+    pub key: Vec<u8>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+// the key itself stays out of logs, created_at is what retiring goes by
+impl std::fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HmacKey")
+            .field("kid", &self.kid)
+            .field("created_at", &self.created_at)
+            .finish_non_exhaustive()
+    }
+}
+
+// the keyring is stored as json, keys are never edited in place, only added and retired
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Keys {
+    pub keys: Vec<HmacKey>,
+}
+
+impl Keys {
+    fn newest(&self) -> Option<&HmacKey> {
+        self.keys.iter().max_by_key(|k| k.kid)
+    }
+
+    fn generate(&mut self, rng: &mut ThreadRng) -> u32 {
+        let kid = self.newest().map(|k| k.kid + 1).unwrap_or(1);
+        let mut key = vec![0u8; 64];
+        rng.fill_bytes(&mut key);
+        self.keys.push(HmacKey {
+            kid,
+            key,
+            created_at: chrono::Utc::now(),
+        });
+        kid
+    }
+
+    // a key can go once its successor has been signing for longer than any link can live,
+    // everything it signed has expired by then
+    fn retire(&mut self) -> Vec<u32> {
+        let longest =
+            chrono::Duration::seconds(common::structs::ShareDuration::LONGEST.seconds() as i64);
+        let now = chrono::Utc::now();
+        let mut keys = self.keys.clone();
+        keys.sort_by_key(|k| k.kid);
+
+        let retired = keys
+            .windows(2)
+            .filter(|pair| pair[1].created_at + longest <= now)
+            .map(|pair| pair[0].kid)
+            .collect::<Vec<u32>>();
+
+        self.keys.retain(|k| !retired.contains(&k.kid));
+        retired
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyInfo {
+    pub kid: u32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    // when the key can be retired, None for the newest key
+    pub retires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RotationReport {
+    pub kid: u32,
+    pub retired: Vec<u32>,
+}
+
+pub struct HmacKeyring {
     _watcher: notify::RecommendedWatcher,
-    key: Arc<Mutex<Vec<u8>>>,
+    file_path: &'static str,
+    keys: Arc<Mutex<Keys>>,
 }
 
-impl HmacKeyGenerator {
+impl HmacKeyring {
     pub fn new(file_path: &'static str) -> Self {
         let mut rng = rand::thread_rng();
-        let key = Arc::new(Mutex::new({
-            // attempt to retrieve from disc, if failure, generate a new key and write to disc, if writing fails panic
-            match std::fs::read(file_path) {
-                Ok(key) => key,
-                Err(_) => generate_and_write_key(file_path, &mut rng)
-                    .expect("Failed to write HMAC key to disc"),
+        let keys = Arc::new(Mutex::new({
+            // attempt to retrieve from disc, if failure, generate a new keyring and write to disc, if writing fails panic
+            match read_keys(file_path) {
+                Some(keys) => keys,
+                None => generate_and_write_keys(file_path, &mut rng)
+                    .expect("Failed to write HMAC keyring to disc"),
             }
         }));
 
         let watcher = {
-            let key = Arc::clone(&key);
+            let keys = Arc::clone(&keys);
             let the_file_path = std::path::PathBuf::from_str(file_path)
                 .expect("Failed to convert HMAC key path to PathBuf");
-            let mut watcher = notify::recommended_watcher(move |res| {
-                if let Ok(notify::Event {
+            let mut watcher = notify::recommended_watcher(move |res| match res {
+                Ok(notify::Event {
                     kind: notify::EventKind::Remove(_),
                     paths: _,
                     attrs: _,
-                }) = res
-                {
-                    log::warn!("HMAC key file removed, generating new key");
+                }) => {
+                    log::warn!("HMAC keyring file removed, generating new keyring");
                     // ensure the key file no longer exists
                     if std::fs::metadata(file_path).is_ok() {
-                        log::trace!("HMAC key file still exists, removing");
+                        log::trace!("HMAC keyring file still exists, removing");
                         let _ = std::fs::remove_file(file_path);
                     }
-                    let mut key = key.blocking_lock();
-                    *key = generate_and_write_key(file_path, &mut rand::thread_rng())
-                        .expect("Failed to write HMAC key to disc");
-                    log::info!("New HMAC key generated");
-                } else {
-                    log::error!("Failed to handle event: {:?}", res);
+                    let mut keys = keys.blocking_lock();
+                    *keys = generate_and_write_keys(file_path, &mut rand::thread_rng())
+                        .expect("Failed to write HMAC keyring to disc");
+                    log::info!("New HMAC keyring generated");
+                }
+                Ok(notify::Event {
+                    kind: notify::EventKind::Modify(_),
+                    paths: _,
+                    attrs: _,
+                }) => {
+                    // picks up keys rotated by another instance sharing the file, a partial write won't parse and is skipped
+                    if let Some(new_keys) = std::fs::read(file_path)
+                        .ok()
+                        .and_then(|data| serde_json::from_slice::<Keys>(&data).ok())
+                        .filter(|keys| !keys.keys.is_empty())
+                    {
+                        *keys.blocking_lock() = new_keys;
+                        log::trace!("HMAC keyring reloaded");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("Failed to handle event: {:?}", e);
                 }
             })
             .expect("Failed to create watcher");
 
             watcher
                 .watch(the_file_path.as_path(), notify::RecursiveMode::NonRecursive)
-                .expect("Failed to watch HMAC key file");
+                .expect("Failed to watch HMAC keyring file");
 
             watcher
         };
 
         Self {
             _watcher: watcher, // just held so it doesn't get dropped
-            key,
+            file_path,
+            keys,
         }
     }
-    pub async fn get_key(&self) -> Vec<u8> {
-        self.key.lock().await.clone()
+
+    pub async fn get_key(&self, kid: u32) -> Option<Vec<u8>> {
+        self.keys
+            .lock()
+            .await
+            .keys
+            .iter()
+            .find(|k| k.kid == kid)
+            .map(|k| k.key.clone())
+    }
+
+    // new signatures always use the newest key
+    pub async fn newest_kid(&self) -> u32 {
+        self.keys.lock().await.newest().map(|k| k.kid).unwrap_or(0)
+    }
+
+    pub async fn list(&self) -> Vec<KeyInfo> {
+        let longest =
+            chrono::Duration::seconds(common::structs::ShareDuration::LONGEST.seconds() as i64);
+        let mut keys = self.keys.lock().await.keys.clone();
+        keys.sort_by_key(|k| k.kid);
+
+        keys.iter()
+            .enumerate()
+            .map(|(i, k)| KeyInfo {
+                kid: k.kid,
+                created_at: k.created_at,
+                retires_at: keys.get(i + 1).map(|next| next.created_at + longest),
+            })
+            .collect()
+    }
+
+    // adds a new signing key and drops any old keys that nothing valid can be signed with anymore
+    pub async fn rotate(&self) -> anyhow::Result<RotationReport> {
+        let mut keys = self.keys.lock().await;
+        let mut new_keys = keys.clone();
+        let kid = new_keys.generate(&mut rand::thread_rng());
+        let retired = new_keys.retire();
+        write_keys(self.file_path, &new_keys)?;
+        *keys = new_keys;
+        Ok(RotationReport { kid, retired })
+    }
+}
+
+fn read_keys(file_path: &str) -> Option<Keys> {
+    let data = std::fs::read(file_path).ok()?;
+    match serde_json::from_slice::<Keys>(&data) {
+        Ok(keys) if !keys.keys.is_empty() => Some(keys),
+        Ok(_) => None,
+        Err(_) if !data.is_empty() => {
+            // a bare key from before the keyring existed, keep using it as the first key
+            Some(Keys {
+                keys: vec![HmacKey {
+                    kid: 1,
+                    key: data,
+                    created_at: chrono::Utc::now(),
+                }],
+            })
+        }
+        Err(_) => None,
     }
 }
 
-fn generate_and_write_key(file_path: &str, rng: &mut ThreadRng) -> std::io::Result<Vec<u8>> {
-    let key = rsa::RsaPrivateKey::new(rng, 4096)
-        .expect("Failed to generate RSA key")
-        .d()
-        .to_bytes_be();
-    std::fs::write(file_path, &key)?;
-    Ok(key)
+// written in place rather than renamed over, replacing the file would look like a removal to the watcher
+fn write_keys(file_path: &str, keys: &Keys) -> anyhow::Result<()> {
+    std::fs::write(file_path, serde_json::to_vec(keys)?)?;
+    Ok(())
+}
+
+fn generate_and_write_keys(file_path: &str, rng: &mut ThreadRng) -> anyhow::Result<Keys> {
+    let mut keys = Keys::default();
+    keys.generate(rng);
+    write_keys(file_path, &keys)?;
+    Ok(keys)
+}
+
+mod base64_key {
+    use base64::Engine as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(key: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::BASE64_ENGINE.encode(key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::BASE64_ENGINE
+            .decode(s)
+            .map_err(serde::de::Error::custom)
+    }
 }
@@ -1,10 +1,12 @@
 use anyhow::{anyhow, Result};
-use common::structs::ClaimedFileInfo;
+use common::structs::{ClaimedFileInfo, FileVariant, MediaInfo, PendingUpload};
 use imageproc::drawing::draw_text_mut;
 use std::collections::HashMap;
 
 use crate::filters::MemberToken;
+use crate::statics::{PREVIEW_SECONDS, VARIANT_MIN_SIZE};
 
+// keyed by upload id, the value holds the uploading member's hash alongside the file
 pub struct UnclaimedFiles {
     pub files: HashMap<String, (String, File, tokio::time::Instant)>,
 }
@@ -15,12 +17,16 @@ impl UnclaimedFiles {
     }
 
     pub async fn add_file(&mut self, file: File, token: MemberToken) -> Result<String> {
+        let owner = token.database_hash().to_string();
+        if self.pending_count(&owner) >= *crate::statics::MAX_PENDING_UPLOADS {
+            return Err(anyhow!(
+                "You have too many files pending, post or cancel one of them first"
+            ));
+        }
         for _ in 0..3 {
             let id = nanoid::nanoid!(16);
-            if let std::collections::hash_map::Entry::Vacant(e) =
-                self.files.entry(token.database_hash().to_string())
-            {
-                e.insert((id.clone(), file, tokio::time::Instant::now()));
+            if let std::collections::hash_map::Entry::Vacant(e) = self.files.entry(id.clone()) {
+                e.insert((owner, file, tokio::time::Instant::now()));
                 return Ok(id);
             }
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -33,13 +39,19 @@ impl UnclaimedFiles {
         createfile: &common::structs::CreateFile,
         token: MemberToken,
         is_thread_post: bool,
-    ) -> Result<ClaimedFileInfo> {
-        match self.files.remove(&*token.database_hash()) {
-            Some((tid, file, _)) => {
-                if tid != createfile.id {
-                    return Err(anyhow!("Invalid id"));
-                }
-
+        text_previews: bool,
+        bans: &[database::FileBan],
+    ) -> Result<(ClaimedFileInfo, PendingEncode)> {
+        let owned = self
+            .files
+            .get(&createfile.id)
+            .is_some_and(|(owner, _, _)| *owner == *token.database_hash());
+        // someone else's upload is treated as if it doesn't exist, and stays pending for them
+        if !owned {
+            return Err(anyhow!("File not found"));
+        }
+        match self.files.remove(&createfile.id) {
+            Some((_, file, _)) => {
                 if file.mimetype.split('/').next() == Some("application") {
                     return Err(anyhow!("Invalid file type"));
                 }
@@ -54,6 +66,10 @@ impl UnclaimedFiles {
                     format!("{}{}", env!("FILE_STORAGE_PATH"), universalfilepath.clone());
 
                 let filehash = common::hash_file(&file.data);
+                let filesize = file.data.len();
+                let preview_lines = (text_previews && file.mimetype.starts_with("text/"))
+                    .then(|| text_preview_lines(&file.data, is_thread_post));
+                crate::filebans::check_hash(bans, &filehash)?;
                 {
                     let folders = format!("{}{}", env!("FILE_STORAGE_PATH"), universalfolderpath);
                     tokio::fs::create_dir_all(folders).await?;
@@ -72,16 +88,37 @@ impl UnclaimedFiles {
                 }
 
                 let ext = file.extension.to_uppercase();
+                let is_audio = file.mimetype.starts_with("audio/");
                 let handle = tokio::task::spawn(async move {
                     let thumbpath = format!("{diskfilepath}-thumb.jpg");
-                    let output = tokio::process::Command::new("ffmpeg")
-                        .args(["-i", &diskfilepath])
-                        .args(["-r", "1"])
-                        .args(if is_thread_post {
+
+                    if let Some(lines) = preview_lines {
+                        return match render_text_preview(&lines, is_thread_post, &thumbpath) {
+                            Ok(_) => Ok((thumbpath, None)),
+                            Err(e) => {
+                                log::error!("Failed to render text preview: {e:?}");
+                                Err(diskfilepath)
+                            }
+                        };
+                    }
+
+                    let mut command = tokio::process::Command::new("ffmpeg");
+                    command.args(["-i", &diskfilepath]);
+                    if is_audio {
+                        // a waveform says a lot more about an audio file than its extension does
+                        command.args(if is_thread_post {
+                            ["-filter_complex", "showwavespic=s=200x100:colors=0x7f7f7f"]
+                        } else {
+                            ["-filter_complex", "showwavespic=s=80x40:colors=0x7f7f7f"]
+                        });
+                    } else {
+                        command.args(["-r", "1"]).args(if is_thread_post {
                             ["-vf", "scale=200:-2"]
                         } else {
                             ["-vf", "scale=80:-2"]
-                        })
+                        });
+                    }
+                    let output = command
                         .args(["-frames:v", "1"])
                         .arg(&thumbpath)
                         .arg("-y")
@@ -90,7 +127,11 @@ impl UnclaimedFiles {
 
                     if output.is_ok() {
                         if tokio::fs::metadata(thumbpath.clone()).await.is_ok() {
-                            Ok(thumbpath)
+                            // only real thumbnails get a perceptual hash, the fallback one looks the same for every file
+                            let phash = image::open(&thumbpath)
+                                .ok()
+                                .map(|img| crate::filebans::perceptual_hash(&img));
+                            Ok((thumbpath, phash))
                         } else {
                             // FFMPEG failed to create thumbnail, manually create one with the file type printed over our default thumbnail
 
@@ -134,7 +175,7 @@ impl UnclaimedFiles {
                             );
 
                             match img.save(&thumbpath) {
-                                Ok(_) => Ok(thumbpath),
+                                Ok(_) => Ok((thumbpath, None)),
                                 Err(e) => {
                                     log::error!("Failed to save thumbnail: {e:?}");
                                     Err(diskfilepath)
@@ -145,39 +186,301 @@ impl UnclaimedFiles {
                         Err(diskfilepath)
                     }
                 });
-                let path = match handle.await? {
-                    Ok(path) => path,
+                let (path, phash) = match handle.await? {
+                    Ok(v) => v,
                     Err(f) => {
                         tokio::fs::remove_file(f).await?;
                         return Err(anyhow!("Invalid file"));
                     }
                 };
-                Ok(ClaimedFileInfo {
-                    path: universalfilepath,
-                    hash: filehash,
-                    thumbnail: path,
-                    spoiler: createfile.spoiler,
-                })
+                if let Some(phash) = phash {
+                    if let Err(e) = crate::filebans::check_phash(bans, phash) {
+                        let diskfilepath =
+                            format!("{}{}", env!("FILE_STORAGE_PATH"), universalfilepath);
+                        tokio::fs::remove_file(&path).await?;
+                        tokio::fs::remove_file(diskfilepath).await?;
+                        return Err(e);
+                    }
+                }
+                let encode = PendingEncode {
+                    diskfilepath: format!("{}{}", env!("FILE_STORAGE_PATH"), universalfilepath),
+                    mimetype: file.mimetype,
+                    size: filesize,
+                };
+
+                Ok((
+                    ClaimedFileInfo {
+                        path: universalfilepath,
+                        hash: filehash,
+                        thumbnail: path,
+                        spoiler: createfile.spoiler,
+                        phash,
+                        variants: Vec::new(),
+                        media: MediaInfo::default(),
+                        original_name: file.filename,
+                        size: filesize as i64,
+                    },
+                    encode,
+                ))
             }
             None => Err(anyhow!("File not found")),
         }
     }
 
     pub async fn trim_files(&mut self) -> Result<()> {
-        let mut to_remove = Vec::new();
-        for (token, (_, _, timeout)) in self.files.iter() {
-            if timeout.elapsed().as_secs() > env!("FILE_LIFESPAN").parse::<u64>()? {
-                to_remove.push(token.clone());
+        let lifespan = env!("FILE_LIFESPAN").parse::<u64>()?;
+        self.files
+            .retain(|_, (_, _, timeout)| timeout.elapsed().as_secs() <= lifespan);
+        Ok(())
+    }
+
+    fn pending_count(&self, owner: &str) -> usize {
+        self.files
+            .values()
+            .filter(|(tokenhash, _, _)| tokenhash == owner)
+            .count()
+    }
+
+    // (bytes, count) of everything the member has pending
+    pub fn pending_usage(&self, owner: &str) -> (i64, i64) {
+        self.files
+            .values()
+            .filter(|(tokenhash, _, _)| tokenhash == owner)
+            .fold((0, 0), |(bytes, count), (_, file, _)| {
+                (bytes + file.data.len() as i64, count + 1)
+            })
+    }
+
+    pub fn pending_size(&self, id: &str, token: MemberToken) -> Option<i64> {
+        self.files
+            .get(id)
+            .filter(|(owner, _, _)| *owner == *token.database_hash())
+            .map(|(_, file, _)| file.data.len() as i64)
+    }
+
+    pub fn pending_uploads(&self, token: MemberToken) -> Result<Vec<PendingUpload>> {
+        let lifespan = env!("FILE_LIFESPAN").parse::<u64>()?;
+        let owner = token.database_hash();
+        let mut uploads = self
+            .files
+            .iter()
+            .filter(|(_, (tokenhash, _, _))| *tokenhash == *owner)
+            .map(|(id, (_, file, uploaded))| PendingUpload {
+                id: id.clone(),
+                filename: file.filename.clone(),
+                mimetype: file.mimetype.clone(),
+                size: file.data.len() as u64,
+                expires_in: lifespan.saturating_sub(uploaded.elapsed().as_secs()),
+            })
+            .collect::<Vec<PendingUpload>>();
+        uploads.sort_by_key(|u| u.expires_in);
+        Ok(uploads)
+    }
+
+    pub fn cancel_upload(&mut self, id: &str, token: MemberToken) -> Result<()> {
+        match self.files.get(id) {
+            Some((owner, _, _)) if *owner == *token.database_hash() => {
+                self.files.remove(id);
+                Ok(())
             }
+            _ => Err(anyhow!("File not found")),
         }
-        for token in to_remove {
-            self.files.remove(&token);
+    }
+}
+
+// strips any client side directories and anything unprintable, keeping at most 128 characters
+fn sanitize_filename(filename: &str) -> Option<String> {
+    let name = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(128)
+        .collect::<String>();
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+// best effort, anything ffprobe can't tell us is left empty
+async fn probe_media(diskfilepath: &str, mimetype: &str) -> MediaInfo {
+    #[derive(serde::Deserialize)]
+    struct Probe {
+        #[serde(default)]
+        streams: Vec<ProbeStream>,
+        format: Option<ProbeFormat>,
+    }
+    #[derive(serde::Deserialize)]
+    struct ProbeStream {
+        width: Option<i32>,
+        height: Option<i32>,
+    }
+    #[derive(serde::Deserialize)]
+    struct ProbeFormat {
+        duration: Option<String>,
+    }
+
+    let output = match tokio::process::Command::new("ffprobe")
+        .args(["-v", "error"])
+        .args(["-show_entries", "format=duration:stream=width,height"])
+        .args(["-of", "json"])
+        .arg(diskfilepath)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            log::warn!("Failed to run ffprobe on {diskfilepath}: {e}");
+            return MediaInfo::default();
         }
-        Ok(())
+    };
+
+    let probe = match serde_json::from_slice::<Probe>(&output.stdout) {
+        Ok(probe) => probe,
+        Err(e) => {
+            log::warn!("Failed to parse ffprobe output for {diskfilepath}: {e}");
+            return MediaInfo::default();
+        }
+    };
+
+    let dimensions = probe.streams.iter().find_map(|s| s.width.zip(s.height));
+
+    MediaInfo {
+        // still images report a duration too, which is meaningless
+        duration_ms: probe
+            .format
+            .and_then(|f| f.duration)
+            .and_then(|d| d.parse::<f64>().ok())
+            .filter(|d| *d > 0.0 && (!mimetype.starts_with("image/") || mimetype == "image/gif"))
+            .map(|d| (d * 1000.0) as i64),
+        width: dimensions.map(|(w, _)| w),
+        height: dimensions.map(|(_, h)| h),
     }
+}
+
+fn text_preview_lines(data: &[u8], is_thread_post: bool) -> Vec<String> {
+    let (lines, columns) = if is_thread_post { (12, 28) } else { (5, 12) };
+    String::from_utf8_lossy(&data[..data.len().min(4096)])
+        .lines()
+        .take(lines)
+        .map(|line| line.replace('\t', "    ").chars().take(columns).collect())
+        .collect()
+}
 
-    pub fn has_pending(&self, token: MemberToken) -> bool {
-        self.files.contains_key(&*token.database_hash())
+fn render_text_preview(lines: &[String], is_thread_post: bool, thumbpath: &str) -> Result<()> {
+    let (size, scale) = if is_thread_post {
+        (200, ab_glyph::PxScale { x: 14.0, y: 14.0 })
+    } else {
+        (80, ab_glyph::PxScale { x: 12.0, y: 12.0 })
+    };
+
+    let font = ab_glyph::FontVec::try_from_vec((*crate::statics::FONT).to_vec())?;
+    let mut img = image::RgbImage::from_pixel(size, size, image::Rgb([240, 240, 240]));
+
+    for (i, line) in lines.iter().enumerate() {
+        draw_text_mut(
+            &mut img,
+            image::Rgb([40, 40, 40]),
+            4,
+            4 + i as i32 * (scale.y as i32 + 2),
+            scale,
+            &font,
+            line,
+        );
+    }
+
+    img.save(thumbpath)?;
+    Ok(())
+}
+
+// best effort, a variant that fails to encode is simply left out
+async fn generate_variants(diskfilepath: &str, mimetype: &str, size: usize) -> Vec<FileVariant> {
+    let mut wanted = Vec::new();
+    if mimetype.starts_with("video/") || mimetype == "image/gif" {
+        wanted.push(FileVariant::Preview);
+    } else if mimetype.starts_with("image/")
+        && !mimetype.contains("svg")
+        && size >= VARIANT_MIN_SIZE
+    {
+        if mimetype != "image/webp" {
+            wanted.push(FileVariant::Webp);
+        }
+        if *crate::statics::GENERATE_AVIF && mimetype != "image/avif" {
+            wanted.push(FileVariant::Avif);
+        }
+    }
+
+    let mut variants = Vec::new();
+    for variant in wanted {
+        let output = format!("{diskfilepath}{}", variant.suffix());
+        let args: Vec<String> = match variant {
+            FileVariant::Preview => vec![
+                "-t".into(),
+                PREVIEW_SECONDS.to_string(),
+                "-an".into(),
+                "-vf".into(),
+                "scale=320:-2".into(),
+                "-c:v".into(),
+                "libvpx-vp9".into(),
+                "-b:v".into(),
+                "0".into(),
+                "-crf".into(),
+                "40".into(),
+                "-deadline".into(),
+                "realtime".into(),
+            ],
+            FileVariant::Webp => vec![
+                "-c:v".into(),
+                "libwebp".into(),
+                "-quality".into(),
+                "80".into(),
+            ],
+            FileVariant::Avif => vec![
+                "-c:v".into(),
+                "libaom-av1".into(),
+                "-still-picture".into(),
+                "1".into(),
+                "-crf".into(),
+                "32".into(),
+            ],
+        };
+
+        let status = tokio::process::Command::new("ffmpeg")
+            .args(["-i", diskfilepath])
+            .args(&args)
+            .arg(&output)
+            .arg("-y")
+            .output()
+            .await;
+
+        match (status, tokio::fs::metadata(&output).await) {
+            (Ok(status), Ok(meta)) if status.status.success() && meta.len() > 0 => {
+                variants.push(variant)
+            }
+            _ => {
+                log::warn!(
+                    "Failed to generate {} variant of {diskfilepath}",
+                    variant.name()
+                );
+                let _ = tokio::fs::remove_file(&output).await;
+            }
+        }
+    }
+    variants
+}
+
+// probing and variant encoding can take a while, so it runs once the upload locks are released
+pub struct PendingEncode {
+    diskfilepath: String,
+    mimetype: String,
+    size: usize,
+}
+
+impl PendingEncode {
+    pub async fn run(self) -> (MediaInfo, Vec<FileVariant>) {
+        let media = probe_media(&self.diskfilepath, &self.mimetype).await;
+        let variants = generate_variants(&self.diskfilepath, &self.mimetype, self.size).await;
+        (media, variants)
     }
 }
 
@@ -185,14 +488,21 @@ pub struct File {
     pub extension: String,
     pub mimetype: String,
     pub data: Vec<u8>,
+    pub filename: Option<String>,
 }
 
 impl File {
-    pub fn new(extension: String, mimetype: String, data: Vec<u8>) -> Self {
+    pub fn new(
+        extension: String,
+        mimetype: String,
+        data: Vec<u8>,
+        filename: Option<String>,
+    ) -> Self {
         Self {
             extension,
             data,
             mimetype,
+            filename,
         }
     }
 
@@ -206,6 +516,7 @@ pub struct FileBuilder {
     extension: Option<String>,
     mimetype: Option<String>,
     data: Option<Vec<u8>>,
+    filename: Option<String>,
 }
 
 impl FileBuilder {
@@ -228,11 +539,17 @@ impl FileBuilder {
         self
     }
 
+    pub fn filename(mut self, filename: &str) -> Self {
+        self.filename = sanitize_filename(filename);
+        self
+    }
+
     pub fn build(self) -> Result<File> {
         Ok(File::new(
             self.extension.ok_or(anyhow!("Missing extension"))?,
             self.mimetype.ok_or(anyhow!("Missing mimetype"))?,
             self.data.ok_or(anyhow!("Missing data"))?,
+            self.filename,
         ))
     }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use common::structs::{SafePost, ThreadRef, WatchedThread};
+use deadpool::managed::Object;
+use diesel_async::{pooled_connection::AsyncDieselConnectionManager, AsyncPgConnection};
+
+use crate::{database_bindings::Database, filters::MemberToken};
+
+// the watched list itself lives in members.watching, members.seen remembers how far each thread was read
+
+pub async fn list(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    token: MemberToken,
+) -> Result<Vec<WatchedThread>> {
+    let member_hash = token.database_hash();
+    let member = database::get_member(conn, &member_hash).await?;
+    let seen = member.seen_posts();
+
+    // boards the member lost access to drop their watches on the next post, until then they're hidden
+    let mut allowed = HashMap::new();
+    let mut threads = vec![];
+    for thread in database::get_threads_by_id(conn, &member.watching).await? {
+        let access = match allowed.get(&thread.board) {
+            Some(access) => *access,
+            None => {
+                let access = database::check_access(conn, &member_hash, thread.board)
+                    .await
+                    .unwrap_or(false);
+                allowed.insert(thread.board, access);
+                access
+            }
+        };
+        if access {
+            threads.push(thread);
+        }
+    }
+
+    // the opening posts carry the board, post number and thumbnail, and come out of one batch
+    let op_ids = threads.iter().map(|t| t.post_id).collect::<Vec<i64>>();
+    let ops = database::get_posts_by_id(conn, &op_ids).await?;
+    let ops = ops
+        .iter()
+        .map(|p| p.id)
+        .zip(database::safe_posts(conn, &ops, &member_hash).await?)
+        .collect::<HashMap<i64, SafePost>>();
+
+    let seen_up_to = threads
+        .iter()
+        .flat_map(|t| {
+            // watches from before seen posts were tracked count every reply as unseen
+            let op = ops.get(&t.post_id)?;
+            Some((t.id, seen.get(&t.id).copied().unwrap_or(op.post_number)))
+        })
+        .collect::<Vec<(i64, i64)>>();
+    let activity = database::threads_activity(conn, &seen_up_to).await?;
+
+    let mut watched = vec![];
+    for thread in threads {
+        let Some(op) = ops.get(&thread.post_id) else {
+            continue;
+        };
+        let (latest_post_number, unseen) = activity
+            .get(&thread.id)
+            .copied()
+            .unwrap_or((op.post_number, 0));
+
+        watched.push(WatchedThread {
+            board_discriminator: op.board_discriminator.clone(),
+            thread_post_number: op.post_number,
+            topic: thread.topic,
+            thumbnail: op.file.as_ref().map(|file| file.claimed.thumbnail.clone()),
+            latest_post_number,
+            unseen,
+        });
+    }
+
+    // the most active threads first
+    watched.sort_by(|a, b| {
+        b.unseen
+            .cmp(&a.unseen)
+            .then(b.latest_post_number.cmp(&a.latest_post_number))
+    });
+    Ok(watched)
+}
+
+// returns the post number the thread is now marked as seen up to
+pub async fn mark_seen(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    disc: &str,
+    post: i64,
+    token: MemberToken,
+) -> Result<i64> {
+    let thread = Database::get_raw_thread(conn, disc, post).await?;
+    let member_hash = token.database_hash();
+    let member = database::get_member(conn, &member_hash).await?;
+    let (latest, _) = database::thread_activity(conn, thread.id, 0).await?;
+
+    // threads that aren't watched have nothing to count, so nothing is stored for them
+    if member.watching.contains(&thread.id) {
+        let mut seen = member.seen_posts();
+        if seen.insert(thread.id, latest) != Some(latest) {
+            database::set_seen_posts(conn, &member_hash, &seen).await?;
+        }
+    }
+    Ok(latest)
+}
+
+// keeps the seen map in step with the watched list, called whenever a thread is watched or unwatched
+pub async fn track_seen(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member: &database::Member,
+    thread_id: i64,
+    watching: bool,
+) -> Result<()> {
+    let mut seen = member.seen_posts();
+    if watching {
+        let (latest, _) = database::thread_activity(conn, thread_id, 0).await?;
+        seen.insert(thread_id, latest);
+    } else if seen.remove(&thread_id).is_none() {
+        return Ok(());
+    }
+    database::set_seen_posts(conn, &member.token_hash, &seen).await
+}
+
+// returns what's still watched afterwards
+pub async fn unwatch(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    threads: Vec<ThreadRef>,
+    token: MemberToken,
+) -> Result<Vec<WatchedThread>> {
+    for thread in threads {
+        Database::set_watching(
+            conn,
+            &thread.board_discriminator,
+            thread.thread_post_number,
+            token.clone(),
+            false,
+        )
+        .await?;
+    }
+    list(conn, token).await
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use common::structs::{PushStream, WsClientMessage, WsServerMessage};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    sync::mpsc::UnboundedSender,
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+use warp::ws::{Message, WebSocket};
+
+use crate::{filters::MemberToken, push::Delivery};
+
+// one connection can follow any number of streams, each one gets a task forwarding into the socket
+pub async fn handle(socket: WebSocket, token: MemberToken) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<WsServerMessage>();
+    let mut subscriptions = HashMap::<PushStream, JoinHandle<()>>::new();
+
+    let mut heartbeat =
+        tokio::time::interval(Duration::from_secs(crate::statics::WS_HEARTBEAT_SECONDS));
+    let timeout = Duration::from_secs(crate::statics::WS_TIMEOUT_SECONDS);
+    let mut last_seen = Instant::now();
+
+    loop {
+        tokio::select! {
+            incoming = ws_rx.next() => {
+                let message = match incoming {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                last_seen = Instant::now();
+                if message.is_close() {
+                    break;
+                }
+                // pongs and binary frames only count as a sign of life
+                let Ok(text) = message.to_str() else {
+                    continue;
+                };
+                let reply = match serde_json::from_str::<WsClientMessage>(text) {
+                    Ok(message) => handle_message(message, &token, &tx, &mut subscriptions).await,
+                    Err(e) => Some(WsServerMessage::Error {
+                        message: e.to_string(),
+                    }),
+                };
+                if let Some(reply) = reply {
+                    let _ = tx.send(reply);
+                }
+            }
+            outgoing = rx.recv() => {
+                let Some(outgoing) = outgoing else {
+                    break;
+                };
+                match serde_json::to_string(&outgoing) {
+                    Ok(text) => {
+                        if ws_tx.send(Message::text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::error!("error serializing websocket message: {}", e),
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > timeout {
+                    break;
+                }
+                if ws_tx.send(Message::ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // dropping the receivers lets the push holder clean up the senders on its next send
+    for (_, task) in subscriptions {
+        task.abort();
+    }
+    let _ = ws_tx.close().await;
+}
+
+async fn handle_message(
+    message: WsClientMessage,
+    token: &MemberToken,
+    tx: &UnboundedSender<WsServerMessage>,
+    subscriptions: &mut HashMap<PushStream, JoinHandle<()>>,
+) -> Option<WsServerMessage> {
+    Some(match message {
+        WsClientMessage::Subscribe {
+            stream,
+            last_event_id,
+        } => {
+            if subscriptions.contains_key(&stream) {
+                return Some(WsServerMessage::Subscribed { stream });
+            }
+            if let Err(e) = check_access(&stream, token).await {
+                return Some(WsServerMessage::Error {
+                    message: e.to_string(),
+                });
+            }
+
+            let ident = crate::push::stream_ident(&stream, &token.database_hash());
+            let (missed, mut rx) = crate::PUSH_NOTIFS
+                .lock()
+                .await
+                .attach(&ident, last_event_id);
+
+            // sent before the task starts so the client sees it ahead of any replayed events
+            let _ = tx.send(WsServerMessage::Subscribed {
+                stream: stream.clone(),
+            });
+
+            let task = tokio::spawn({
+                let tx = tx.clone();
+                let stream = stream.clone();
+                async move {
+                    for delivery in missed {
+                        if forward(&tx, &stream, delivery).is_err() {
+                            return;
+                        }
+                    }
+                    while let Some(delivery) = rx.recv().await {
+                        if forward(&tx, &stream, delivery).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+            subscriptions.insert(stream, task);
+            return None;
+        }
+        WsClientMessage::Unsubscribe { stream } => {
+            if let Some(task) = subscriptions.remove(&stream) {
+                task.abort();
+            }
+            WsServerMessage::Unsubscribed { stream }
+        }
+        WsClientMessage::Ping => WsServerMessage::Pong,
+    })
+}
+
+// same rules as the SSE endpoints, private boards only stream to members that could read them
+async fn check_access(stream: &PushStream, token: &MemberToken) -> Result<()> {
+    match stream {
+        PushStream::Member => Ok(()),
+        PushStream::Board(discriminator) | PushStream::Thread(discriminator, _) => {
+            let mut conn = crate::POOL
+                .get()
+                .await
+                .map_err(|_| anyhow!("error connecting to backend"))?;
+            crate::database_bindings::Database::get_board(&mut conn, discriminator, token.clone())
+                .await
+                .map_err(|_| anyhow!("Board not found"))?;
+            Ok(())
+        }
+    }
+}
+
+fn forward(
+    tx: &UnboundedSender<WsServerMessage>,
+    stream: &PushStream,
+    delivery: Delivery,
+) -> Result<()> {
+    let message = match crate::push::event_parts(&delivery.message) {
+        Ok((event, data)) => WsServerMessage::Event {
+            stream: stream.clone(),
+            id: delivery.id,
+            event: event.to_owned(),
+            data,
+        },
+        Err(e) => WsServerMessage::Error {
+            message: format!("{:?}", e),
+        },
+    };
+    tx.send(message).map_err(|_| anyhow!("websocket closed"))
+}
@@ -27,6 +27,18 @@ pub fn format_seconds(s: u64) -> String {
     out.trim().to_string()
 }
 
+// how much of an opening post the catalog shows, the backend and live catalog updates cut it the same way
+pub const CATALOG_EXCERPT_LENGTH: usize = 120;
+
+// the first `length` characters of a post, trimmed
+pub fn excerpt(content: &str, length: usize) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(length) {
+        Some((end, _)) => format!("{}...", &content[..end]),
+        None => content.to_owned(),
+    }
+}
+
 pub fn hash_with_salt(s: &str, salt: &str) -> String {
     let mut hasher = sha2::Sha256::new();
     hasher.update(s.as_bytes());
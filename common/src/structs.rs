@@ -10,6 +10,30 @@ use serde::{Deserialize, Serialize};
 pub struct BoardWithThreads {
     pub info: SafeBoard,
     pub threads: Vec<ThreadWithLazyPosts>,
+    // zero based, `pages` is how many there are at the requested page size
+    #[serde(default)]
+    pub page: i64,
+    #[serde(default)]
+    pub pages: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Catalog {
+    pub info: SafeBoard,
+    pub threads: Vec<CatalogThread>,
+}
+
+// just enough of a thread to show it in the catalog, in bump order
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CatalogThread {
+    pub thread_post_number: i64,
+    pub topic: String,
+    pub excerpt: String,
+    pub file: Option<FileInfo>,
+    pub reply_count: i64,
+    pub image_count: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_bump: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -37,6 +61,9 @@ pub struct ThreadWithPosts {
     pub post_count: i64,
     pub posts: Vec<SafePost>,
     pub topic: String,
+    // archived threads can still be read but not replied to
+    #[serde(default)]
+    pub archived: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -58,6 +85,8 @@ pub struct SafePost {
     pub content: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub replies: Vec<Reply>,
+    #[serde(default)]
+    pub sage: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -104,6 +133,97 @@ pub struct ClaimedFileInfo {
     pub thumbnail: String,
     pub hash: String,
     pub spoiler: bool,
+    // only used for matching file bans, sending it out would help tune images around them
+    #[serde(skip)]
+    pub phash: Option<i64>,
+    #[serde(default)]
+    pub variants: Vec<FileVariant>,
+    #[serde(default)]
+    pub media: MediaInfo,
+    // None when the uploader sent no name or the board hides filenames
+    #[serde(default)]
+    pub original_name: Option<String>,
+    // in bytes
+    #[serde(default)]
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub duration_ms: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+impl MediaInfo {
+    // formats the duration as m:ss, or h:mm:ss for anything an hour or longer
+    pub fn duration_string(&self) -> Option<String> {
+        self.duration_ms.map(|ms| {
+            let secs = ms / 1000;
+            let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+            if h > 0 {
+                format!("{h}:{m:02}:{s:02}")
+            } else {
+                format!("{m}:{s:02}")
+            }
+        })
+    }
+
+    pub fn dimensions_string(&self) -> Option<String> {
+        self.width.zip(self.height).map(|(w, h)| format!("{w}x{h}"))
+    }
+}
+
+impl ClaimedFileInfo {
+    pub fn variant_path(&self, variant: FileVariant) -> String {
+        format!("{}{}", self.path, variant.suffix())
+    }
+
+    pub fn has_variant(&self, variant: FileVariant) -> bool {
+        self.variants.contains(&variant)
+    }
+}
+
+// alternate encodings generated alongside the original upload, stored at `{path}{suffix}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FileVariant {
+    // short muted loop for videos and gifs
+    Preview,
+    Webp,
+    Avif,
+}
+
+impl FileVariant {
+    pub const ALL: [Self; 3] = [Self::Preview, Self::Webp, Self::Avif];
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Preview => "-preview.webm",
+            Self::Webp => "-variant.webp",
+            Self::Avif => "-variant.avif",
+        }
+    }
+
+    pub fn mimetype(&self) -> &'static str {
+        match self {
+            Self::Preview => "video/webm",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+        }
+    }
+
+    // the name stored in the database
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Preview => "preview",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.name() == name)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -120,12 +240,117 @@ pub struct CreateBoard {
     pub name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PendingUpload {
+    pub id: String,
+    pub filename: Option<String>,
+    pub mimetype: String,
+    pub size: u64,
+    // seconds until the upload is discarded if it isn't claimed by a post
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareDuration {
+    Hour,
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl ShareDuration {
+    pub const ALL: [ShareDuration; 4] = [
+        ShareDuration::Hour,
+        ShareDuration::Day,
+        ShareDuration::Week,
+        ShareDuration::Month,
+    ];
+    // nothing can be signed for longer than this
+    pub const LONGEST: ShareDuration = ShareDuration::Month;
+
+    pub fn seconds(&self) -> u64 {
+        match self {
+            ShareDuration::Hour => 60 * 60,
+            ShareDuration::Day => 60 * 60 * 24,
+            ShareDuration::Week => 60 * 60 * 24 * 7,
+            ShareDuration::Month => 60 * 60 * 24 * 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CreateShareLink {
+    #[serde(default)]
+    pub duration: ShareDuration,
+    pub max_views: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ShareLinkInfo {
+    pub id: i64,
+    pub path: String,
+    pub url: String,
+    // unix timestamps in seconds
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub max_views: Option<i64>,
+    pub views: i64,
+    pub revoked: bool,
+}
+
+// every field is optional so a single setting can be changed without touching the others
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BoardSettings {
+    pub text_previews: Option<bool>,
+    pub hide_filenames: Option<bool>,
+    // uploads allowed within QUOTA_WINDOW, 0 removes the limit
+    pub quota_bytes: Option<i64>,
+    pub quota_files: Option<i64>,
+    // replies after which a thread stops bumping, 0 removes the limit
+    pub bump_limit: Option<i64>,
+    // live threads kept on the board, 0 removes the limit
+    pub max_threads: Option<i64>,
+    pub overflow: Option<OverflowPolicy>,
+}
+
+// what happens to the least recently bumped thread once a board has more than its max_threads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    #[default]
+    Archive,
+    Delete,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Archive => "archive",
+            OverflowPolicy::Delete => "delete",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "archive" => Some(OverflowPolicy::Archive),
+            "delete" => Some(OverflowPolicy::Delete),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CreatePost {
     pub file: Option<CreateFile>,
     pub content: String,
     pub author: Option<String>,
     pub moderator: bool,
+    // replies that leave the thread where it is, sage is shown on the post and silent (moderators only) isn't
+    #[serde(default)]
+    pub sage: bool,
+    #[serde(default)]
+    pub silent: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -220,13 +445,198 @@ pub struct Banner {
     pub href: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InboxKind {
+    WatchedPost,
+    Reply,
+    // moderator actions on your own posts
+    PostDeleted,
+    FileBanned,
+}
+
+impl InboxKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InboxKind::WatchedPost => "watched_post",
+            InboxKind::Reply => "reply",
+            InboxKind::PostDeleted => "post_deleted",
+            InboxKind::FileBanned => "file_banned",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "watched_post" => Some(InboxKind::WatchedPost),
+            "reply" => Some(InboxKind::Reply),
+            "post_deleted" => Some(InboxKind::PostDeleted),
+            "file_banned" => Some(InboxKind::FileBanned),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InboxItem {
+    pub id: i64,
+    pub kind: InboxKind,
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+    pub post_number: i64,
+    pub summary: String,
+    pub thumbnail: Option<String>,
+    pub created_at: u64,
+    pub read: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inbox {
+    pub items: Vec<InboxItem>,
+    pub unread: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchedThread {
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+    pub topic: String,
+    pub thumbnail: Option<String>,
+    pub latest_post_number: i64,
+    // posts since the member last had the thread open
+    pub unseen: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ThreadRef {
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub post: SafePost,
+    pub topic: String,
+    // whether the post opened its thread, only those can match on the topic
+    pub is_thread: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    // the stemmed words that were searched for, for highlighting
+    pub terms: Vec<String>,
+    pub page: i64,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportState {
+    Pending,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExportStatus {
+    pub id: String,
+    pub state: ExportState,
+    // what the archive is saved as, "{board}-{thread}.zip" or "{board}.zip"
+    pub name: String,
+    // only set once the archive is ready
+    pub download: Option<String>,
+    pub error: Option<String>,
+    // in bytes
+    pub size: Option<u64>,
+    // unix timestamps in seconds, the archive is deleted after expires_at
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum PushMessage {
     Open,
     NewPost(Arc<SafePost>),
+    // sent to the authors of the posts it quotes
+    ReplyToYou(Arc<SafePost>),
+    // everything that lands in a member's inbox, with the id to mark it read by
+    Inbox(Arc<InboxItem>),
+    // the rest are only sent to board and thread streams
+    NewThread(Arc<ThreadWithLazyPosts>),
+    PostDeleted(DeletedPost),
+    // carries the reply that bumped the thread
+    ThreadBumped(Arc<SafePost>),
+    // a reply that didn't bump, the thread keeps its place
+    ThreadReplied(Arc<SafePost>),
+    ThreadStatusChanged(ThreadStatusChange),
     Close,
 }
 
+// which set of events a realtime connection is listening to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PushStream {
+    // the member's own notifications
+    Member,
+    Board(String),
+    Thread(String, i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    Subscribe {
+        stream: PushStream,
+        last_event_id: Option<u64>,
+    },
+    Unsubscribe {
+        stream: PushStream,
+    },
+    Ping,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    // same event names and json data as the SSE endpoints
+    Event {
+        stream: PushStream,
+        id: Option<u64>,
+        event: String,
+        data: Option<String>,
+    },
+    Subscribed {
+        stream: PushStream,
+    },
+    Unsubscribed {
+        stream: PushStream,
+    },
+    Pong,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeletedPost {
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+    pub post_number: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadStatus {
+    Deleted,
+    Archived,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ThreadStatusChange {
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+    pub status: ThreadStatus,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SubscriptionData {
     pub endpoint: String,
@@ -238,3 +648,34 @@ pub struct SubscriptionKeys {
     pub auth: String,
     pub p256dh: String,
 }
+
+// only affects web pushes, the notifications shown while the site is open always come through
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationPreferences {
+    pub push: bool,
+    // only replies to your own posts, nothing from watched threads
+    pub mentions_only: bool,
+    pub quiet_hours: Option<QuietHours>,
+    // pushes are collected and sent as one summary every this many minutes
+    pub digest_minutes: Option<u32>,
+}
+
+impl Default for NotificationPreferences {
+    fn default() -> Self {
+        Self {
+            push: true,
+            mentions_only: false,
+            quiet_hours: None,
+            digest_minutes: None,
+        }
+    }
+}
+
+// hours of the day in `timezone`, wraps around midnight when start is after end
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+    pub timezone: String,
+}
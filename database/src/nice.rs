@@ -25,6 +25,13 @@ pub struct Board {
     pub discriminator: String,
     pub post_count: i64,
     pub private: bool,
+    pub text_previews: bool,
+    pub hide_filenames: bool,
+    pub quota_bytes: Option<i64>,
+    pub quota_files: Option<i64>,
+    pub bump_limit: Option<i64>,
+    pub max_threads: Option<i64>,
+    pub overflow: String,
 }
 
 impl Board {
@@ -32,18 +39,32 @@ impl Board {
         &self,
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         token: &str,
+        page: i64,
+        page_size: i64,
     ) -> Result<BoardWithThreads> {
         use crate::threads::dsl::*;
-        let mut bthreads = Vec::new();
-        for thread in threads
+        // archived threads are only reachable by their url
+        let thread_count = threads
             .filter(board.eq(self.id))
+            .filter(archived_at.is_null())
+            .select(count(id))
+            .first::<i64>(conn)
+            .await?;
+        let pages = (thread_count + page_size - 1) / page_size;
+        // the first page always exists, an empty board just has nothing on it
+        let offset = page
+            .checked_mul(page_size)
+            .filter(|_| page >= 0 && (page == 0 || page < pages))
+            .ok_or_else(|| anyhow::anyhow!("Page out of range"))?;
+        let page_threads = threads
+            .filter(board.eq(self.id))
+            .filter(archived_at.is_null())
             .order(latest_post.desc())
+            .offset(offset)
+            .limit(page_size)
             .load::<Thread>(conn)
-            .await?
-            .iter()
-        {
-            bthreads.push(thread.with_lazy_posts(conn, token).await?);
-        }
+            .await?;
+        let bthreads = Thread::many_with_lazy_posts(conn, &page_threads, token).await?;
         Ok(BoardWithThreads {
             info: SafeBoard {
                 name: self.name.clone(),
@@ -51,6 +72,92 @@ impl Board {
                 private: self.private,
             },
             threads: bthreads,
+            page,
+            pages,
+        })
+    }
+
+    // three queries for the whole board no matter how many threads it has, callers check access
+    pub async fn catalog(
+        &self,
+        conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    ) -> Result<Catalog> {
+        use crate::{files, posts, threads};
+
+        let rows = threads::table
+            .filter(threads::board.eq(self.id))
+            .filter(threads::archived_at.is_null())
+            .inner_join(posts::table.on(posts::id.eq(threads::post_id)))
+            .left_join(files::table.on(files::id.eq(threads::post_id)))
+            .order(threads::latest_post.desc())
+            .select((
+                threads::all_columns,
+                posts::all_columns,
+                files::all_columns.nullable(),
+            ))
+            .load::<(Thread, Post, Option<File>)>(conn)
+            .await?;
+
+        // (thread, posts including the op, posts with a file)
+        let activity = posts::table
+            .left_join(files::table.on(files::id.eq(posts::id)))
+            .filter(posts::board.eq(self.id))
+            .group_by(posts::thread)
+            .select((posts::thread, count(posts::id), count(files::id.nullable())))
+            .load::<(i64, i64, i64)>(conn)
+            .await?
+            .into_iter()
+            .map(|(thread, total, images)| (thread, (total, images)))
+            .collect::<std::collections::HashMap<_, _>>();
+
+        // the last bump is when the post latest_post points at was made, sage and silent replies don't move
+        // it so it agrees with the order
+        let bumps = posts::table
+            .filter(
+                posts::id.eq_any(
+                    rows.iter()
+                        .map(|(thread, _, _)| thread.latest_post)
+                        .collect::<Vec<i64>>(),
+                ),
+            )
+            .select((posts::id, posts::timestamp))
+            .load::<(i64, chrono::NaiveDateTime)>(conn)
+            .await?
+            .into_iter()
+            .collect::<std::collections::HashMap<_, _>>();
+
+        // one spoiler image stands in for every spoilered op
+        let spoiler = if rows
+            .iter()
+            .any(|(_, _, file)| file.as_ref().is_some_and(|f| f.spoiler))
+        {
+            get_random_spoiler(conn, self.id).await?
+        } else {
+            String::new()
+        };
+
+        Ok(Catalog {
+            info: self.safe(),
+            threads: rows
+                .into_iter()
+                .map(|(thread, op, file)| {
+                    let (total, images) = activity.get(&thread.id).copied().unwrap_or((1, 0));
+                    let latest = bumps.get(&thread.latest_post).copied();
+                    CatalogThread {
+                        thread_post_number: op.post_number,
+                        topic: thread.topic,
+                        excerpt: common::excerpt(&op.content, common::CATALOG_EXCERPT_LENGTH),
+                        file: file.map(|f| f.info_on(self, spoiler.clone())),
+                        reply_count: total - 1,
+                        image_count: images,
+                        created_at: TimeZone::from_utc_datetime(&chrono::Utc, &op.timestamp),
+                        last_bump: TimeZone::from_utc_datetime(
+                            &chrono::Utc,
+                            &latest.unwrap_or(op.timestamp),
+                        ),
+                    }
+                })
+                .collect(),
         })
     }
 
@@ -61,6 +168,10 @@ impl Board {
             private: self.private,
         }
     }
+
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        OverflowPolicy::from_name(&self.overflow).unwrap_or_default()
+    }
 }
 
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
@@ -70,6 +181,7 @@ pub struct Thread {
     pub post_id: i64,
     pub latest_post: i64,
     pub topic: String,
+    pub archived_at: Option<chrono::NaiveDateTime>,
 }
 
 impl Thread {
@@ -81,30 +193,22 @@ impl Thread {
         use crate::posts::dsl::*;
         let tposts = posts
             .filter(thread.eq(self.id))
-            .filter(id.ne(self.post_id))
             .order(post_number.asc())
             .load::<Post>(conn)
             .await?;
-        let mut safeposts = Vec::new();
-        for post in tposts.iter() {
-            safeposts.push(post.safe(conn, token).await?);
-        }
-        let post_count = posts
-            .select(count(id))
-            .filter(thread.eq(self.id))
-            .filter(id.ne(self.post_id))
-            .first::<i64>(conn)
-            .await?;
-        let tpost = posts
-            .filter(id.eq(self.post_id))
-            .first::<Post>(conn)
-            .await?;
+        let mut safeposts = safe_posts(conn, &tposts, token).await?;
+        let op = tposts
+            .iter()
+            .position(|p| p.id == self.post_id)
+            .ok_or_else(|| anyhow::anyhow!("Thread post not found"))?;
+        let tpost = safeposts.remove(op);
         Ok(ThreadWithPosts {
             board: self.board,
-            post_count,
+            post_count: safeposts.len() as i64,
             topic: self.topic.clone(),
-            thread_post: tpost.safe(conn, token).await?,
+            thread_post: tpost,
             posts: safeposts,
+            archived: self.archived_at.is_some(),
         })
     }
     pub async fn with_lazy_posts(
@@ -112,36 +216,81 @@ impl Thread {
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         token: &str,
     ) -> Result<ThreadWithLazyPosts> {
+        Self::many_with_lazy_posts(conn, std::slice::from_ref(self), token)
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Thread not found"))
+    }
+    // a board page shows several threads at once, their posts are loaded together
+    pub async fn many_with_lazy_posts(
+        conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+        list: &[Thread],
+        token: &str,
+    ) -> Result<Vec<ThreadWithLazyPosts>> {
         use crate::posts::dsl::*;
-        let tposts: Vec<Post> = posts
-            .filter(thread.eq(self.id))
-            .filter(id.ne(self.post_id))
-            .order(post_number.desc())
-            .limit(5)
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Array, BigInt, Bool};
+        use std::collections::HashMap;
+
+        let thread_ids = list.iter().map(|t| t.id).collect::<Vec<i64>>();
+        let op_ids = list.iter().map(|t| t.post_id).collect::<Vec<i64>>();
+        let post_counts = posts
+            .filter(thread.eq_any(&thread_ids))
+            .filter(id.ne_all(&op_ids))
+            .group_by(thread)
+            .select((thread, count(id)))
+            .load::<(i64, i64)>(conn)
+            .await?
+            .into_iter()
+            .collect::<HashMap<i64, i64>>();
+
+        // the last five replies of every thread in one go
+        let in_preview = sql::<Bool>(
+            "posts.id IN (SELECT ranked.id FROM (SELECT posts.id, ROW_NUMBER() OVER \
+             (PARTITION BY posts.thread ORDER BY posts.post_number DESC) AS n FROM posts \
+             WHERE posts.thread = ANY(",
+        )
+        .bind::<Array<BigInt>, _>(thread_ids.clone())
+        .sql(") AND posts.id <> ALL(")
+        .bind::<Array<BigInt>, _>(op_ids.clone())
+        .sql(")) ranked WHERE ranked.n <= 5)");
+        let latest = posts
+            .filter(in_preview)
+            .order((thread.asc(), post_number.asc()))
             .load::<Post>(conn)
             .await?;
-        let post_count = posts
-            .select(count(id))
-            .filter(thread.eq(self.id))
-            .filter(id.ne(self.post_id))
-            .first::<i64>(conn)
-            .await?;
-        let mut safeposts = Vec::new();
-        for post in tposts.iter() {
-            safeposts.push(post.safe(conn, token).await?);
+
+        let mut previews = HashMap::<i64, Vec<i64>>::new();
+        for p in &latest {
+            previews.entry(p.thread).or_default().push(p.id);
         }
-        safeposts.reverse();
-        let tpost = posts
-            .filter(id.eq(self.post_id))
-            .first::<Post>(conn)
-            .await?;
-        Ok(ThreadWithLazyPosts {
-            board: self.board,
-            post_count,
-            topic: self.topic.clone(),
-            thread_post: tpost.safe(conn, token).await?,
-            posts: safeposts,
-        })
+        let mut tposts = posts.filter(id.eq_any(&op_ids)).load::<Post>(conn).await?;
+        tposts.extend(latest);
+
+        let safeposts = tposts
+            .iter()
+            .map(|p| p.id)
+            .zip(safe_posts(conn, &tposts, token).await?)
+            .collect::<HashMap<i64, SafePost>>();
+
+        list.iter()
+            .map(|t| {
+                let preview = previews.remove(&t.id).unwrap_or_default();
+                Ok(ThreadWithLazyPosts {
+                    board: t.board,
+                    post_count: post_counts.get(&t.id).copied().unwrap_or_default(),
+                    topic: t.topic.clone(),
+                    thread_post: safeposts
+                        .get(&t.post_id)
+                        .cloned()
+                        .ok_or_else(|| anyhow::anyhow!("Thread post not found"))?,
+                    posts: preview
+                        .iter()
+                        .flat_map(|p| safeposts.get(p).cloned())
+                        .collect(),
+                })
+            })
+            .collect()
     }
 }
 
@@ -159,6 +308,7 @@ pub struct Post {
     pub content: String,
     pub timestamp: chrono::NaiveDateTime,
     pub replies_to: Vec<i64>,
+    pub sage: bool,
 }
 
 impl Post {
@@ -167,56 +317,232 @@ impl Post {
         conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
         token: &str,
     ) -> Result<SafePost> {
-        use crate::posts::dsl::*;
-        let replies = posts
-            .select(Self::as_select())
-            .load::<Self>(conn)
+        safe_posts(conn, std::slice::from_ref(self), token)
             .await?
-            .iter()
-            .flat_map(|p| p.replies_to.contains(&self.id).then_some(p.id))
-            .collect::<Vec<i64>>();
+            .pop()
+            .ok_or_else(|| anyhow::anyhow!("Post not found"))
+    }
+}
+
+// the replies, files, boards and thread numbers of a whole batch come from one query each, results keep the batch's order
+pub async fn safe_posts(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    batch: &[Post],
+    token: &str,
+) -> Result<Vec<SafePost>> {
+    use crate::{boards, files, post_replies, posts, threads};
+    use std::collections::HashMap;
+
+    if batch.is_empty() {
+        return Ok(vec![]);
+    }
+    let ids = batch.iter().map(|p| p.id).collect::<Vec<i64>>();
+
+    // (quoted post, replying post's number, its thread, its board), oldest reply first
+    let backlinks = post_replies::table
+        .inner_join(posts::table.on(posts::id.eq(post_replies::post)))
+        .filter(post_replies::quoted.eq_any(&ids))
+        .order(posts::id.asc())
+        .select((
+            post_replies::quoted,
+            posts::post_number,
+            posts::thread,
+            posts::board,
+        ))
+        .load::<(i64, i64, i64, i64)>(conn)
+        .await?;
 
-        let mut newreplies = Vec::new();
-        for reply in replies {
-            newreplies.push(get_reply_info(reply, self.board, conn).await?);
+    let mut thread_ids = batch
+        .iter()
+        .map(|p| p.thread)
+        .chain(backlinks.iter().map(|(_, _, thread, _)| *thread))
+        .collect::<Vec<i64>>();
+    thread_ids.sort_unstable();
+    thread_ids.dedup();
+    let thread_numbers = threads::table
+        .inner_join(posts::table.on(posts::id.eq(threads::post_id)))
+        .filter(threads::id.eq_any(&thread_ids))
+        .select((threads::id, posts::post_number))
+        .load::<(i64, i64)>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashMap<i64, i64>>();
+
+    let mut board_ids = batch
+        .iter()
+        .map(|p| p.board)
+        .chain(backlinks.iter().map(|(_, _, _, board)| *board))
+        .collect::<Vec<i64>>();
+    board_ids.sort_unstable();
+    board_ids.dedup();
+    let board_list = boards::table
+        .filter(boards::id.eq_any(&board_ids))
+        .load::<Board>(conn)
+        .await?
+        .into_iter()
+        .map(|b| (b.id, b))
+        .collect::<HashMap<i64, Board>>();
+
+    // private boards are checked and spoilers picked once per board rather than once per file
+    let post_boards = batch
+        .iter()
+        .map(|p| (p.id, p.board))
+        .collect::<HashMap<i64, i64>>();
+    let mut allowed = HashMap::new();
+    let mut spoilers = HashMap::new();
+    let mut file_infos = HashMap::new();
+    for file in files::table
+        .filter(files::id.eq_any(&ids))
+        .load::<File>(conn)
+        .await?
+    {
+        let board = post_boards
+            .get(&file.id)
+            .and_then(|b| board_list.get(b))
+            .ok_or_else(|| anyhow::anyhow!("Board not found"))?;
+        if board.private {
+            let access = match allowed.get(&board.id) {
+                Some(access) => *access,
+                None => {
+                    let access = check_access(conn, token, board.id).await?;
+                    allowed.insert(board.id, access);
+                    access
+                }
+            };
+            if !access {
+                return Err(anyhow::anyhow!("Not authorized to view this file"));
+            }
         }
+        let spoiler_img = if file.spoiler {
+            match spoilers.get(&board.id) {
+                Some(spoiler) => spoiler.clone(),
+                None => {
+                    let spoiler = get_random_spoiler(conn, board.id).await?;
+                    spoilers.insert(board.id, spoiler.clone());
+                    spoiler
+                }
+            }
+        } else {
+            String::new()
+        };
+        file_infos.insert(file.id, file.info_on(board, spoiler_img));
+    }
 
-        let t = get_file(conn, Some(token.to_owned()), self.id).await;
+    let discriminator = |board: i64| {
+        board_list
+            .get(&board)
+            .map(|b| b.discriminator.clone())
+            .ok_or_else(|| anyhow::anyhow!("Board not found"))
+    };
+    let thread_number = |thread: i64| {
+        thread_numbers
+            .get(&thread)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Thread not found"))
+    };
 
-        let board_discrim = get_board_discrim(self.board, conn).await?;
+    let mut replies: HashMap<i64, Vec<Reply>> = HashMap::new();
+    for (target, number, thread, board) in backlinks {
+        replies.entry(target).or_default().push(Reply {
+            post_number: number.to_string(),
+            thread_post_number: Some(thread_number(thread)?.to_string()),
+            board_discriminator: discriminator(board)?,
+            external: post_boards.get(&target) != Some(&board),
+        });
+    }
 
-        Ok(SafePost {
-            post_number: self.post_number,
-            file: t?,
-            thread_post_number: thread_post_number(self.thread, conn).await?,
-            board_discriminator: board_discrim,
-            author: User::load_from(self.author.clone(), self.moderator),
-            content: self.content.clone(),
-            timestamp: TimeZone::from_utc_datetime(&chrono::Utc, &self.timestamp),
-            replies: newreplies,
+    batch
+        .iter()
+        .map(|p| {
+            Ok(SafePost {
+                post_number: p.post_number,
+                file: file_infos.get(&p.id).cloned(),
+                thread_post_number: thread_number(p.thread)?,
+                board_discriminator: discriminator(p.board)?,
+                author: User::load_from(p.author.clone(), p.moderator),
+                content: p.content.clone(),
+                timestamp: TimeZone::from_utc_datetime(&chrono::Utc, &p.timestamp),
+                replies: replies.get(&p.id).cloned().unwrap_or_default(),
+                sage: p.sage,
+            })
         })
+        .collect()
+}
+
+// written alongside the post, duplicate quotes of the same post are stored once
+pub async fn add_post_replies(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    reply: i64,
+    targets: &[i64],
+) -> Result<()> {
+    use crate::post_replies::dsl::*;
+
+    if targets.is_empty() {
+        return Ok(());
     }
+    diesel::insert_into(post_replies)
+        .values(
+            targets
+                .iter()
+                .map(|t| (post.eq(reply), quoted.eq(*t)))
+                .collect::<Vec<_>>(),
+        )
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
+
+    Ok(())
 }
 
-pub async fn get_reply_info(
-    tid: i64,
-    og_board: i64,
+// remembers which member wrote a post, so they can be told about replies without rehashing every member
+pub async fn add_post_author(
     conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
-) -> Result<Reply> {
-    use crate::posts::dsl::*;
-    let post = posts.filter(id.eq(tid)).first::<Post>(conn).await?;
-    let external = post.board != og_board;
+    post_id: i64,
+    member_hash: &str,
+) -> Result<()> {
+    use crate::{members, post_authors};
+
+    let Some(member_id) = members::table
+        .filter(members::token_hash.eq(member_hash))
+        .select(members::id)
+        .first::<i64>(conn)
+        .await
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    diesel::insert_into(post_authors::table)
+        .values((
+            post_authors::post.eq(post_id),
+            post_authors::member.eq(member_id),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await?;
 
-    let thisboard = get_board_discrim(post.board, conn).await?;
+    Ok(())
+}
 
-    let post_thread = thread_post_number(post.thread, conn).await?;
+// the members that wrote any of the given posts
+pub async fn get_post_author_members(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    post_ids: &[i64],
+) -> Result<Vec<Member>> {
+    use crate::{members, post_authors};
+
+    let authors = members::table
+        .filter(
+            members::id.eq_any(
+                post_authors::table
+                    .filter(post_authors::post.eq_any(post_ids))
+                    .select(post_authors::member),
+            ),
+        )
+        .load::<Member>(conn)
+        .await?;
 
-    Ok(Reply {
-        post_number: post.post_number.to_string(),
-        thread_post_number: Some(post_thread.to_string()),
-        board_discriminator: thisboard,
-        external,
-    })
+    Ok(authors)
 }
 
 pub async fn get_board_discrim(
@@ -287,12 +613,60 @@ pub async fn get_file_from_path(
     Ok(file)
 }
 
+pub struct FileCacheInfo {
+    pub hash: String,
+    pub board: i64,
+    pub private: bool,
+    // already None if the board hides filenames
+    pub original_name: Option<String>,
+}
+
+// just enough to answer a request for a stored file without building the whole FileInfo
+pub async fn get_file_cache_info(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<Option<FileCacheInfo>> {
+    use crate::{boards, files, posts};
+    use diesel::result::OptionalExtension;
+
+    let info = files::table
+        .inner_join(posts::table.inner_join(boards::table))
+        .filter(files::filepath.eq(path))
+        .select((
+            files::hash,
+            boards::id,
+            boards::private,
+            files::original_name,
+            boards::hide_filenames,
+        ))
+        .first::<(String, i64, bool, Option<String>, bool)>(conn)
+        .await
+        .optional()?;
+
+    Ok(info.map(
+        |(hash, board, private, original_name, hide_filenames)| FileCacheInfo {
+            hash,
+            board,
+            private,
+            original_name: original_name.filter(|_| !hide_filenames),
+        },
+    ))
+}
+
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct File {
     pub id: i64,
     pub filepath: String,
     pub hash: String,
     pub spoiler: bool,
+    pub phash: Option<i64>,
+    pub variants: Vec<String>,
+    pub duration_ms: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub original_name: Option<String>,
+    pub size: i64,
+    pub uploader: Option<String>,
 }
 
 impl File {
@@ -325,25 +699,49 @@ impl File {
             }
         }
 
-        let thumbnail = if self.spoiler {
+        let spoiler_img = if self.spoiler {
             get_random_spoiler(conn, board.id).await?
+        } else {
+            String::new()
+        };
+
+        Ok(self.info_on(&board, spoiler_img))
+    }
+
+    // for callers that already have the board, `spoiler_img` is only used when the file is spoilered
+    pub fn info_on(&self, board: &Board, spoiler_img: String) -> FileInfo {
+        let thumbnail = if self.spoiler {
+            spoiler_img
         } else {
             format!("{}-thumb.jpg", self.filepath)
         };
 
-        Ok(FileInfo {
+        FileInfo {
             claimed: ClaimedFileInfo {
                 path: self.filepath.clone(),
                 thumbnail,
                 hash: self.hash.clone(),
                 spoiler: self.spoiler,
+                phash: self.phash,
+                variants: self
+                    .variants
+                    .iter()
+                    .flat_map(|v| FileVariant::from_name(v))
+                    .collect(),
+                media: MediaInfo {
+                    duration_ms: self.duration_ms,
+                    width: self.width,
+                    height: self.height,
+                },
+                original_name: self.original_name.clone().filter(|_| !board.hide_filenames),
+                size: self.size,
             },
             board: MicroBoardInfo {
-                discriminator: board.discriminator,
+                discriminator: board.discriminator.clone(),
                 private: board.private,
                 id: board.id,
             },
-        })
+        }
     }
     // pub fn raw_info(&self) -> FileInfo {
     //     let thumbnail = format!("{}-thumb.jpg", self.filepath);
@@ -381,6 +779,72 @@ pub struct Spoiler {
     pub boards: Vec<i64>,
 }
 
+#[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TrashedFile {
+    pub id: i64,
+    pub filepath: String,
+    pub trashed_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileBan {
+    pub id: i64,
+    pub hash: String,
+    pub phash: Option<i64>,
+    pub board: Option<i64>,
+    pub reason: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShareLink {
+    pub id: i64,
+    pub filepath: String,
+    pub creator: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub max_views: Option<i64>,
+    pub views: i64,
+    pub revoked: bool,
+    pub kid: i32,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct InboxEntry {
+    pub id: i64,
+    pub recipient: String,
+    pub kind: String,
+    pub board_discriminator: String,
+    pub thread_post_number: i64,
+    pub post_number: i64,
+    pub summary: String,
+    pub thumbnail: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub read: bool,
+}
+
+#[derive(Queryable, Debug, Clone, PartialEq, Eq)]
+pub struct PushJob {
+    pub id: i64,
+    pub member: i64,
+    pub endpoint: String,
+    pub subscription: serde_json::Value,
+    pub payload: String,
+    pub attempts: i32,
+    pub next_attempt: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_error: Option<String>,
+    // held back by quiet hours or a digest, sent together with the member's other digest jobs
+    pub digest: bool,
+}
+
+pub struct QueuedPush {
+    pub member: i64,
+    pub subscription: SubscriptionData,
+    pub send_at: chrono::NaiveDateTime,
+    pub digest: bool,
+}
+
 #[derive(Queryable, Debug, Clone, PartialEq, Eq)]
 pub struct Member {
     pub id: i64,
@@ -388,6 +852,11 @@ pub struct Member {
     pub push_data: serde_json::Value,
     pub watching: Vec<i64>,
     pub admin: bool,
+    pub notification_prefs: serde_json::Value,
+    // threads that are still watched but don't send web pushes
+    pub muted: Vec<i64>,
+    // thread id to the last post number the member saw in it, only kept for watched threads
+    pub seen: serde_json::Value,
 }
 
 impl Member {
@@ -395,6 +864,13 @@ impl Member {
         let data = serde_json::from_value(self.push_data.clone())?;
         Ok(data)
     }
+    // members that never saved any get the defaults
+    pub fn notification_preferences(&self) -> NotificationPreferences {
+        serde_json::from_value(self.notification_prefs.clone()).unwrap_or_default()
+    }
+    pub fn seen_posts(&self) -> std::collections::HashMap<i64, i64> {
+        serde_json::from_value(self.seen.clone()).unwrap_or_default()
+    }
 }
 
 pub async fn get_random_spoiler(
@@ -423,6 +899,1183 @@ pub async fn get_random_spoiler(
     //     .ok_or_else(|| anyhow::anyhow!("No spoilers found!"))
 }
 
+// (bytes, files) uploaded under a quota key after `since`
+pub async fn get_member_upload_usage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    quota_key: &str,
+    since: chrono::NaiveDateTime,
+) -> Result<(i64, i64)> {
+    use crate::{files, posts};
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::BigInt;
+
+    let usage = files::table
+        .inner_join(posts::table)
+        .filter(files::uploader.eq(quota_key))
+        .filter(posts::timestamp.gt(since))
+        .select((
+            sql::<BigInt>("COALESCE(SUM(files.size), 0)::BIGINT"),
+            count_star(),
+        ))
+        .first::<(i64, i64)>(conn)
+        .await?;
+
+    Ok(usage)
+}
+
+// (bytes, files) posted to a board after `since`
+pub async fn get_board_upload_usage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    board: i64,
+    since: chrono::NaiveDateTime,
+) -> Result<(i64, i64)> {
+    use crate::{files, posts};
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::BigInt;
+
+    let usage = files::table
+        .inner_join(posts::table)
+        .filter(posts::board.eq(board))
+        .filter(posts::timestamp.gt(since))
+        .select((
+            sql::<BigInt>("COALESCE(SUM(files.size), 0)::BIGINT"),
+            count_star(),
+        ))
+        .first::<(i64, i64)>(conn)
+        .await?;
+
+    Ok(usage)
+}
+
+// (board discriminator, bytes, files) for every board with stored files, largest first
+pub async fn get_board_usage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<(String, i64, i64)>> {
+    use crate::{boards, files, posts};
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::BigInt;
+
+    let usage = files::table
+        .inner_join(posts::table.inner_join(boards::table))
+        .group_by(boards::discriminator)
+        .select((
+            boards::discriminator,
+            sql::<BigInt>("COALESCE(SUM(files.size), 0)::BIGINT"),
+            count_star(),
+        ))
+        .order(sql::<BigInt>("SUM(files.size)").desc())
+        .load::<(String, i64, i64)>(conn)
+        .await?;
+
+    Ok(usage)
+}
+
+// (mimetype, bytes, files) across all stored files, largest first
+pub async fn get_mimetype_usage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<(String, i64, i64)>> {
+    use crate::files;
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::{BigInt, Text};
+
+    // paths look like /files/{type}/{subtype}/{name}
+    const MIMETYPE: &str =
+        "COALESCE(substring(files.filepath from '^/files/(.*)/[^/]*$'), 'unknown')";
+
+    let usage = files::table
+        .group_by(sql::<Text>(MIMETYPE))
+        .select((
+            sql::<Text>(MIMETYPE),
+            sql::<BigInt>("COALESCE(SUM(files.size), 0)::BIGINT"),
+            count_star(),
+        ))
+        .order(sql::<BigInt>("SUM(files.size)").desc())
+        .load::<(String, i64, i64)>(conn)
+        .await?;
+
+    Ok(usage)
+}
+
+// (quota key, bytes, files) for each uploader, largest first. the key is the salted quota hash, it
+// tells uploaders apart without matching any member
+pub async fn get_uploader_usage(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    limit: i64,
+) -> Result<Vec<(String, i64, i64)>> {
+    use crate::files;
+    use diesel::dsl::{count_star, sql};
+    use diesel::sql_types::BigInt;
+
+    let usage = files::table
+        .filter(files::uploader.is_not_null())
+        .group_by(files::uploader)
+        .select((
+            files::uploader.assume_not_null(),
+            sql::<BigInt>("COALESCE(SUM(files.size), 0)::BIGINT"),
+            count_star(),
+        ))
+        .order(sql::<BigInt>("SUM(files.size)").desc())
+        .limit(limit)
+        .load::<(String, i64, i64)>(conn)
+        .await?;
+
+    Ok(usage)
+}
+
+// (id, filepath) of file rows stored before sizes were recorded
+pub async fn get_unsized_files(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<(i64, String)>> {
+    use crate::files::dsl::*;
+
+    let unsized_files = files
+        .filter(size.eq(0))
+        .select((id, filepath))
+        .load::<(i64, String)>(conn)
+        .await?;
+
+    Ok(unsized_files)
+}
+
+pub async fn set_file_size(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    file_id: i64,
+    new_size: i64,
+) -> Result<()> {
+    use crate::files::dsl::*;
+
+    diesel::update(files.filter(id.eq(file_id)))
+        .set(size.eq(new_size))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+// token hashes of members that still have file rows under the key quotas used before they were salted
+pub async fn get_unsalted_uploaders(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<String>> {
+    use crate::{files, members};
+
+    let uploaders = members::table
+        .filter(
+            members::token_hash.eq_any(
+                files::table
+                    .filter(files::uploader.is_not_null())
+                    .select(files::uploader.assume_not_null()),
+            ),
+        )
+        .select(members::token_hash)
+        .load::<String>(conn)
+        .await?;
+
+    Ok(uploaders)
+}
+
+pub async fn rekey_uploader(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    old_key: &str,
+    new_key: &str,
+) -> Result<usize> {
+    use crate::files::dsl::*;
+
+    let updated = diesel::update(files.filter(uploader.eq(old_key)))
+        .set(uploader.eq(new_key))
+        .execute(conn)
+        .await?;
+
+    Ok(updated)
+}
+
+// every path under the file storage that is still referenced by a row somewhere, thumbnails included
+pub async fn get_referenced_paths(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<std::collections::HashSet<String>> {
+    let mut paths = std::collections::HashSet::new();
+
+    for (path, variants) in crate::files::dsl::files
+        .select((crate::files::dsl::filepath, crate::files::dsl::variants))
+        .load::<(String, Vec<String>)>(conn)
+        .await?
+    {
+        for variant in variants.iter().flat_map(|v| FileVariant::from_name(v)) {
+            paths.insert(format!("{path}{}", variant.suffix()));
+        }
+        paths.insert(format!("{path}-thumb.jpg"));
+        paths.insert(path);
+    }
+
+    paths.extend(
+        crate::banners::dsl::banners
+            .select(crate::banners::dsl::img_path)
+            .load::<String>(conn)
+            .await?,
+    );
+
+    paths.extend(
+        crate::spoilers::dsl::spoilers
+            .select(crate::spoilers::dsl::img_path)
+            .load::<String>(conn)
+            .await?,
+    );
+
+    // files an admin took back out of the trash have no row of their own anymore
+    paths.extend(
+        crate::restored_files::dsl::restored_files
+            .select(crate::restored_files::dsl::filepath)
+            .load::<String>(conn)
+            .await?,
+    );
+
+    Ok(paths)
+}
+
+// file rows whose post no longer exists
+pub async fn get_orphaned_files(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<File>> {
+    use crate::{files, posts};
+
+    let orphans = files::table
+        .left_join(posts::table)
+        .filter(posts::id.is_null())
+        .select((
+            files::id,
+            files::filepath,
+            files::hash,
+            files::spoiler,
+            files::phash,
+            files::variants,
+            files::duration_ms,
+            files::width,
+            files::height,
+            files::original_name,
+            files::size,
+            files::uploader,
+        ))
+        .load::<File>(conn)
+        .await?;
+
+    Ok(orphans)
+}
+
+pub async fn delete_files(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    ids: Vec<i64>,
+) -> Result<()> {
+    use crate::files::dsl::*;
+
+    diesel::delete(files.filter(id.eq_any(ids)))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+// filled in after the post is made, encoding happens outside the upload locks
+pub async fn set_file_media(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    file_id: i64,
+    media: MediaInfo,
+    file_variants: &[FileVariant],
+) -> Result<()> {
+    use crate::files::dsl::*;
+
+    diesel::update(files.filter(id.eq(file_id)))
+        .set((
+            variants.eq(file_variants
+                .iter()
+                .map(|v| v.name().to_owned())
+                .collect::<Vec<String>>()),
+            duration_ms.eq(media.duration_ms),
+            width.eq(media.width),
+            height.eq(media.height),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn add_trashed_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<TrashedFile> {
+    use crate::trash::dsl::*;
+
+    let trashed = diesel::insert_into(trash)
+        .values((filepath.eq(path), trashed_at.eq(diesel::dsl::now)))
+        .on_conflict(filepath)
+        .do_update()
+        .set(trashed_at.eq(diesel::dsl::now))
+        .get_result::<TrashedFile>(conn)
+        .await?;
+
+    Ok(trashed)
+}
+
+pub async fn add_restored_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<()> {
+    use crate::restored_files::dsl::*;
+
+    diesel::insert_into(restored_files)
+        .values((filepath.eq(path), restored_at.eq(diesel::dsl::now)))
+        .on_conflict(filepath)
+        .do_update()
+        .set(restored_at.eq(diesel::dsl::now))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_trashed_files(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<TrashedFile>> {
+    use crate::trash::dsl::*;
+
+    let trashed = trash
+        .order(trashed_at.desc())
+        .load::<TrashedFile>(conn)
+        .await?;
+
+    Ok(trashed)
+}
+
+pub async fn get_trashed_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+) -> Result<Option<TrashedFile>> {
+    use crate::trash::dsl::*;
+
+    let trashed = trash
+        .filter(filepath.eq(path))
+        .first::<TrashedFile>(conn)
+        .await
+        .optional()?;
+
+    Ok(trashed)
+}
+
+// trashed files that were moved to the trash before `cutoff`
+pub async fn get_expired_trash(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    cutoff: chrono::NaiveDateTime,
+) -> Result<Vec<TrashedFile>> {
+    use crate::trash::dsl::*;
+
+    let trashed = trash
+        .filter(trashed_at.lt(cutoff))
+        .load::<TrashedFile>(conn)
+        .await?;
+
+    Ok(trashed)
+}
+
+pub async fn remove_trashed_file(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    trashed_id: i64,
+) -> Result<()> {
+    use crate::trash::dsl::*;
+
+    diesel::delete(trash.filter(id.eq(trashed_id)))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+// a ban without a board applies everywhere
+pub async fn add_file_ban(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    file_hash: &str,
+    file_phash: Option<i64>,
+    tboard: Option<i64>,
+    ban_reason: Option<String>,
+) -> Result<FileBan> {
+    use crate::file_bans::dsl::*;
+
+    let ban = diesel::insert_into(file_bans)
+        .values((
+            hash.eq(file_hash),
+            phash.eq(file_phash),
+            board.eq(tboard),
+            reason.eq(ban_reason),
+            created_at.eq(diesel::dsl::now),
+        ))
+        .get_result::<FileBan>(conn)
+        .await?;
+
+    Ok(ban)
+}
+
+// every ban that applies to uploads on `tboard`, global bans included
+pub async fn get_file_bans(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    tboard: i64,
+) -> Result<Vec<FileBan>> {
+    use crate::file_bans::dsl::*;
+
+    let bans = file_bans
+        .filter(board.is_null())
+        .or_filter(board.eq(tboard))
+        .load::<FileBan>(conn)
+        .await?;
+
+    Ok(bans)
+}
+
+pub async fn get_all_file_bans(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<FileBan>> {
+    use crate::file_bans::dsl::*;
+
+    let bans = file_bans
+        .order(created_at.desc())
+        .load::<FileBan>(conn)
+        .await?;
+
+    Ok(bans)
+}
+
+pub async fn remove_file_ban(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    ban_id: i64,
+) -> Result<()> {
+    use crate::file_bans::dsl::*;
+
+    let removed = diesel::delete(file_bans.filter(id.eq(ban_id)))
+        .execute(conn)
+        .await?;
+
+    if removed == 0 {
+        return Err(anyhow::anyhow!("Ban not found"));
+    }
+
+    Ok(())
+}
+
+pub async fn add_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    path: &str,
+    member_hash: &str,
+    expires: chrono::NaiveDateTime,
+    view_limit: Option<i64>,
+    signing_kid: i32,
+) -> Result<ShareLink> {
+    use crate::share_links::dsl::*;
+
+    let link = diesel::insert_into(share_links)
+        .values((
+            filepath.eq(path),
+            creator.eq(member_hash),
+            created_at.eq(diesel::dsl::now),
+            expires_at.eq(expires),
+            max_views.eq(view_limit),
+            kid.eq(signing_kid),
+        ))
+        .get_result::<ShareLink>(conn)
+        .await?;
+
+    Ok(link)
+}
+
+pub async fn get_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    link_id: i64,
+) -> Result<Option<ShareLink>> {
+    use crate::share_links::dsl::*;
+
+    let link = share_links
+        .filter(id.eq(link_id))
+        .first::<ShareLink>(conn)
+        .await
+        .optional()?;
+
+    Ok(link)
+}
+
+// links that have expired are left out, there's nothing left to manage about them
+pub async fn get_member_share_links(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+) -> Result<Vec<ShareLink>> {
+    use crate::share_links::dsl::*;
+
+    let links = share_links
+        .filter(creator.eq(member_hash))
+        .filter(expires_at.gt(diesel::dsl::now))
+        .order(created_at.desc())
+        .load::<ShareLink>(conn)
+        .await?;
+
+    Ok(links)
+}
+
+pub async fn revoke_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    link_id: i64,
+    member_hash: &str,
+) -> Result<()> {
+    use crate::share_links::dsl::*;
+
+    let revoked_count = diesel::update(
+        share_links
+            .filter(id.eq(link_id))
+            .filter(creator.eq(member_hash)),
+    )
+    .set(revoked.eq(true))
+    .execute(conn)
+    .await?;
+
+    if revoked_count == 0 {
+        return Err(anyhow::anyhow!("Share link not found"));
+    }
+
+    Ok(())
+}
+
+// counts a view, fails once the link has used up all of its views
+pub async fn use_share_link(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    link_id: i64,
+) -> Result<()> {
+    use crate::share_links::dsl::*;
+
+    let updated = diesel::update(
+        share_links
+            .filter(id.eq(link_id))
+            .filter(max_views.is_null().or(max_views.gt(views.nullable()))),
+    )
+    .set(views.eq(views + 1))
+    .execute(conn)
+    .await?;
+
+    if updated == 0 {
+        return Err(anyhow::anyhow!("Share link has no views left"));
+    }
+
+    Ok(())
+}
+
+// expired links can't be used anyway, this just stops the table growing forever
+pub async fn delete_expired_share_links(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<usize> {
+    use crate::share_links::dsl::*;
+
+    let deleted = diesel::delete(share_links.filter(expires_at.lt(diesel::dsl::now)))
+        .execute(conn)
+        .await?;
+
+    Ok(deleted)
+}
+
+pub async fn enqueue_pushes(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    jobs: Vec<QueuedPush>,
+    body: &str,
+) -> Result<usize> {
+    use crate::push_outbox::dsl::*;
+
+    let rows = jobs
+        .into_iter()
+        .map(|job| {
+            serde_json::to_value(&job.subscription).map(|value| {
+                (
+                    member.eq(job.member),
+                    endpoint.eq(job.subscription.endpoint),
+                    subscription.eq(value),
+                    payload.eq(body),
+                    next_attempt.eq(job.send_at),
+                    digest.eq(job.digest),
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let queued = diesel::insert_into(push_outbox)
+        .values(rows)
+        .execute(conn)
+        .await?;
+
+    Ok(queued)
+}
+
+// jobs that are due get leased until `lease_until`, a worker that dies mid delivery just lets the lease run out
+pub async fn claim_pushes(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    limit: i64,
+    lease_until: chrono::NaiveDateTime,
+) -> Result<Vec<PushJob>> {
+    use crate::push_outbox::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+    let due = push_outbox
+        .filter(next_attempt.le(now))
+        .order(next_attempt.asc())
+        .limit(limit)
+        .select(id)
+        .load::<i64>(conn)
+        .await?;
+
+    if due.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // the next_attempt check makes sure two claimers racing for the same job can't both get it
+    let mut claimed = diesel::update(
+        push_outbox
+            .filter(id.eq_any(due))
+            .filter(next_attempt.le(now)),
+    )
+    .set(next_attempt.eq(lease_until))
+    .get_results::<PushJob>(conn)
+    .await?;
+
+    // digests are sent one per endpoint, so every due digest job for an endpoint in this batch comes along
+    // even when the limit cut it off
+    let mut digest_endpoints = claimed
+        .iter()
+        .filter(|job| job.digest)
+        .map(|job| job.endpoint.clone())
+        .collect::<Vec<String>>();
+    if digest_endpoints.is_empty() {
+        return Ok(claimed);
+    }
+    digest_endpoints.sort();
+    digest_endpoints.dedup();
+
+    claimed.extend(
+        diesel::update(
+            push_outbox
+                .filter(digest.eq(true))
+                .filter(endpoint.eq_any(digest_endpoints))
+                .filter(next_attempt.le(now)),
+        )
+        .set(next_attempt.eq(lease_until))
+        .get_results::<PushJob>(conn)
+        .await?,
+    );
+
+    Ok(claimed)
+}
+
+pub async fn finish_push(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    job_id: i64,
+) -> Result<()> {
+    use crate::push_outbox::dsl::*;
+
+    diesel::delete(push_outbox.filter(id.eq(job_id)))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn retry_push(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    job_id: i64,
+    retry_at: chrono::NaiveDateTime,
+    error: &str,
+) -> Result<()> {
+    use crate::push_outbox::dsl::*;
+
+    diesel::update(push_outbox.filter(id.eq(job_id)))
+        .set((
+            attempts.eq(attempts + 1),
+            next_attempt.eq(retry_at),
+            last_error.eq(error),
+        ))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+// the push service told us the subscription is gone, so it's dropped from the member and from every queued job
+pub async fn remove_push_subscription(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_id: i64,
+    gone: &str,
+) -> Result<()> {
+    {
+        use crate::push_outbox::dsl::*;
+        diesel::delete(push_outbox.filter(endpoint.eq(gone)))
+            .execute(conn)
+            .await?;
+    }
+
+    use crate::members::dsl::*;
+
+    let Some(found) = members
+        .filter(id.eq(member_id))
+        .first::<Member>(conn)
+        .await
+        .optional()?
+    else {
+        return Ok(());
+    };
+
+    let mut subscriptions = found.parse_push_data()?;
+    subscriptions.retain(|sub| sub.endpoint != gone);
+    diesel::update(members.filter(id.eq(member_id)))
+        .set(push_data.eq(serde_json::to_value(subscriptions)?))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_member(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+) -> Result<Member> {
+    use crate::members::dsl::*;
+
+    let member = members
+        .filter(token_hash.eq(member_hash))
+        .first::<Member>(conn)
+        .await?;
+
+    Ok(member)
+}
+
+pub async fn set_notification_preferences(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+    prefs: &NotificationPreferences,
+) -> Result<()> {
+    use crate::members::dsl::*;
+
+    diesel::update(members.filter(token_hash.eq(member_hash)))
+        .set(notification_prefs.eq(serde_json::to_value(prefs)?))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_muted_threads(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+    threads: Vec<i64>,
+) -> Result<()> {
+    use crate::members::dsl::*;
+
+    diesel::update(members.filter(token_hash.eq(member_hash)))
+        .set(muted.eq(threads))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_seen_posts(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+    seen_posts: &std::collections::HashMap<i64, i64>,
+) -> Result<()> {
+    use crate::members::dsl::*;
+
+    diesel::update(members.filter(token_hash.eq(member_hash)))
+        .set(seen.eq(serde_json::to_value(seen_posts)?))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_threads_by_id(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    ids: &[i64],
+) -> Result<Vec<Thread>> {
+    use crate::threads::dsl::*;
+
+    let found = threads.filter(id.eq_any(ids)).load::<Thread>(conn).await?;
+
+    Ok(found)
+}
+
+pub async fn get_posts_by_id(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    ids: &[i64],
+) -> Result<Vec<Post>> {
+    use crate::posts::dsl::*;
+
+    let found = posts.filter(id.eq_any(ids)).load::<Post>(conn).await?;
+
+    Ok(found)
+}
+
+// the newest post number in a thread and how many posts came after `seen_up_to`
+pub async fn thread_activity(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    thread_id: i64,
+    seen_up_to: i64,
+) -> Result<(i64, i64)> {
+    use crate::posts::dsl::*;
+
+    let latest = posts
+        .filter(thread.eq(thread_id))
+        .select(diesel::dsl::max(post_number))
+        .first::<Option<i64>>(conn)
+        .await?
+        .unwrap_or_default();
+    let unseen = posts
+        .filter(thread.eq(thread_id))
+        .filter(post_number.gt(seen_up_to))
+        .select(count(id))
+        .first::<i64>(conn)
+        .await?;
+
+    Ok((latest, unseen))
+}
+
+// thread_activity for a batch of (thread, seen_up_to) in one query, threads without posts are left out
+pub async fn threads_activity(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    seen_up_to: &[(i64, i64)],
+) -> Result<std::collections::HashMap<i64, (i64, i64)>> {
+    use crate::posts::dsl::*;
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Array, BigInt};
+
+    let (ids, seen) = seen_up_to
+        .iter()
+        .copied()
+        .unzip::<i64, i64, Vec<i64>, Vec<i64>>();
+    // each post is compared against the seen number stored at its thread's position
+    let unseen = sql::<BigInt>("COUNT(*) FILTER (WHERE posts.post_number > (")
+        .bind::<Array<BigInt>, _>(seen)
+        .sql(")[array_position(")
+        .bind::<Array<BigInt>, _>(ids.clone())
+        .sql(", posts.thread)])");
+
+    let activity = posts
+        .filter(thread.eq_any(&ids))
+        .group_by(thread)
+        .select((thread, diesel::dsl::max(post_number), unseen))
+        .load::<(i64, Option<i64>, i64)>(conn)
+        .await?
+        .into_iter()
+        .map(|(t, latest, unseen)| (t, (latest.unwrap_or_default(), unseen)))
+        .collect();
+
+    Ok(activity)
+}
+
+// what a search is narrowed down to, `boards` must only hold boards the searcher can see
+pub struct PostSearch {
+    pub query: String,
+    pub boards: Vec<i64>,
+    pub after: Option<chrono::NaiveDateTime>,
+    pub before: Option<chrono::NaiveDateTime>,
+    pub has_file: Option<bool>,
+    pub author: Option<String>,
+    pub threads_only: bool,
+}
+
+// the expressions here match the GIN indexes in POSTGRESQL.md, change them together
+// newest matches first, each post comes with its thread's topic and whether it opened the thread
+pub async fn search_posts(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    search: &PostSearch,
+    offset: i64,
+    limit: i64,
+) -> Result<Vec<(Post, String, bool)>> {
+    use crate::posts::dsl::*;
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Bool, Text};
+
+    let matches_content =
+        sql::<Bool>("to_tsvector('english', posts.content) @@ websearch_to_tsquery('english', ")
+            .bind::<Text, _>(search.query.clone())
+            .sql(")");
+    let matches_topic =
+        sql::<Bool>("to_tsvector('english', threads.topic) @@ websearch_to_tsquery('english', ")
+            .bind::<Text, _>(search.query.clone())
+            .sql(")");
+    let opens_thread = id.eq(crate::threads::post_id);
+
+    let mut query = posts
+        .inner_join(crate::threads::table)
+        .filter(board.eq_any(search.boards.clone()))
+        .select((
+            Post::as_select(),
+            crate::threads::topic,
+            crate::threads::post_id,
+        ))
+        .into_boxed();
+
+    // topics only belong to the opening post, so only it can match on one
+    query = if search.threads_only {
+        query.filter(opens_thread.and(matches_content.or(matches_topic)))
+    } else {
+        query.filter(matches_content.or(opens_thread.and(matches_topic)))
+    };
+    if let Some(after) = search.after {
+        query = query.filter(timestamp.ge(after));
+    }
+    if let Some(before) = search.before {
+        query = query.filter(timestamp.lt(before));
+    }
+    if let Some(name) = search.author.clone() {
+        query = query.filter(author.eq(name));
+    }
+    query = match search.has_file {
+        Some(true) => query.filter(id.eq_any(crate::files::table.select(crate::files::id))),
+        Some(false) => query.filter(id.ne_all(crate::files::table.select(crate::files::id))),
+        None => query,
+    };
+
+    let found = query
+        .order((timestamp.desc(), id.desc()))
+        .offset(offset)
+        .limit(limit)
+        .load::<(Post, String, i64)>(conn)
+        .await?;
+
+    Ok(found
+        .into_iter()
+        .map(|(post, topic, thread_post)| {
+            let opened = post.id == thread_post;
+            (post, topic, opened)
+        })
+        .collect())
+}
+
+// the stemmed words a search looks for, so the frontend can point them out
+pub async fn search_terms(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    words: &str,
+) -> Result<Vec<String>> {
+    use diesel::dsl::sql;
+    use diesel::sql_types::{Array, Text};
+
+    let terms = diesel::select(
+        sql::<Array<Text>>("tsvector_to_array(to_tsvector('english', ")
+            .bind::<Text, _>(words.to_owned())
+            .sql("))"),
+    )
+    .get_result::<Vec<String>>(conn)
+    .await?;
+
+    Ok(terms)
+}
+
+// undoes the bump the new post's insert gave the thread
+pub async fn set_latest_post(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    thread_id: i64,
+    value: i64,
+) -> Result<()> {
+    use crate::threads::dsl::*;
+
+    diesel::update(threads.filter(id.eq(thread_id)))
+        .set(latest_post.eq(value))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_boards_with_capacity(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<Vec<Board>> {
+    use crate::boards::dsl::*;
+
+    let found = boards
+        .filter(max_threads.is_not_null())
+        .load::<Board>(conn)
+        .await?;
+
+    Ok(found)
+}
+
+// the live threads past the first `keep` in bump order
+pub async fn get_overflowing_threads(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    board_id: i64,
+    keep: i64,
+) -> Result<Vec<Thread>> {
+    use crate::threads::dsl::*;
+
+    let found = threads
+        .filter(board.eq(board_id))
+        .filter(archived_at.is_null())
+        .order(latest_post.desc())
+        .offset(keep.max(0))
+        .load::<Thread>(conn)
+        .await?;
+
+    Ok(found)
+}
+
+// every thread on a board including archived ones, oldest first
+pub async fn get_board_threads(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    board_id: i64,
+) -> Result<Vec<Thread>> {
+    use crate::threads::dsl::*;
+
+    let found = threads
+        .filter(board.eq(board_id))
+        .order(post_id.asc())
+        .load::<Thread>(conn)
+        .await?;
+
+    Ok(found)
+}
+
+pub async fn archive_thread(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    thread_id: i64,
+) -> Result<()> {
+    use crate::threads::dsl::*;
+
+    diesel::update(threads.filter(id.eq(thread_id)))
+        .set(archived_at.eq(diesel::dsl::now.nullable()))
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+// one entry per recipient, `item` only provides the contents
+pub async fn add_inbox_entries(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    recipients: &[&str],
+    item: &InboxItem,
+) -> Result<Vec<InboxEntry>> {
+    use crate::inbox::dsl::*;
+
+    if recipients.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let rows = recipients
+        .iter()
+        .map(|member_hash| {
+            (
+                recipient.eq(*member_hash),
+                kind.eq(item.kind.as_str()),
+                board_discriminator.eq(&item.board_discriminator),
+                thread_post_number.eq(item.thread_post_number),
+                post_number.eq(item.post_number),
+                summary.eq(&item.summary),
+                thumbnail.eq(&item.thumbnail),
+                created_at.eq(diesel::dsl::now),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let entries = diesel::insert_into(inbox)
+        .values(rows)
+        .get_results::<InboxEntry>(conn)
+        .await?;
+
+    Ok(entries)
+}
+
+// newest first, `before` is the id of the last entry of the previous page
+pub async fn get_inbox(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+    before: Option<i64>,
+    limit: i64,
+    unread_only: bool,
+) -> Result<Vec<InboxEntry>> {
+    use crate::inbox::dsl::*;
+
+    let mut query = inbox.filter(recipient.eq(member_hash)).into_boxed();
+    if let Some(before) = before {
+        query = query.filter(id.lt(before));
+    }
+    if unread_only {
+        query = query.filter(read.eq(false));
+    }
+
+    let entries = query
+        .order(id.desc())
+        .limit(limit)
+        .load::<InboxEntry>(conn)
+        .await?;
+
+    Ok(entries)
+}
+
+pub async fn count_unread_inbox(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+) -> Result<i64> {
+    use crate::inbox::dsl::*;
+
+    let unread = inbox
+        .filter(recipient.eq(member_hash))
+        .filter(read.eq(false))
+        .select(count(id))
+        .first::<i64>(conn)
+        .await?;
+
+    Ok(unread)
+}
+
+// marks a single entry when given an id, otherwise everything
+pub async fn mark_inbox_read(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    member_hash: &str,
+    entry: Option<i64>,
+) -> Result<usize> {
+    use crate::inbox::dsl::*;
+
+    let mut query = diesel::update(
+        inbox
+            .filter(recipient.eq(member_hash))
+            .filter(read.eq(false)),
+    )
+    .set(read.eq(true))
+    .into_boxed();
+    if let Some(entry) = entry {
+        query = query.filter(id.eq(entry));
+    }
+
+    let marked = query.execute(conn).await?;
+
+    Ok(marked)
+}
+
+pub async fn delete_old_inbox_entries(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+    older_than: chrono::NaiveDateTime,
+) -> Result<usize> {
+    use crate::inbox::dsl::*;
+
+    let deleted = diesel::delete(inbox.filter(created_at.lt(older_than)))
+        .execute(conn)
+        .await?;
+
+    Ok(deleted)
+}
+
+// how many jobs are waiting and when the oldest one was queued
+pub async fn pending_pushes(
+    conn: &mut Object<AsyncDieselConnectionManager<AsyncPgConnection>>,
+) -> Result<(i64, Option<chrono::NaiveDateTime>)> {
+    use crate::push_outbox::dsl::*;
+
+    let pending = push_outbox
+        .select((count(id), diesel::dsl::min(created_at)))
+        .first::<(i64, Option<chrono::NaiveDateTime>)>(conn)
+        .await?;
+
+    Ok(pending)
+}
+
 #[derive(Queryable, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BoardAccess {
     id: Uuid,
@@ -16,6 +16,13 @@ diesel::table! {
         discriminator -> Text,
         post_count -> BigInt,
         private -> Bool,
+        text_previews -> Bool,
+        hide_filenames -> Bool,
+        quota_bytes -> Nullable<BigInt>,
+        quota_files -> Nullable<BigInt>,
+        bump_limit -> Nullable<BigInt>,
+        max_threads -> Nullable<BigInt>,
+        overflow -> Text,
     }
 }
 
@@ -26,12 +33,46 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    file_bans (id) {
+        id -> BigInt,
+        hash -> Text,
+        phash -> Nullable<BigInt>,
+        board -> Nullable<BigInt>,
+        reason -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     files (id) {
         id -> BigInt,
         filepath -> Text,
         hash -> Text,
         spoiler -> Bool,
+        phash -> Nullable<BigInt>,
+        variants -> Array<Text>,
+        duration_ms -> Nullable<BigInt>,
+        width -> Nullable<Integer>,
+        height -> Nullable<Integer>,
+        original_name -> Nullable<Text>,
+        size -> BigInt,
+        uploader -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    inbox (id) {
+        id -> BigInt,
+        recipient -> Text,
+        kind -> Text,
+        board_discriminator -> Text,
+        thread_post_number -> BigInt,
+        post_number -> BigInt,
+        summary -> Text,
+        thumbnail -> Nullable<Text>,
+        created_at -> Timestamp,
+        read -> Bool,
     }
 }
 
@@ -42,6 +83,23 @@ diesel::table! {
         push_data -> Jsonb,
         watching -> Array<BigInt>,
         admin -> Bool,
+        notification_prefs -> Jsonb,
+        muted -> Array<BigInt>,
+        seen -> Jsonb,
+    }
+}
+
+diesel::table! {
+    post_authors (post) {
+        post -> BigInt,
+        member -> BigInt,
+    }
+}
+
+diesel::table! {
+    post_replies (post, quoted) {
+        post -> BigInt,
+        quoted -> BigInt,
     }
 }
 
@@ -57,6 +115,43 @@ diesel::table! {
         content -> Text,
         timestamp -> Timestamp,
         replies_to -> Array<BigInt>,
+        sage -> Bool,
+    }
+}
+
+diesel::table! {
+    push_outbox (id) {
+        id -> BigInt,
+        member -> BigInt,
+        endpoint -> Text,
+        subscription -> Jsonb,
+        payload -> Text,
+        attempts -> Integer,
+        next_attempt -> Timestamp,
+        created_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        digest -> Bool,
+    }
+}
+
+diesel::table! {
+    restored_files (filepath) {
+        filepath -> Text,
+        restored_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    share_links (id) {
+        id -> BigInt,
+        filepath -> Text,
+        creator -> Text,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        max_views -> Nullable<BigInt>,
+        views -> BigInt,
+        revoked -> Bool,
+        kid -> Integer,
     }
 }
 
@@ -75,6 +170,15 @@ diesel::table! {
         post_id -> BigInt,
         latest_post -> BigInt,
         topic -> Text,
+        archived_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    trash (id) {
+        id -> BigInt,
+        filepath -> Text,
+        trashed_at -> Timestamp,
     }
 }
 
@@ -89,11 +193,27 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(file_bans -> boards (board));
 diesel::joinable!(files -> posts (id));
 diesel::joinable!(posts -> boards (board));
 diesel::joinable!(posts -> threads (thread));
 diesel::joinable!(threads -> boards (board));
 
 diesel::allow_tables_to_appear_in_same_query!(
-    banners, boards, config, files, members, posts, spoilers, threads,
+    banners,
+    boards,
+    config,
+    file_bans,
+    files,
+    inbox,
+    members,
+    post_authors,
+    post_replies,
+    posts,
+    push_outbox,
+    restored_files,
+    share_links,
+    spoilers,
+    threads,
+    trash,
 );
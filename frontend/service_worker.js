@@ -42,7 +42,8 @@ self.addEventListener('install', function (e) {
 self.addEventListener('push', (e) => {
   let raw_body = e.data.json();
   let body = JSON.parse(raw_body.body);
-  let final_body = body.author + " posted in " + body.thread_topic + "\n" + body.content;
+  let action = body.kind == "reply" ? " replied to you in " : " posted in ";
+  let final_body = body.kind == "digest" ? body.content : body.author + action + body.thread_topic + "\n" + body.content;
   const options = {
     body: final_body,
     icon: body.thumbnail || 'res/icon-256.png',
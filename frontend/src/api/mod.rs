@@ -1,5 +1,7 @@
 use common::structs::{
-    Banner, BoardWithThreads, CreatePost, CreateThread, SafeBoard, SafePost, ThreadWithPosts,
+    Banner, BoardWithThreads, Catalog, CreatePost, CreateThread, ExportStatus, Inbox,
+    NotificationPreferences, SafeBoard, SafePost, SearchResults, ShareLinkInfo, ThreadRef,
+    ThreadWithPosts, WatchedThread,
 };
 use gloo_net::http::Request;
 use serde::{de::DeserializeOwned, Serialize};
@@ -109,6 +111,26 @@ impl Api {
         }
     }
 
+    pub async fn get_catalog(
+        &self,
+        board: impl Display + ToString + Copy,
+    ) -> Result<Catalog, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/board/{board_discriminator}/catalog
+        standard_get(&format!("/api/v1/board/{}/catalog", board), &token).await
+    }
+
+    // later pages aren't cached, the first one is what get_board returns
+    pub async fn get_board_page(
+        &self,
+        board: impl Display + ToString + Copy,
+        page: i64,
+    ) -> Result<BoardWithThreads, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/board/{board_discriminator}?page=i64
+        standard_get(&format!("/api/v1/board/{}?page={}", board, page), &token).await
+    }
+
     #[allow(unused_variables)]
     pub async fn get_board(
         &self,
@@ -361,6 +383,11 @@ impl Api {
         }
     }
 
+    pub async fn cancel_upload(&self, id: &str) -> Result<String, ApiError> {
+        let token = self.formatted_token();
+        standard_delete::<String>(&format!("/api/v1/file/{}", id), &token).await
+    }
+
     pub async fn share_file(
         &self,
         file: &str, // /files/mimetype/id.ext
@@ -370,6 +397,16 @@ impl Api {
         standard_put::<String, ()>(&format!("/api/v1/share{}", file), &token, &()).await
     }
 
+    pub async fn list_share_links(&self) -> Result<Vec<ShareLinkInfo>, ApiError> {
+        let token = self.formatted_token();
+        standard_get::<Vec<ShareLinkInfo>>("/api/v1/share", &token).await
+    }
+
+    pub async fn revoke_share_link(&self, id: i64) -> Result<String, ApiError> {
+        let token = self.formatted_token();
+        standard_delete::<String>(&format!("/api/v1/share/{}", id), &token).await
+    }
+
     pub async fn create_thread(
         &self,
         board: impl Display + ToString + Copy,
@@ -448,6 +485,70 @@ impl Api {
         .await
     }
 
+    pub async fn get_watched_threads(&self) -> Result<Vec<WatchedThread>, ApiError> {
+        let token = self.formatted_token();
+        standard_get("/api/v1/watching", &token).await
+    }
+
+    // returns the threads that are still watched
+    pub async fn unwatch_threads(
+        &self,
+        threads: &Vec<ThreadRef>,
+    ) -> Result<Vec<WatchedThread>, ApiError> {
+        let token = self.formatted_token();
+        standard_post("/api/v1/watching/unwatch", &token, threads).await
+    }
+
+    pub async fn search(
+        &self,
+        params: &SearchParams,
+        page: i64,
+    ) -> Result<SearchResults, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/search?q=String&...&page=i64
+        standard_get(
+            &format!("/api/v1/search?{}&page={}", params.to_query(), page),
+            &token,
+        )
+        .await
+    }
+
+    // without a thread the whole board is exported
+    pub async fn start_export(
+        &self,
+        board: impl Display + ToString + Copy,
+        thread: Option<i64>,
+    ) -> Result<ExportStatus, ApiError> {
+        let token = self.formatted_token();
+        // POST /api/v1/export/{board_discriminator}/{thread_id}
+        let path = match thread {
+            Some(thread) => format!("/api/v1/export/{}/{}", board, thread),
+            None => format!("/api/v1/export/{}", board),
+        };
+        standard_post(&path, &token, &()).await
+    }
+
+    pub async fn get_export(&self, id: &str) -> Result<ExportStatus, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/exports/{id}
+        standard_get(&format!("/api/v1/exports/{}", id), &token).await
+    }
+
+    pub async fn mark_thread_seen(
+        &self,
+        board: impl Display + ToString + Copy,
+        post: impl Display + ToString + Copy,
+    ) -> Result<i64, ApiError> {
+        let token = self.formatted_token();
+        // POST /api/v1/board/{board_discriminator}/post/{post_number}/seen
+        standard_post(
+            &format!("/api/v1/board/{}/post/{}/seen", board, post),
+            &token,
+            &(),
+        )
+        .await
+    }
+
     pub async fn get_watching(
         &self,
         board: impl Display + ToString + Copy,
@@ -462,6 +563,76 @@ impl Api {
         .await
     }
 
+    pub async fn set_muted(
+        &self,
+        board: impl Display + ToString + Copy,
+        post: impl Display + ToString + Copy,
+        muted: bool,
+    ) -> Result<bool, ApiError> {
+        let token = self.formatted_token();
+        // PUT /api/v1/board/{board_discriminator}/post/{post_number}/muted
+        standard_put(
+            &format!("/api/v1/board/{}/post/{}/muted", board, post),
+            &token,
+            &muted,
+        )
+        .await
+    }
+
+    pub async fn get_muted(
+        &self,
+        board: impl Display + ToString + Copy,
+        post: impl Display + ToString + Copy,
+    ) -> Result<bool, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/board/{board_discriminator}/post/{post_number}/muted
+        standard_get(
+            &format!("/api/v1/board/{}/post/{}/muted", board, post),
+            &token,
+        )
+        .await
+    }
+
+    pub async fn get_notification_preferences(&self) -> Result<NotificationPreferences, ApiError> {
+        let token = self.formatted_token();
+        standard_get("/api/v1/preferences/notifications", &token).await
+    }
+
+    pub async fn set_notification_preferences(
+        &self,
+        prefs: &NotificationPreferences,
+    ) -> Result<NotificationPreferences, ApiError> {
+        let token = self.formatted_token();
+        standard_put("/api/v1/preferences/notifications", &token, prefs).await
+    }
+
+    pub async fn get_inbox(&self, unread_only: bool) -> Result<Inbox, ApiError> {
+        let token = self.formatted_token();
+        // GET /api/v1/inbox?unread_only=bool
+        standard_get(
+            &format!("/api/v1/inbox?unread_only={}", unread_only),
+            &token,
+        )
+        .await
+    }
+
+    pub async fn get_unread_count(&self) -> Result<i64, ApiError> {
+        let token = self.formatted_token();
+        standard_get("/api/v1/inbox/unread", &token).await
+    }
+
+    // both return how many unread notifications are left
+    pub async fn mark_inbox_read(&self, id: i64) -> Result<i64, ApiError> {
+        let token = self.formatted_token();
+        // POST /api/v1/inbox/{id}/read
+        standard_post(&format!("/api/v1/inbox/{}/read", id), &token, &()).await
+    }
+
+    pub async fn mark_all_inbox_read(&self) -> Result<i64, ApiError> {
+        let token = self.formatted_token();
+        standard_post("/api/v1/inbox/read", &token, &()).await
+    }
+
     pub async fn get_banner(&self, board: impl Display + ToString) -> Result<Banner, ApiError> {
         let token = self.formatted_token();
 
@@ -563,6 +734,44 @@ impl Api {
     }
 }
 
+// everything a search is filtered by, the page is passed separately
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchParams {
+    pub q: String,
+    pub board: Option<String>,
+    // unix timestamps in seconds
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+    pub has_file: Option<bool>,
+    pub author: Option<String>,
+    pub threads_only: bool,
+}
+
+impl SearchParams {
+    fn to_query(&self) -> String {
+        let mut query = format!("q={}", js_sys::encode_uri_component(&self.q));
+        if let Some(board) = &self.board {
+            query.push_str(&format!("&board={}", js_sys::encode_uri_component(board)));
+        }
+        if let Some(after) = self.after {
+            query.push_str(&format!("&after={}", after));
+        }
+        if let Some(before) = self.before {
+            query.push_str(&format!("&before={}", before));
+        }
+        if let Some(has_file) = self.has_file {
+            query.push_str(&format!("&has_file={}", has_file));
+        }
+        if let Some(author) = &self.author {
+            query.push_str(&format!("&author={}", js_sys::encode_uri_component(author)));
+        }
+        if self.threads_only {
+            query.push_str("&threads_only=true");
+        }
+        query
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ApiError {
     Gloo(AttrValue),
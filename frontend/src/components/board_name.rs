@@ -17,8 +17,12 @@ pub fn BoardName(props: &Props) -> Html {
         });
     }
 
-    let (board_discriminator, is_thread) = location.map_or((None, false), |b| {
-        (b.board_discriminator(), b.thread_id().is_some())
+    // threads and the catalog link back to the board index
+    let (board_discriminator, off_index) = location.map_or((None, false), |b| {
+        (
+            b.board_discriminator(),
+            !matches!(b, BaseRoute::BoardPage { .. }),
+        )
     });
 
     let (mousein, mouseout) = {
@@ -41,7 +45,7 @@ pub fn BoardName(props: &Props) -> Html {
         }
     );
     html! {
-        <MaybeLink to={BaseRoute::BoardPage { board_discriminator: props.board.discriminator.clone() }}  link={(board_discriminator != Some(props.board.discriminator.clone())) || is_thread}>
+        <MaybeLink to={BaseRoute::BoardPage { board_discriminator: props.board.discriminator.clone() }}  link={(board_discriminator != Some(props.board.discriminator.clone())) || off_index}>
             <div class={format!("{}-board-name-link{}", props.prefix, if board_discriminator == Some(props.board.discriminator.clone()) { "-selected" } else { "" })} id={id} onmouseover={mousein} onmouseout={mouseout} >
                 <span class={format!("{}-board-name-container", props.prefix)} >
                     {
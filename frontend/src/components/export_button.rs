@@ -0,0 +1,108 @@
+use common::structs::{ExportState, ExportStatus};
+use yew::prelude::*;
+
+use crate::{api::ApiState, ApiContext};
+
+// bigger exports keep building after the first answer, their status is checked this often
+const POLL_MS: u32 = 3000;
+
+// without a thread_post_number the whole board is exported
+
+#[function_component]
+pub fn ExportButton(props: &Props) -> Html {
+    let state = use_state(|| ApiState::Pending::<ExportStatus>);
+
+    let api_ctx = use_context::<Option<ApiContext>>().flatten();
+
+    let on_click = {
+        let state = state.clone();
+        let props = props.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            match api_ctx {
+                Some(ref api_ctx) => match api_ctx.api.clone() {
+                    Ok(api) => {
+                        let props = props.clone();
+                        let state = state.clone();
+                        state.set(ApiState::Loading);
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let mut result = api
+                                .start_export(&props.board_discriminator, props.thread_post_number)
+                                .await;
+                            while let Ok(status) = &result {
+                                if status.state != ExportState::Pending {
+                                    break;
+                                }
+                                state.set(ApiState::Loaded(status.clone()));
+                                gloo::timers::future::TimeoutFuture::new(POLL_MS).await;
+                                result = api.get_export(&status.id).await;
+                            }
+                            match result {
+                                Ok(v) => {
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+        })
+    };
+
+    let title = if props.thread_post_number.is_some() {
+        "Download this thread as a zip"
+    } else {
+        "Download every thread on this board as a zip"
+    };
+
+    if let ApiState::Pending = *state {
+        return html! {
+            <div class="export-button">
+                <span onclick={on_click} title={title}>{"Export"}</span>
+            </div>
+        };
+    }
+
+    match state.standard_html("ExportButton", |status| match status.state {
+        ExportState::Pending => html! {
+            <div class="export-button">
+                <span class="export-pending">{"Exporting..."}</span>
+            </div>
+        },
+        ExportState::Ready => html! {
+            <div class="export-button">
+                <a href={status.download.clone().unwrap_or_default()} download={status.name.clone()}>
+                    { format!("Download {}", status.name) }
+                </a>
+            </div>
+        },
+        ExportState::Failed => html! {
+            <div class="export-button">
+                <span onclick={on_click.clone()} title={status.error.clone().unwrap_or_default()}>{"Export failed, try again"}</span>
+            </div>
+        },
+    }) {
+        Ok(v) => v,
+        Err(e) => html! {
+            <div class="export-button">
+                <span onclick={on_click.clone()} title={format!("{}", *e)}>{"Export failed, try again"}</span>
+            </div>
+        },
+    }
+}
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub board_discriminator: String,
+    #[prop_or_default]
+    pub thread_post_number: Option<i64>,
+}
@@ -5,6 +5,7 @@ use crate::{
 };
 
 use super::HoveredOrExpandedState;
+use common::structs::FileVariant;
 use yew::prelude::*;
 use yew_hooks::use_local_storage;
 
@@ -160,6 +161,8 @@ pub fn File(props: &Props) -> Html {
         })
     };
 
+    let meta = media_summary(&props.file.claimed.media);
+
     html! {
         <div class="post-file-container" draggable="false">
             // <div class="post-file-header">
@@ -192,6 +195,19 @@ pub fn File(props: &Props) -> Html {
                 <span class="post-hash" title={format!("Hash: {}", props.file.claimed.hash.clone())}>
                     { if emojis { "" } else { "Hash" }}
                 </span>
+                if props.file.claimed.original_name.is_some() || meta.is_some() {
+                    <span class="post-file-caption">
+                        if let Some(name) = &props.file.claimed.original_name {
+                            <a href={props.file.claimed.path.clone()} download={name.clone()} title={name.clone()} draggable="false">{ shorten_filename(name) }</a>
+                        }
+                        if props.file.claimed.original_name.is_some() && meta.is_some() {
+                            { " · " }
+                        }
+                        if let Some(meta) = &meta {
+                            <span class="post-file-meta">{ meta }</span>
+                        }
+                    </span>
+                }
                 if props.file.claimed.path.contains("/audio/") || props.file.claimed.path.contains("/video/") {
                     <span class="might-have-sound-indicator" >
                         {
@@ -229,7 +245,7 @@ pub fn File(props: &Props) -> Html {
                         HoveredOrExpandedState::None => {
                             html! {
                                 <div class="post-file-thumbnail">
-                                    <img src={props.file.claimed.thumbnail.clone()} />
+                                    { thumbnail_html(&props.file) }
                                 </div>
                             }
                         }
@@ -240,7 +256,7 @@ pub fn File(props: &Props) -> Html {
                         } => {
                             html! {
                                 <>
-                                    <img src={props.file.claimed.thumbnail.clone()} />
+                                    { thumbnail_html(&props.file) }
                                     <div class="floating-image" style={format!("left: calc({}px + 1em) !important; top: calc({}px) !important; position: absolute !important; transform: translateY({}) !important;", x, y, offset.percent())}>
                                         {
                                             file_html(&props.file)
@@ -270,6 +286,45 @@ pub struct Props {
     pub file: common::structs::FileInfo,
 }
 
+// keeps the extension visible when a long name gets cut down
+fn shorten_filename(name: &str) -> String {
+    const MAX: usize = 24;
+    if name.chars().count() <= MAX {
+        return name.to_owned();
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.chars().count() < 8 => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+    let keep = MAX.saturating_sub(ext.chars().count() + 1);
+    format!("{}…{}", stem.chars().take(keep).collect::<String>(), ext)
+}
+
+fn media_summary(media: &common::structs::MediaInfo) -> Option<String> {
+    match (media.dimensions_string(), media.duration_string()) {
+        (Some(dimensions), Some(duration)) => Some(format!("{dimensions}, {duration}")),
+        (dimensions, duration) => dimensions.or(duration),
+    }
+}
+
+// smaller screens get the recompressed variants, larger ones the original upload
+const VARIANT_MEDIA: &str = "(max-width: 1024px)";
+
+fn thumbnail_html(file: &common::structs::FileInfo) -> Html {
+    if !file.claimed.spoiler && file.claimed.has_variant(FileVariant::Preview) {
+        // the poster covers browsers that can't play webm
+        html! {
+            <video autoplay=true loop=true muted=true playsinline=true class="post-file-preview" poster={file.claimed.thumbnail.clone()} draggable="false">
+                <source src={file.claimed.variant_path(FileVariant::Preview)} type={FileVariant::Preview.mimetype()} />
+            </video>
+        }
+    } else {
+        html! {
+            <img src={file.claimed.thumbnail.clone()} />
+        }
+    }
+}
+
 fn file_html(file: &common::structs::FileInfo) -> Html {
     let mimetype = file.claimed.path.replace("/files/", "");
     let mut th = mimetype.split('/');
@@ -293,8 +348,17 @@ fn file_html(file: &common::structs::FileInfo) -> Html {
         }
         Some(m) => match m {
             "image" => {
+                // the browser takes the first source it supports whose media query matches, falling back to the original
                 html! {
-                    <img src={file.claimed.path.clone()} draggable="false"/>
+                    <picture>
+                        if file.claimed.has_variant(FileVariant::Avif) {
+                            <source srcset={file.claimed.variant_path(FileVariant::Avif)} type={FileVariant::Avif.mimetype()} media={VARIANT_MEDIA} />
+                        }
+                        if file.claimed.has_variant(FileVariant::Webp) {
+                            <source srcset={file.claimed.variant_path(FileVariant::Webp)} type={FileVariant::Webp.mimetype()} media={VARIANT_MEDIA} />
+                        }
+                        <img src={file.claimed.path.clone()} draggable="false"/>
+                    </picture>
                 }
             }
             "video" => {
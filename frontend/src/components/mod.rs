@@ -5,12 +5,15 @@ mod board_title;
 mod context_error;
 mod delete_button;
 mod editors;
+mod export_button;
 mod invite_tools;
 // mod feedback;
 mod file;
 mod footer;
 mod header;
 mod maybe_link;
+mod mute_button;
+mod notification_settings;
 mod notifications;
 mod post;
 mod post_box;
@@ -18,6 +21,7 @@ mod powered_by;
 mod reply;
 mod richtext;
 mod settings;
+mod share_links;
 mod spinner;
 mod thread;
 mod watch_button;
@@ -29,6 +33,7 @@ pub use board_title::BoardTitle;
 pub use context_error::ContextError;
 pub use delete_button::DeleteButton;
 pub use editors::*;
+pub use export_button::ExportButton;
 pub use invite_tools::InviteTools;
 // pub use feedback::FeedbackButton;
 pub use editors::theme_editor::ThemeEditor;
@@ -37,6 +42,8 @@ pub use file::File;
 pub use footer::Footer;
 pub use header::Header;
 pub use maybe_link::{MaybeLink, MaybeLinkProps};
+pub use mute_button::MuteButton;
+pub use notification_settings::NotificationSettings;
 pub use notifications::NotificationBox;
 pub use post::Post;
 pub use post_box::PostBox;
@@ -44,6 +51,7 @@ pub use powered_by::PoweredBy;
 pub use reply::Reply;
 pub use richtext::RichText;
 pub use settings::SettingsButton;
+pub use share_links::ShareLinks;
 pub use spinner::Spinner;
 pub use thread::Thread;
 pub use watch_button::WatchButton;
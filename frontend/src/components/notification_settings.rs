@@ -0,0 +1,191 @@
+use common::structs::{NotificationPreferences, QuietHours};
+use yew::prelude::*;
+use yew_hooks::use_effect_once;
+
+use crate::{
+    api::ApiState,
+    helpers::{on_change_select_element, on_change_to_string},
+    ApiContext,
+};
+
+const DIGEST_OPTIONS: [u32; 6] = [5, 15, 30, 60, 60 * 4, 60 * 24];
+
+// every change is saved right away, the server echoes back what it stored
+
+#[function_component]
+pub fn NotificationSettings() -> Html {
+    let state = use_state(|| ApiState::Pending::<NotificationPreferences>);
+
+    let api_ctx = use_context::<Option<ApiContext>>().flatten();
+    let timezone = use_context::<UseStateHandle<chrono_tz::Tz>>();
+
+    let save = {
+        let state = state.clone();
+        let api_ctx = api_ctx.clone();
+        Callback::from(move |prefs: NotificationPreferences| match api_ctx {
+            Some(ref api_ctx) => match api_ctx.api.clone() {
+                Ok(api) => {
+                    let state = state.clone();
+                    wasm_bindgen_futures::spawn_local(async move {
+                        match api.set_notification_preferences(&prefs).await {
+                            Ok(v) => {
+                                state.set(ApiState::Loaded(v));
+                            }
+                            Err(e) => {
+                                state.set(ApiState::Error(e));
+                            }
+                        };
+                    });
+                }
+                Err(e) => {
+                    state.set(ApiState::Error(e));
+                }
+            },
+            None => {
+                state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+            }
+        })
+    };
+
+    {
+        let state = state.clone();
+        use_effect_once(move || {
+            match api_ctx {
+                Some(api_ctx) => match api_ctx.api {
+                    Ok(api) => {
+                        state.set(ApiState::Loading);
+                        wasm_bindgen_futures::spawn_local(async move {
+                            match api.get_notification_preferences().await {
+                                Ok(v) => {
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+            || {}
+        })
+    }
+
+    let timezone = timezone
+        .map(|tz| tz.name().to_owned())
+        .unwrap_or_else(|| "UTC".to_owned());
+
+    match state.standard_html("NotificationSettings", |prefs| {
+        let on_push = {
+            let prefs = prefs.clone();
+            save.reform(move |e: Event| NotificationPreferences {
+                push: on_change_to_string(e).is_some_and(|input| input.checked()),
+                ..prefs.clone()
+            })
+        };
+        let on_mentions_only = {
+            let prefs = prefs.clone();
+            save.reform(move |e: Event| NotificationPreferences {
+                mentions_only: on_change_to_string(e).is_some_and(|input| input.checked()),
+                ..prefs.clone()
+            })
+        };
+        let on_quiet_start = {
+            let prefs = prefs.clone();
+            let timezone = timezone.clone();
+            save.reform(move |e: Event| {
+                let start = on_change_select_element(e).and_then(|s| s.value().parse::<u32>().ok());
+                NotificationPreferences {
+                    quiet_hours: start.map(|start| QuietHours {
+                        start,
+                        end: prefs.quiet_hours.as_ref().map(|q| q.end).unwrap_or(8),
+                        timezone: timezone.clone(),
+                    }),
+                    ..prefs.clone()
+                }
+            })
+        };
+        let on_quiet_end = {
+            let prefs = prefs.clone();
+            let timezone = timezone.clone();
+            save.reform(move |e: Event| {
+                let end = on_change_select_element(e).and_then(|s| s.value().parse::<u32>().ok());
+                NotificationPreferences {
+                    quiet_hours: prefs.quiet_hours.as_ref().map(|q| QuietHours {
+                        start: q.start,
+                        end: end.unwrap_or(q.end),
+                        timezone: timezone.clone(),
+                    }),
+                    ..prefs.clone()
+                }
+            })
+        };
+        let on_digest = {
+            let prefs = prefs.clone();
+            save.reform(move |e: Event| NotificationPreferences {
+                digest_minutes: on_change_select_element(e)
+                    .and_then(|s| s.value().parse::<u32>().ok()),
+                ..prefs.clone()
+            })
+        };
+
+        let quiet_start = prefs.quiet_hours.as_ref().map(|q| q.start);
+        let quiet_end = prefs.quiet_hours.as_ref().map(|q| q.end);
+
+        html! {
+            <div class="notification-settings">
+                <span class="notification-settings-title">{"Notifications"}</span>
+                <label class="notification-settings-row">
+                    <input type="checkbox" checked={prefs.push} onchange={on_push} />
+                    {"Push notifications"}
+                </label>
+                <label class="notification-settings-row">
+                    <input type="checkbox" checked={prefs.mentions_only} disabled={!prefs.push} onchange={on_mentions_only} />
+                    {"Only replies to my posts"}
+                </label>
+                <div class="notification-settings-row">
+                    <span>{"Quiet hours"}</span>
+                    <select onchange={on_quiet_start}>
+                        <option value="off" selected={quiet_start.is_none()}>{"Off"}</option>
+                        { for (0..24).map(|hour| html! { <option value={hour.to_string()} selected={quiet_start == Some(hour)}>{ format!("{:02}:00", hour) }</option> }) }
+                    </select>
+                    if let Some(quiet) = prefs.quiet_hours.as_ref() {
+                        <span>{"to"}</span>
+                        <select onchange={on_quiet_end}>
+                            { for (0..24).map(|hour| html! { <option value={hour.to_string()} selected={quiet_end == Some(hour)}>{ format!("{:02}:00", hour) }</option> }) }
+                        </select>
+                        <span class="notification-settings-timezone">{ quiet.timezone.clone() }</span>
+                    }
+                </div>
+                <div class="notification-settings-row">
+                    <span>{"Digest"}</span>
+                    <select onchange={on_digest}>
+                        <option value="off" selected={prefs.digest_minutes.is_none()}>{"Off, send right away"}</option>
+                        { for DIGEST_OPTIONS.iter().map(|minutes| html! { <option value={minutes.to_string()} selected={prefs.digest_minutes == Some(*minutes)}>{ every(*minutes) }</option> }) }
+                    </select>
+                </div>
+            </div>
+        }
+    }) {
+        Ok(v) => v,
+        Err(e) => html! {
+            <div class="notification-settings">
+                <span class="notification-settings-title">{ format!("Notifications ({})", *e) }</span>
+            </div>
+        },
+    }
+}
+
+fn every(minutes: u32) -> String {
+    match minutes {
+        m if m % (60 * 24) == 0 => format!("Every {} day(s)", m / (60 * 24)),
+        m if m % 60 == 0 => format!("Every {} hour(s)", m / 60),
+        m => format!("Every {} minutes", m),
+    }
+}
@@ -1,27 +1,92 @@
-use common::structs::{PushMessage, SafePost};
+use common::structs::{InboxItem, InboxKind, PushMessage, PushStream};
 use yew::prelude::*;
+use yew_hooks::use_effect_once;
 use yew_router::prelude::{use_navigator, use_route};
 
-use crate::{components::richtext::SpoilableText, hooks::use_server_sent_event, BaseRoute};
+use crate::{
+    components::richtext::SpoilableText, hooks::use_server_sent_event, ApiContext, BaseRoute,
+};
+
+// starts out with whatever is still unread in the inbox, live notices are appended as they come in
 
 #[function_component]
 pub fn NotificationBox() -> Html {
-    let mut handle = use_server_sent_event(None, vec!["new_post"]);
+    let mut handle = use_server_sent_event(PushStream::Member, vec!["inbox"]);
     let pop = use_state(|| None);
     let open = use_state(|| false);
+    let unread = use_state(|| 0i64);
 
     let active_notifications = use_state(Vec::<NotificationInfo>::new);
 
+    let api = use_context::<Option<ApiContext>>()
+        .flatten()
+        .and_then(|ctx| ctx.api.ok());
+
+    {
+        let api = api.clone();
+        let active_notifications = active_notifications.clone();
+        let unread = unread.clone();
+        use_effect_once(move || {
+            if let Some(api) = api {
+                wasm_bindgen_futures::spawn_local(async move {
+                    match api.get_inbox(true).await {
+                        Ok(inbox) => {
+                            // the inbox is newest first, the box shows the newest last
+                            active_notifications.set(
+                                inbox
+                                    .items
+                                    .into_iter()
+                                    .rev()
+                                    .map(NotificationInfo::from)
+                                    .collect(),
+                            );
+                            unread.set(inbox.unread);
+                        }
+                        Err(e) => {
+                            gloo::console::error!(format!("failed to load inbox: {:?}", e));
+                        }
+                    }
+                });
+            }
+            || {}
+        })
+    }
+
+    // marks an entry read, or all of them without an id, and takes over the server's unread count
+    let mark_read = {
+        let unread = unread.clone();
+        Callback::from(move |id: Option<i64>| {
+            if let Some(api) = api.clone() {
+                let unread = unread.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let res = match id {
+                        Some(id) => api.mark_inbox_read(id).await,
+                        None => api.mark_all_inbox_read().await,
+                    };
+                    match res {
+                        Ok(left) => unread.set(left),
+                        Err(e) => {
+                            gloo::console::error!(format!("failed to mark inbox read: {:?}", e));
+                        }
+                    }
+                });
+            }
+        })
+    };
+
     {
         let pop = pop.clone();
         let active_notifications = active_notifications.clone();
         let open = open.clone();
+        let mark_read = mark_read.clone();
         use_effect_with(
             (pop, active_notifications, open),
             move |(pop, active_notifications, open)| {
                 if let Some(tpop) = **pop {
                     let mut cloned = active_notifications.to_vec();
-                    cloned.remove(tpop);
+                    if tpop < cloned.len() {
+                        mark_read.emit(Some(cloned.remove(tpop).id));
+                    }
                     if cloned.is_empty() {
                         open.set(false);
                     }
@@ -34,25 +99,34 @@ pub fn NotificationBox() -> Html {
 
     let route = use_route::<BaseRoute>();
 
-    if let Some(thing) = handle.get() {
-        let mut cloned = active_notifications.to_vec();
-        match thing {
-            PushMessage::Open => {}
-            PushMessage::NewPost(post) => {
-                if route
-                    != Some(BaseRoute::ThreadPage {
-                        board_discriminator: post.board_discriminator.clone(),
-                        thread_id: post.thread_post_number.to_string(),
-                    })
-                {
-                    cloned.push(post.as_ref().clone().into());
-                }
-            }
-            PushMessage::Close => {}
+    if let Some(PushMessage::Inbox(item)) = handle.get() {
+        let on_thread_page = route
+            == Some(BaseRoute::ThreadPage {
+                board_discriminator: item.board_discriminator.clone(),
+                thread_id: item.thread_post_number.to_string(),
+            });
+        // new posts in the thread being read are seen already, replies and mod actions can scroll by unnoticed
+        if item.kind == InboxKind::WatchedPost && on_thread_page {
+            mark_read.emit(Some(item.id));
+        } else {
+            let mut cloned = active_notifications.to_vec();
+            cloned.push(item.as_ref().clone().into());
+            active_notifications.set(cloned);
+            unread.set(*unread + 1);
         }
-        active_notifications.set(cloned);
     }
 
+    let mark_all_read = {
+        let active_notifications = active_notifications.clone();
+        let open = open.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            mark_read.emit(None);
+            active_notifications.set(vec![]);
+            open.set(false);
+        })
+    };
+
     let disregard = {
         let pop = pop;
         Callback::from(move |i: usize| {
@@ -91,9 +165,15 @@ pub fn NotificationBox() -> Html {
     html! {
         if !active_notifications.is_empty() {
             <div class="notification-popup">
-                <span href="#" class="notification-popup-toggle" onclick={ let open = open.clone(); Callback::from(move |_| open.set(!*open)) }>{ if *open { "" } else { "" } }</span>
+                <span href="#" class="notification-popup-toggle" onclick={ let open = open.clone(); Callback::from(move |_| open.set(!*open)) }>
+                    { if *open { "" } else { "" } }
+                    if *unread > 0 {
+                        <span class="notification-unread">{ *unread }</span>
+                    }
+                </span>
                 if *open {
                     <div class="notification-list">
+                        <span class="notification-mark-all" onclick={mark_all_read}>{"Mark all read"}</span>
                         {
                             for active_notifications.iter().enumerate().map(|(i, n)| {
 
@@ -126,31 +206,36 @@ pub fn NotificationBox() -> Html {
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct NotificationInfo {
+    pub id: i64,
     pub path: Path,
     pub title: String,
     pub body: String,
     pub icon: Option<String>,
 }
 
-impl From<SafePost> for NotificationInfo {
-    fn from(post: SafePost) -> Self {
+impl From<InboxItem> for NotificationInfo {
+    fn from(item: InboxItem) -> Self {
         Self {
-            // path: format!(
-            //     "/{}/thread/{}",
-            //     post.board_discriminator, post.thread_post_number
-            // ),
-            path: Path::Thread(post.board_discriminator.clone(), post.thread_post_number),
-            title: "New post!".to_owned(),
+            id: item.id,
+            path: Path::Thread(item.board_discriminator.clone(), item.thread_post_number),
+            title: match item.kind {
+                InboxKind::WatchedPost => "New post!",
+                InboxKind::Reply => "Reply to you!",
+                InboxKind::PostDeleted => "A moderator deleted your post",
+                InboxKind::FileBanned => "A moderator banned your file",
+            }
+            .to_owned(),
             body: {
-                let content = post.content.trim();
+                let content = item.summary.trim();
                 let lim = 30;
-                if content.len() > lim {
-                    format!("{}...", &content[..(lim - 3)])
-                } else {
-                    content.to_owned()
+                match content.char_indices().nth(lim - 3) {
+                    Some((end, _)) if content.chars().count() > lim => {
+                        format!("{}...", &content[..end])
+                    }
+                    _ => content.to_owned(),
                 }
             },
-            icon: post.file.map(|f| f.claimed.thumbnail),
+            icon: item.thumbnail,
         }
     }
 }
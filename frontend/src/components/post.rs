@@ -3,7 +3,7 @@ use yew::prelude::*;
 use yew_router::prelude::use_route;
 
 use crate::{
-    components::{DeleteButton, File, MaybeLink, Reply, RichText, WatchButton},
+    components::{DeleteButton, File, MaybeLink, MuteButton, Reply, RichText, WatchButton},
     helpers::CallbackContext,
     BaseRoute,
 };
@@ -67,12 +67,16 @@ pub fn Post(props: &Props) -> Html {
                     <DeleteButton post_number={props.post.post_number} board_discriminator={props.post.board_discriminator.clone()} />
                     if props.topic.is_some() {
                         <WatchButton post_number={props.post.post_number} board_discriminator={props.post.board_discriminator.clone()} />
+                        <MuteButton post_number={props.post.post_number} board_discriminator={props.post.board_discriminator.clone()} />
                     }
                     <span class="post-author">{ match &props.post.author {
                         User::Anonymous => { html! { <>{"Anonymous"}</> } }
                         User::Named(name) => { html! { <>{name}</> } }
                         User::Mod(name) => { html! { <span class="post-author-admin">{format!("{} ## MOD", name)}</span> } }
                     } }</span>
+                    if props.post.sage {
+                        <span class="post-sage" title="This reply didn't bump the thread">{"SAGE"}</span>
+                    }
                     {
                         if let Some(on_click) = &*on_click {
                             html! {
@@ -38,6 +38,8 @@ pub fn PostBox(props: &Props) -> Html {
         content: use_state(String::new),
         file: use_state(|| None),
         spoiler: use_state(|| false),
+        sage: use_state(|| false),
+        silent: use_state(|| false),
     };
 
     let emojis = use_local_storage::<bool>("emojis".to_string()).unwrap_or(true);
@@ -80,11 +82,16 @@ pub fn PostBox(props: &Props) -> Html {
                         None
                     };
 
+                    let upload_id = file.as_ref().map(|f| f.id.clone());
+
                     let create_post = CreatePost {
                         author: Some((*post.name).clone()).filter(|name| !name.is_empty()),
                         content: (*post.content).clone(),
                         moderator: *post.moderator,
                         file,
+                        sage: *post.sage,
+                        // only moderators get the option, the server checks again
+                        silent: *post.moderator && *post.silent,
                     };
 
                     match match routeinfo.map(|(board, thread)| (board, thread.id())) {
@@ -130,7 +137,13 @@ pub fn PostBox(props: &Props) -> Html {
                             post.reset();
                             state.set(ApiState::Loaded(v))
                         }
-                        Err(e) => state.set(ApiState::Error(e)),
+                        Err(e) => {
+                            // don't leave the upload counting against the pending limit, it gets uploaded again on retry
+                            if let Some(id) = upload_id {
+                                let _ = api.cancel_upload(&id).await;
+                            }
+                            state.set(ApiState::Error(e))
+                        }
                     }
                 } else {
                     state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
@@ -190,6 +203,8 @@ pub fn PostBox(props: &Props) -> Html {
     let on_input_content = post.content_change_callback();
     let on_change_file = post.file_change_callback();
     let on_click_spoiler = post.spoiler_change_callback();
+    let on_click_sage = post.sage_change_callback();
+    let on_click_silent = post.silent_change_callback();
 
     match routeinfo {
         Some((Some(_), thread)) => {
@@ -226,6 +241,18 @@ pub fn PostBox(props: &Props) -> Html {
                                         }
                                     }
                                 </div>
+                                if thread.is_some() {
+                                    <div class="post-box-sage" id="notop">
+                                        <a title={ if *post.sage { "Reply without bumping the thread" } else { "Reply and bump the thread" } } onclick={on_click_sage}>
+                                            { if *post.sage { "Sage" } else { "Bump" } }
+                                        </a>
+                                        if *post.moderator {
+                                            <a title={ if *post.silent { "Post without bumping or showing sage" } else { "Post normally" } } onclick={on_click_silent}>
+                                                { if *post.silent { "Silent" } else { "Loud" } }
+                                            </a>
+                                        }
+                                    </div>
+                                }
                                 {
                                     if thread.is_none() {
                                         html! {
@@ -299,6 +326,8 @@ pub struct CreatePostInfo {
     pub content: UseStateHandle<String>,
     pub file: UseStateHandle<Option<web_sys::File>>,
     pub spoiler: UseStateHandle<bool>,
+    pub sage: UseStateHandle<bool>,
+    pub silent: UseStateHandle<bool>,
 }
 
 impl CreatePostInfo {
@@ -368,11 +397,29 @@ impl CreatePostInfo {
         })
     }
 
+    pub fn sage_change_callback(&self) -> Callback<MouseEvent> {
+        let sage = self.sage.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            sage.set(!*sage);
+        })
+    }
+
+    pub fn silent_change_callback(&self) -> Callback<MouseEvent> {
+        let silent = self.silent.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            silent.set(!*silent);
+        })
+    }
+
     pub fn reset(&self) {
         self.topic.set(String::new());
         self.content.set(String::new());
         self.file.set(None);
         self.spoiler.set(false);
+        self.sage.set(false);
+        self.silent.set(false);
     }
 }
 
@@ -26,13 +26,13 @@ pub fn RichText(props: &Props) -> Html {
                 for lines.rev().map(|line| {
                     html! {
                         <>
-                            <RichLine board={props.board.clone()} line={line.clone()} thread_post_number={thread_post_number.clone()} invert={invert} />
+                            <RichLine board={props.board.clone()} line={line.clone()} thread_post_number={thread_post_number.clone()} invert={invert} highlight={props.highlight.clone()} />
                             <br />
                         </>
                     }
                 })
             }
-            <RichLine board={props.board.clone()} line={last.clone()} thread_post_number={thread_post_number.clone()} invert={invert} />
+            <RichLine board={props.board.clone()} line={last.clone()} thread_post_number={thread_post_number.clone()} invert={invert} highlight={props.highlight.clone()} />
         </div>
     }
 }
@@ -43,6 +43,9 @@ pub struct Props {
     pub board: AttrValue,
     pub thread_post_number: AttrValue,
     pub invert: bool,
+    // lowercase word beginnings to mark, used by search results
+    #[prop_or_default]
+    pub highlight: Vec<AttrValue>,
 }
 
 pub struct RichTextContent {
@@ -97,12 +100,12 @@ fn RichLine(props: &RichLineProps) -> Html {
         }
         (_, Some(richtextinfo)) => {
             html! {
-                <span class={richtextinfo.class.to_string()}><SpoilableText content={richtextinfo.string} /></span>
+                <span class={richtextinfo.class.to_string()}><SpoilableText content={richtextinfo.string} highlight={props.highlight.clone()} /></span>
             }
         }
         _ => {
             html! {
-                <span><SpoilableText content={props.line.clone()} /></span>
+                <span><SpoilableText content={props.line.clone()} highlight={props.highlight.clone()} /></span>
             }
         }
     }
@@ -114,6 +117,7 @@ struct RichLineProps {
     pub board: AttrValue,
     pub thread_post_number: AttrValue,
     pub invert: bool,
+    pub highlight: Vec<AttrValue>,
 }
 
 #[function_component]
@@ -128,7 +132,7 @@ pub fn SpoilableText(props: &SpoilableProps) -> Html {
 
     html! {
         <>
-            {first}
+            {highlighted(first, &props.highlight)}
             {
                 // iterate over the splits in pairs
                 following.chunks(2).map(|s| {
@@ -137,7 +141,7 @@ pub fn SpoilableText(props: &SpoilableProps) -> Html {
                         html! {
                             <>
                                 <SpoiledText content={s.first().unwrap_or(&AttrValue::from("this shouldn't happen")).clone()} />
-                                {next}
+                                {highlighted(next, &props.highlight)}
                             </>
                         }
                     } else {
@@ -155,6 +159,42 @@ pub fn SpoilableText(props: &SpoilableProps) -> Html {
 #[derive(Clone, PartialEq, Properties)]
 pub struct SpoilableProps {
     pub content: AttrValue,
+    #[prop_or_default]
+    pub highlight: Vec<AttrValue>,
+}
+
+// search terms come back stemmed, so any word starting with one is marked rather than only exact matches
+fn highlighted(text: &str, terms: &[AttrValue]) -> Html {
+    if terms.is_empty() {
+        return html! { <>{text.to_owned()}</> };
+    }
+
+    let mut parts = vec![];
+    let mut last = 0;
+    let mut word_start = None;
+    // the trailing space ends a word that runs to the end of the text
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (c.is_alphanumeric(), word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                let word = &text[start..i];
+                let lower = word.to_lowercase();
+                if terms.iter().any(|t| lower.starts_with(t.as_str())) {
+                    parts.push(html! { <>{text[last..start].to_owned()}</> });
+                    parts.push(html! { <mark class="search-highlight">{word.to_owned()}</mark> });
+                    last = i;
+                }
+                word_start = None;
+            }
+            _ => {}
+        }
+    }
+    parts.push(html! { <>{text[last..].to_owned()}</> });
+
+    parts.into_iter().collect::<Html>()
 }
 
 #[function_component]
@@ -0,0 +1,138 @@
+use common::structs::ShareLinkInfo;
+use yew::prelude::*;
+use yew_hooks::use_effect_once;
+
+use crate::{api::ApiState, ApiContext};
+
+// lists the share links the user has made, with a button to revoke each of them
+
+#[function_component]
+pub fn ShareLinks() -> Html {
+    let state = use_state(|| ApiState::Pending::<Vec<ShareLinkInfo>>);
+
+    let api_ctx = use_context::<Option<ApiContext>>().flatten();
+
+    let on_click_revoke = {
+        let state = state.clone();
+        let api_ctx = api_ctx.clone();
+        Callback::from(move |(e, id): (MouseEvent, i64)| {
+            e.prevent_default();
+            match api_ctx {
+                Some(ref api_ctx) => match api_ctx.api.clone() {
+                    Ok(api) => {
+                        let state = state.clone();
+                        wasm_bindgen_futures::spawn_local(async move {
+                            if let Err(e) = api.revoke_share_link(id).await {
+                                state.set(ApiState::Error(e));
+                                return;
+                            }
+                            match api.list_share_links().await {
+                                Ok(v) => {
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+        })
+    };
+
+    {
+        let state = state.clone();
+        use_effect_once(move || {
+            match api_ctx {
+                Some(api_ctx) => match api_ctx.api {
+                    Ok(api) => {
+                        state.set(ApiState::Loading);
+                        wasm_bindgen_futures::spawn_local(async move {
+                            match api.list_share_links().await {
+                                Ok(v) => {
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+            || {}
+        })
+    }
+
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+
+    match state.standard_html("ShareLinks", |links| {
+        if links.is_empty() {
+            return html! {};
+        }
+        html! {
+            <div class="share-links">
+                <span class="share-links-title">{"Share Links"}</span>
+                {
+                    for links.iter().map(|link| {
+                        let id = link.id;
+                        let on_click_revoke = on_click_revoke.clone();
+                        html! {
+                            <div class="share-links-content">
+                                <a href={link.url.clone()} class="share-links-path">{ link.path.rsplit('/').next().unwrap_or_default().to_owned() }</a>
+                                <span class="share-links-views">
+                                    {
+                                        match link.max_views {
+                                            Some(max) => format!("{}/{} views", link.views, max),
+                                            None => format!("{} views", link.views),
+                                        }
+                                    }
+                                </span>
+                                {
+                                    if link.revoked {
+                                        html! { <span class="share-links-status">{"Revoked"}</span> }
+                                    } else {
+                                        html! {
+                                            <>
+                                                <span class="share-links-status">{ expires_in(link.expires_at.saturating_sub(now)) }</span>
+                                                <a onclick={on_click_revoke.reform(move |e| (e, id))} class="share-links-button">{"Revoke"}</a>
+                                            </>
+                                        }
+                                    }
+                                }
+                            </div>
+                        }
+                    })
+                }
+            </div>
+        }
+    }) {
+        Ok(v) => v,
+        Err(e) => html! {
+            <div class="share-links">
+                <span class="share-links-title">{ format!("Share Links ({})", *e) }</span>
+            </div>
+        },
+    }
+}
+
+fn expires_in(seconds: u64) -> String {
+    match seconds {
+        s if s >= 60 * 60 * 24 => format!("{}d left", s / (60 * 60 * 24)),
+        s if s >= 60 * 60 => format!("{}h left", s / (60 * 60)),
+        s => format!("{}m left", s / 60),
+    }
+}
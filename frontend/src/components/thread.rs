@@ -80,7 +80,11 @@ pub fn Thread(props: &Props) -> Html {
                 <ContextProvider<CallbackEmitterContext> context={set_add_text_callback}>
                     <ContextProvider<Option<CallbackContext>> context={(*add_text_callback).clone()}>
                         <div class="thread-view">
-                            <PostBox override_thread={props.thread.parent_post().thread_post_number.to_string()}/>
+                            if props.thread.archived() {
+                                <div class="thread-archived">{"This thread is archived, it can't be replied to"}</div>
+                            } else {
+                                <PostBox override_thread={props.thread.parent_post().thread_post_number.to_string()}/>
+                            }
                             <ContextProvider<UseStateHandle<Option<bool>>> context={set_watching}>
                                 <Post post={state.parent_post().clone()} topic={ state.topic() }/>
                             </ContextProvider<UseStateHandle<Option<bool>>>>
@@ -203,6 +207,14 @@ impl ThreadState {
         }
     }
 
+    // board pages only list live threads
+    pub fn archived(&self) -> bool {
+        match self {
+            Self::Expandable(_) => false,
+            Self::Full(thread) => thread.archived,
+        }
+    }
+
     pub fn show_post_box(&self) -> bool {
         match self {
             Self::Expandable(_) => true,
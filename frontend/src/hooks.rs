@@ -1,116 +1,338 @@
-use std::sync::Arc;
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, sync::Arc};
 
-use common::structs::PushMessage;
-use futures::{channel::oneshot, StreamExt};
+use common::structs::{PushMessage, PushStream, WsClientMessage, WsServerMessage};
+use futures::{channel::oneshot, FutureExt as _, SinkExt, StreamExt};
 use yew::prelude::*;
 
 pub struct ServerSentEventHandle {
-    inner: UseStateHandle<Vec<PushMessage>>,
+    queue: EventQueue,
 }
 
 impl ServerSentEventHandle {
+    // hands out one event per render in the order they arrived, and renders again while more are waiting
     pub fn get(&mut self) -> Option<PushMessage> {
-        let mut cloned = self.inner.to_vec();
-        if cloned.is_empty() {
-            return None;
+        let (event, waiting) = {
+            let mut events = self.queue.events.borrow_mut();
+            (events.pop_front(), !events.is_empty())
+        };
+        if waiting {
+            self.queue.update.force_update();
         }
-        let r = cloned.pop();
-        self.inner.set(cloned);
-        r
+        event
     }
 }
 
-#[hook]
-pub fn use_server_sent_event(
-    nav_info: Option<(String, i64)>,
-    event_types: Vec<&'static str>,
-) -> ServerSentEventHandle {
-    let inner = use_state(Vec::new);
-    let passer = use_state(|| None);
+// a replay after reconnecting delivers a burst of events between two renders, none of them can be dropped
+#[derive(Clone)]
+struct EventQueue {
+    events: Rc<RefCell<VecDeque<PushMessage>>>,
+    update: UseForceUpdateHandle,
+}
 
-    {
-        let inner = inner.clone();
-        let passer = passer.clone();
-        use_effect_with((inner, passer), move |(inner, passer)| {
-            let passed = (**passer).clone();
-            if let Some(passed) = passed {
-                let mut cloned = inner.to_vec();
-                cloned.push(passed);
-                inner.set(cloned);
+impl EventQueue {
+    fn push(&self, event: PushMessage) {
+        self.events.borrow_mut().push_back(event);
+        self.update.force_update();
+    }
+}
+
+// SSE is tried first, a transport that fails before delivering anything hands over to the other one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    ServerSentEvents,
+    WebSocket,
+}
+
+impl Transport {
+    fn other(self) -> Self {
+        match self {
+            Transport::ServerSentEvents => Transport::WebSocket,
+            Transport::WebSocket => Transport::ServerSentEvents,
+        }
+    }
+}
+
+enum Outcome {
+    Canceled,
+    // whether anything came through before the connection dropped
+    Dropped { worked: bool },
+}
+
+// a proxy buffering the response never lets the open event through
+const OPEN_TIMEOUT_MS: u32 = 10_000;
+const PING_INTERVAL_MS: u32 = 25_000;
+
+fn sse_path(stream: &PushStream) -> String {
+    match stream {
+        PushStream::Member => "/api/v1/notifications".to_owned(),
+        PushStream::Board(discrim) => format!("/api/v1/board/{}/notifications", discrim),
+        PushStream::Thread(discrim, thread) => {
+            format!("/api/v1/board/{}/thread/{}/notifications", discrim, thread)
+        }
+    }
+}
+
+fn ws_url() -> Option<String> {
+    let location = web_sys::window()?.location();
+    let protocol = match location.protocol().ok()?.as_str() {
+        "https:" => "wss:",
+        _ => "ws:",
+    };
+    Some(format!("{}//{}/api/v1/ws", protocol, location.host().ok()?))
+}
+
+fn parse_event<T: serde::de::DeserializeOwned>(event_type: &str, data: &str) -> Option<T> {
+    serde_json::from_str(data)
+        .map_err(|e| gloo::console::error!(format!("failed to parse {}: {:?}", event_type, e)))
+        .ok()
+}
+
+// both transports carry the same event names and json data
+fn into_push_message(event_type: &str, data: &str) -> Option<PushMessage> {
+    match event_type {
+        "open" => Some(PushMessage::Open),
+        "new_post" => parse_event(event_type, data).map(|p| PushMessage::NewPost(Arc::new(p))),
+        "reply" => parse_event(event_type, data).map(|p| PushMessage::ReplyToYou(Arc::new(p))),
+        "inbox" => parse_event(event_type, data).map(|i| PushMessage::Inbox(Arc::new(i))),
+        "new_thread" => parse_event(event_type, data).map(|t| PushMessage::NewThread(Arc::new(t))),
+        "post_deleted" => parse_event(event_type, data).map(PushMessage::PostDeleted),
+        "thread_bumped" => {
+            parse_event(event_type, data).map(|p| PushMessage::ThreadBumped(Arc::new(p)))
+        }
+        "thread_replied" => {
+            parse_event(event_type, data).map(|p| PushMessage::ThreadReplied(Arc::new(p)))
+        }
+        "thread_status" => parse_event(event_type, data).map(PushMessage::ThreadStatusChanged),
+        "close" => Some(PushMessage::Close),
+        miss => {
+            gloo::console::warn!(format!("unknown event type: `{}`", miss));
+            None
+        }
+    }
+}
+
+fn pass_event(queue: &EventQueue, event_type: &str, data: &str) {
+    if let Some(event) = into_push_message(event_type, data) {
+        gloo::console::info!(format!("got event: {:?}", event));
+        queue.push(event);
+    }
+}
+
+async fn run_server_sent_events(
+    stream: &PushStream,
+    event_types: &[&'static str],
+    last_event_id: &mut Option<u64>,
+    queue: &EventQueue,
+    cancel: &mut oneshot::Receiver<()>,
+) -> Outcome {
+    use gloo::utils::format::JsValueSerdeExt;
+
+    let path = match last_event_id {
+        Some(id) => format!("{}?last_event_id={}", sse_path(stream), id),
+        None => sse_path(stream),
+    };
+    let mut source = match gloo_net::eventsource::futures::EventSource::new(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("failed to create event source: {:?}", e);
+            return Outcome::Dropped { worked: false };
+        }
+    };
+
+    let subs = event_types
+        .iter()
+        .cloned()
+        .flat_map(|e| source.subscribe(e))
+        .collect::<Vec<_>>();
+
+    let mut all = futures::stream::select_all(subs);
+    let mut open_timeout = gloo::timers::future::TimeoutFuture::new(OPEN_TIMEOUT_MS).fuse();
+    let mut worked = false;
+
+    loop {
+        futures::select! {
+            v = all.next() => match v {
+                Some(Ok((event_type, msg))) => {
+                    worked = true;
+                    if let Ok(id) = msg.last_event_id().parse::<u64>() {
+                        *last_event_id = Some(id);
+                    }
+                    let data = msg.data().into_serde::<String>().unwrap_or_default();
+                    pass_event(queue, &event_type, &data);
+                }
+                // the browser retries on its own unless the source gave up entirely
+                Some(Err(_)) => {
+                    if source.state() == gloo_net::eventsource::State::Closed {
+                        return Outcome::Dropped { worked };
+                    }
+                }
+                None => {
+                    return Outcome::Dropped { worked };
+                }
+            },
+            _ = open_timeout => {
+                if !worked {
+                    return Outcome::Dropped { worked };
+                }
             }
-            passer.set(None);
-            || {}
-        });
+            _ = cancel => {
+                return Outcome::Canceled;
+            }
+        }
     }
+}
 
-    {
-        let passer = passer.setter();
-        use_effect_with(nav_info, move |nav_info| {
-            let nav_info = nav_info.clone();
-            let (canceler, mut cancel) = oneshot::channel::<()>();
-            wasm_bindgen_futures::spawn_local(async move {
-                gloo::console::info!("event source spawned");
-                let mut stream =
-                    match gloo_net::eventsource::futures::EventSource::new(&match nav_info {
-                        Some((discrim, thread)) => {
-                            format!("/api/v1/board/{}/thread/{}/notifications", discrim, thread)
+async fn run_websocket(
+    stream: &PushStream,
+    event_types: &[&'static str],
+    last_event_id: &mut Option<u64>,
+    queue: &EventQueue,
+    cancel: &mut oneshot::Receiver<()>,
+) -> Outcome {
+    use gloo_net::websocket::{futures::WebSocket, Message};
+
+    let Some(url) = ws_url() else {
+        return Outcome::Dropped { worked: false };
+    };
+    let socket = match WebSocket::open(&url) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("failed to open websocket: {:?}", e);
+            return Outcome::Dropped { worked: false };
+        }
+    };
+    let (mut write, read) = socket.split();
+    let mut read = read.fuse();
+
+    let subscribe = WsClientMessage::Subscribe {
+        stream: stream.clone(),
+        last_event_id: *last_event_id,
+    };
+    let Ok(subscribe) = serde_json::to_string(&subscribe) else {
+        return Outcome::Dropped { worked: false };
+    };
+    if write.send(Message::Text(subscribe)).await.is_err() {
+        return Outcome::Dropped { worked: false };
+    }
+
+    let ping = serde_json::to_string(&WsClientMessage::Ping).unwrap_or_default();
+    let mut heartbeat = gloo::timers::future::IntervalStream::new(PING_INTERVAL_MS).fuse();
+    let mut worked = false;
+
+    loop {
+        futures::select! {
+            v = read.next() => match v {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<WsServerMessage>(&text) {
+                        Ok(WsServerMessage::Event { id, event, data, .. }) => {
+                            worked = true;
+                            if id.is_some() {
+                                *last_event_id = id;
+                            }
+                            if event_types.contains(&event.as_str()) {
+                                pass_event(queue, &event, data.as_deref().unwrap_or_default());
+                            }
+                        }
+                        // stands in for the open event the SSE endpoints send
+                        Ok(WsServerMessage::Subscribed { .. }) => {
+                            worked = true;
+                            queue.push(PushMessage::Open);
+                        }
+                        Ok(WsServerMessage::Error { message }) => {
+                            gloo::console::error!(format!("websocket error: {}", message));
                         }
-                        None => "/api/v1/notifications".to_owned(),
-                    }) {
-                        Ok(s) => s,
+                        Ok(_) => {}
                         Err(e) => {
-                            log::error!("failed to create event source: {:?}", e);
-                            return;
+                            gloo::console::error!(format!("failed to parse websocket message: {:?}", e));
                         }
-                    };
+                    }
+                }
+                Some(Ok(Message::Bytes(_))) => {}
+                Some(Err(_)) | None => {
+                    return Outcome::Dropped { worked };
+                }
+            },
+            _ = heartbeat.next() => {
+                if write.send(Message::Text(ping.clone())).await.is_err() {
+                    return Outcome::Dropped { worked };
+                }
+            }
+            _ = cancel => {
+                return Outcome::Canceled;
+            }
+        }
+    }
+}
 
+#[hook]
+pub fn use_server_sent_event(
+    stream: PushStream,
+    event_types: Vec<&'static str>,
+) -> ServerSentEventHandle {
+    let queue = EventQueue {
+        events: use_mut_ref(VecDeque::new),
+        update: use_force_update(),
+    };
+
+    {
+        let queue = queue.clone();
+        use_effect_with(stream, move |stream| {
+            let stream = stream.clone();
+            let (canceler, mut cancel) = oneshot::channel::<()>();
+            wasm_bindgen_futures::spawn_local(async move {
                 let mut event_types = event_types.clone();
                 event_types.push("close");
                 event_types.push("open");
 
-                let subs = event_types
-                    .iter()
-                    .cloned()
-                    .flat_map(|e| stream.subscribe(e))
-                    .collect::<Vec<_>>();
-
-                let mut all = futures::stream::select_all(subs);
+                // the last id we saw, sent along on reconnect so the server can replay what we missed
+                let mut last_event_id: Option<u64> = None;
+                let mut transport = Transport::ServerSentEvents;
+                let mut backoff = 1000;
 
                 loop {
-                    futures::select! {
-                        v = all.next() => {
-                            if let Some(Ok((event_type, msg))) = v {
-                                use gloo::utils::format::JsValueSerdeExt;
-                                if let Some(event) = match event_type.as_str() {
-                                    "open" => Some(PushMessage::Open),
-                                    "new_post" => serde_json::from_str(
-                                        msg.data()
-                                            .into_serde::<String>()
-                                            .unwrap_or_default()
-                                            .as_str(),
-                                    )
-                                    .map(|p: common::structs::SafePost| PushMessage::NewPost(Arc::new(p)))
-                                    .map_err(|e| {
-                                        gloo::console::error!(format!("failed to parse new_post: {:?}", e))
-                                    })
-                                    .ok(),
-                                    "close" => Some(PushMessage::Close),
-                                    miss => {
-                                        gloo::console::warn!(format!("unknown event type: `{}`", miss));
-                                        None
-                                    }
-                                } {
-                                    gloo::console::info!(format!("got event: {:?}", event));
-                                    passer.set(Some(event));
-                                }
-                            }
+                    gloo::console::info!(format!("event stream spawned over {:?}", transport));
+                    let outcome = match transport {
+                        Transport::ServerSentEvents => {
+                            run_server_sent_events(
+                                &stream,
+                                &event_types,
+                                &mut last_event_id,
+                                &queue,
+                                &mut cancel,
+                            )
+                            .await
+                        }
+                        Transport::WebSocket => {
+                            run_websocket(
+                                &stream,
+                                &event_types,
+                                &mut last_event_id,
+                                &queue,
+                                &mut cancel,
+                            )
+                            .await
                         }
+                    };
+
+                    match outcome {
+                        Outcome::Canceled => break,
+                        Outcome::Dropped { worked: true } => backoff = 1000,
+                        Outcome::Dropped { worked: false } => transport = transport.other(),
+                    }
+
+                    gloo::console::info!(format!(
+                        "event stream closed, reconnecting over {:?} in {}ms",
+                        transport, backoff
+                    ));
+                    futures::select! {
+                        _ = gloo::timers::future::TimeoutFuture::new(backoff).fuse() => {}
                         _ = cancel => {
                             break;
                         }
                     }
+                    backoff = (backoff * 2).min(30_000);
                 }
-                gloo::console::info!("event source canceled");
+                gloo::console::info!("event stream canceled");
             });
 
             || {
@@ -118,5 +340,5 @@ pub fn use_server_sent_event(
             }
         });
     }
-    ServerSentEventHandle { inner }
+    ServerSentEventHandle { queue }
 }
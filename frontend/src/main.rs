@@ -39,8 +39,14 @@ pub enum BaseRoute {
     Settings,
     #[at("/redeem")]
     Redeem,
+    #[at("/watching")]
+    Watching,
+    #[at("/search")]
+    Search,
     #[at("/:board_discriminator/")]
     BoardPage { board_discriminator: String },
+    #[at("/:board_discriminator/catalog")]
+    CatalogPage { board_discriminator: String },
     #[at("/:board_discriminator/thread/:thread_id")]
     ThreadPage {
         board_discriminator: String,
@@ -59,6 +65,9 @@ impl BaseRoute {
             Self::BoardPage {
                 board_discriminator,
             } => Some(board_discriminator.clone()),
+            Self::CatalogPage {
+                board_discriminator,
+            } => Some(board_discriminator.clone()),
             Self::ThreadPage {
                 board_discriminator,
                 ..
@@ -218,6 +227,16 @@ fn switch(routes: BaseRoute) -> Html {
                 <pages::Redeem/>
             }
         }
+        BaseRoute::Watching => {
+            html! {
+                <pages::Watching/>
+            }
+        }
+        BaseRoute::Search => {
+            html! {
+                <pages::Search/>
+            }
+        }
         BaseRoute::BoardPage {
             board_discriminator: _,
         } => {
@@ -225,6 +244,13 @@ fn switch(routes: BaseRoute) -> Html {
                 <pages::BoardPage />
             }
         }
+        BaseRoute::CatalogPage {
+            board_discriminator: _,
+        } => {
+            html! {
+                <pages::CatalogPage />
+            }
+        }
         BaseRoute::ThreadPage {
             board_discriminator: _,
             thread_id: _,
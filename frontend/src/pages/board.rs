@@ -1,8 +1,14 @@
-use common::structs::{BoardWithThreads, SafePost};
+use common::structs::{BoardWithThreads, PushMessage, PushStream, SafePost, ThreadStatus};
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-use crate::{api::ApiState, components::*, helpers::SuccessfulPostContext, ApiContext, BaseRoute};
+use crate::{
+    api::ApiState, components::*, helpers::SuccessfulPostContext, hooks::use_server_sent_event,
+    ApiContext, BaseRoute,
+};
+
+// how many replies the board page shows under each thread, matches with_lazy_posts on the backend
+const LAZY_POSTS: usize = 5;
 
 #[function_component]
 pub fn BoardPage() -> Html {
@@ -11,9 +17,103 @@ pub fn BoardPage() -> Html {
     let api_ctx = use_context::<Option<ApiContext>>();
 
     let board: UseStateHandle<ApiState<BoardWithThreads>> = use_state(|| ApiState::Pending);
+
+    let mut handle = use_server_sent_event(
+        board_ctx
+            .clone()
+            .and_then(|b| b.board_discriminator())
+            .map(PushStream::Board)
+            .unwrap_or(PushStream::Member),
+        vec![
+            "new_thread",
+            "thread_bumped",
+            "thread_replied",
+            "post_deleted",
+            "thread_status",
+        ],
+    );
+
+    if let Some(message) = handle.get() {
+        if let ApiState::Loaded(mut tboard) = (*board).clone() {
+            let changed = match message {
+                PushMessage::NewThread(thread) => {
+                    tboard.threads.insert(0, thread.as_ref().clone());
+                    true
+                }
+                PushMessage::ThreadBumped(post) => {
+                    match tboard
+                        .threads
+                        .iter()
+                        .position(|t| t.thread_post.post_number == post.thread_post_number)
+                    {
+                        Some(i) => {
+                            // bumped threads move to the top, same as when the board is loaded
+                            let mut thread = tboard.threads.remove(i);
+                            thread.posts.push(post.as_ref().clone());
+                            if thread.posts.len() > LAZY_POSTS {
+                                thread.posts.remove(0);
+                            }
+                            thread.post_count += 1;
+                            tboard.threads.insert(0, thread);
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::ThreadReplied(post) => {
+                    match tboard
+                        .threads
+                        .iter_mut()
+                        .find(|t| t.thread_post.post_number == post.thread_post_number)
+                    {
+                        // past the bump limit, so the thread stays where it is
+                        Some(thread) => {
+                            thread.posts.push(post.as_ref().clone());
+                            if thread.posts.len() > LAZY_POSTS {
+                                thread.posts.remove(0);
+                            }
+                            thread.post_count += 1;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::PostDeleted(deleted) => {
+                    match tboard
+                        .threads
+                        .iter_mut()
+                        .find(|t| t.thread_post.post_number == deleted.thread_post_number)
+                    {
+                        Some(thread) => {
+                            thread
+                                .posts
+                                .retain(|p| p.post_number != deleted.post_number);
+                            thread.post_count -= 1;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::ThreadStatusChanged(change) => match change.status {
+                    // archived threads are only reachable by url
+                    ThreadStatus::Deleted | ThreadStatus::Archived => {
+                        tboard
+                            .threads
+                            .retain(|t| t.thread_post.post_number != change.thread_post_number);
+                        true
+                    }
+                },
+                _ => false,
+            };
+            if changed {
+                board.set(ApiState::Loaded(tboard));
+            }
+        }
+    }
+
     {
         let board = board.clone();
-        let api_ctx = api_ctx;
+        let api_ctx = api_ctx.clone();
         use_effect_with(board_ctx, |board_ctx| {
             board.set(ApiState::Loading);
             let board_ctx = board_ctx.clone();
@@ -47,6 +147,50 @@ pub fn BoardPage() -> Html {
         });
     }
 
+    let loading_more = use_state(|| false);
+    let load_more = {
+        let board = board.clone();
+        let loading_more = loading_more.clone();
+        let api = api_ctx.clone().flatten().and_then(|ctx| ctx.api.ok());
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            let (Some(api), ApiState::Loaded(tboard)) = (api.clone(), (*board).clone()) else {
+                return;
+            };
+            if *loading_more {
+                return;
+            }
+            loading_more.set(true);
+            let board = board.clone();
+            let loading_more = loading_more.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match api
+                    .get_board_page(&tboard.info.discriminator, tboard.page + 1)
+                    .await
+                {
+                    Ok(next) => {
+                        let mut tboard = tboard;
+                        // threads bumped since the last page was loaded would show up twice
+                        for thread in next.threads {
+                            if !tboard.threads.iter().any(|t| {
+                                t.thread_post.post_number == thread.thread_post.post_number
+                            }) {
+                                tboard.threads.push(thread);
+                            }
+                        }
+                        tboard.page = next.page;
+                        tboard.pages = next.pages;
+                        board.set(ApiState::Loaded(tboard));
+                    }
+                    Err(e) => {
+                        gloo::console::error!(format!("failed to load page: {:?}", e));
+                    }
+                }
+                loading_more.set(false);
+            });
+        })
+    };
+
     // let callback = if let Some(c) = use_context::<Option<CallbackContext>>().flatten() {
     //     let nav = nav.clone();
     //     let modified = c.callback.reform(move |r: common::structs::Reply| {
@@ -96,6 +240,10 @@ pub fn BoardPage() -> Html {
                                 }
                                 html! {
                                     <div class={"board-page-threads"}>
+                                        <div class="catalog-controls">
+                                            <Link<BaseRoute> to={BaseRoute::CatalogPage { board_discriminator: board.info.discriminator.clone() }}>{"Catalog"}</Link<BaseRoute>>
+                                            <ExportButton board_discriminator={board.info.discriminator.clone()} />
+                                        </div>
                                         {
                                             board.threads.iter().map(|thread| {
                                                 html! {
@@ -103,6 +251,14 @@ pub fn BoardPage() -> Html {
                                                 }
                                             }).collect::<Html>()
                                         }
+                                        if board.page + 1 < board.pages {
+                                            <div class="board-page-more">
+                                                <a href="#" onclick={load_more.clone()}>{"Load more threads"}</a>
+                                                if *loading_more {
+                                                    <Spinner />
+                                                }
+                                            </div>
+                                        }
                                     </div>
                                 }
                             }).unwrap_or_else(|e| {
@@ -0,0 +1,303 @@
+use common::structs::{
+    Catalog, CatalogThread, PushMessage, PushStream, ThreadStatus, ThreadWithLazyPosts,
+};
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+use crate::{
+    api::ApiState,
+    components::*,
+    helpers::{on_change_select_element, on_input_to_string, SuccessfulPostContext},
+    hooks::use_server_sent_event,
+    ApiContext, BaseRoute,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum CatalogSort {
+    Bump,
+    Created,
+    Replies,
+}
+
+impl CatalogSort {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "created" => Self::Created,
+            "replies" => Self::Replies,
+            _ => Self::Bump,
+        }
+    }
+}
+
+#[function_component]
+pub fn CatalogPage() -> Html {
+    let board_ctx = use_route::<BaseRoute>();
+    let nav = use_navigator();
+    let api_ctx = use_context::<Option<ApiContext>>();
+
+    let catalog: UseStateHandle<ApiState<Catalog>> = use_state(|| ApiState::Pending);
+    let sort = use_state(|| CatalogSort::Bump);
+    let filter = use_state(String::new);
+
+    let mut handle = use_server_sent_event(
+        board_ctx
+            .clone()
+            .and_then(|b| b.board_discriminator())
+            .map(PushStream::Board)
+            .unwrap_or(PushStream::Member),
+        vec![
+            "new_thread",
+            "thread_bumped",
+            "thread_replied",
+            "post_deleted",
+            "thread_status",
+        ],
+    );
+
+    // the server sends threads in bump order, live events keep them that way
+    if let Some(message) = handle.get() {
+        if let ApiState::Loaded(mut tcatalog) = (*catalog).clone() {
+            let changed = match message {
+                PushMessage::NewThread(thread) => {
+                    tcatalog.threads.insert(0, from_lazy(thread.as_ref()));
+                    true
+                }
+                PushMessage::ThreadBumped(post) => {
+                    match tcatalog
+                        .threads
+                        .iter()
+                        .position(|t| t.thread_post_number == post.thread_post_number)
+                    {
+                        Some(i) => {
+                            let mut thread = tcatalog.threads.remove(i);
+                            thread.reply_count += 1;
+                            if post.file.is_some() {
+                                thread.image_count += 1;
+                            }
+                            thread.last_bump = post.timestamp;
+                            tcatalog.threads.insert(0, thread);
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::ThreadReplied(post) => {
+                    match tcatalog
+                        .threads
+                        .iter_mut()
+                        .find(|t| t.thread_post_number == post.thread_post_number)
+                    {
+                        Some(thread) => {
+                            thread.reply_count += 1;
+                            if post.file.is_some() {
+                                thread.image_count += 1;
+                            }
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::PostDeleted(deleted) => {
+                    match tcatalog
+                        .threads
+                        .iter_mut()
+                        .find(|t| t.thread_post_number == deleted.thread_post_number)
+                    {
+                        Some(thread) => {
+                            thread.reply_count -= 1;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+                PushMessage::ThreadStatusChanged(change) => match change.status {
+                    ThreadStatus::Deleted | ThreadStatus::Archived => {
+                        tcatalog
+                            .threads
+                            .retain(|t| t.thread_post_number != change.thread_post_number);
+                        true
+                    }
+                },
+                _ => false,
+            };
+            if changed {
+                catalog.set(ApiState::Loaded(tcatalog));
+            }
+        }
+    }
+
+    {
+        let catalog = catalog.clone();
+        use_effect_with(board_ctx, |board_ctx| {
+            catalog.set(ApiState::Loading);
+            let board_ctx = board_ctx.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match api_ctx {
+                    Some(Some(api_ctx)) => match api_ctx.api {
+                        Err(e) => {
+                            catalog.set(ApiState::Error(e));
+                        }
+                        Ok(api) => {
+                            if let Some(Some(boardinf)) = board_ctx.map(|b| b.board_discriminator())
+                            {
+                                match api.get_catalog(&boardinf).await {
+                                    Err(e) => {
+                                        catalog.set(ApiState::Error(e));
+                                    }
+                                    Ok(thiscatalog) => {
+                                        catalog.set(ApiState::Loaded(thiscatalog));
+                                    }
+                                };
+                            } else {
+                                catalog
+                                    .set(ApiState::ContextError(AttrValue::from("BoardContext")));
+                            }
+                        }
+                    },
+                    _ => {
+                        catalog.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                    }
+                }
+            });
+        });
+    }
+
+    let on_sort = {
+        let sort = sort.clone();
+        Callback::from(move |e: Event| {
+            if let Some(select) = on_change_select_element(e) {
+                sort.set(CatalogSort::from_name(&select.value()));
+            }
+        })
+    };
+    let on_filter = {
+        let filter = filter.clone();
+        Callback::from(move |e: InputEvent| {
+            if let Some(input) = on_input_to_string(e) {
+                filter.set(input.value());
+            }
+        })
+    };
+
+    let successful_post = {
+        let nav = nav.clone();
+
+        SuccessfulPostContext {
+            callback: Callback::from(move |p: common::structs::SafePost| {
+                if let Some(nav) = &nav {
+                    nav.push(&BaseRoute::ThreadPage {
+                        board_discriminator: p.board_discriminator.clone(),
+                        thread_id: p.thread_post_number.to_string(),
+                    });
+                }
+            }),
+        }
+    };
+
+    html! {
+        <ContextProvider<SuccessfulPostContext> context={successful_post}>
+            <div class={"board-page"}>
+                <Header />
+                {
+                    catalog.standard_html("CatalogPage", |catalog| {
+                        if let Some(window) = web_sys::window() {
+                            if let Some(document) = window.document() {
+                                document.set_title(&format!("{}/{}/ - Catalog", crate::PREFIX, catalog.info.discriminator));
+                            }
+                        }
+                        let needle = filter.trim().to_lowercase();
+                        let mut threads = catalog
+                            .threads
+                            .iter()
+                            .filter(|t| {
+                                needle.is_empty()
+                                    || t.topic.to_lowercase().contains(&needle)
+                                    || t.excerpt.to_lowercase().contains(&needle)
+                            })
+                            .collect::<Vec<_>>();
+                        match *sort {
+                            CatalogSort::Bump => {}
+                            CatalogSort::Created => threads.sort_by(|a, b| b.created_at.cmp(&a.created_at)),
+                            CatalogSort::Replies => threads.sort_by(|a, b| b.reply_count.cmp(&a.reply_count)),
+                        }
+                        html! {
+                            <>
+                                <div class="catalog-controls">
+                                    <Link<BaseRoute> to={BaseRoute::BoardPage { board_discriminator: catalog.info.discriminator.clone() }}>{"Index"}</Link<BaseRoute>>
+                                    <select onchange={on_sort.clone()}>
+                                        <option value="bump" selected={*sort == CatalogSort::Bump}>{"Bump order"}</option>
+                                        <option value="created" selected={*sort == CatalogSort::Created}>{"Creation date"}</option>
+                                        <option value="replies" selected={*sort == CatalogSort::Replies}>{"Reply count"}</option>
+                                    </select>
+                                    <input type="text" placeholder="Filter" value={(*filter).clone()} oninput={on_filter.clone()} />
+                                </div>
+                                <div class="catalog-grid">
+                                    {
+                                        for threads.into_iter().map(|thread| {
+                                            let to = BaseRoute::ThreadPage {
+                                                board_discriminator: catalog.info.discriminator.clone(),
+                                                thread_id: thread.thread_post_number.to_string(),
+                                            };
+                                            html! {
+                                                <div class="catalog-thread">
+                                                    if let Some(file) = thread.file.as_ref() {
+                                                        <File file={file.clone()} />
+                                                    }
+                                                    <span class="catalog-thread-counts" title="replies / images">
+                                                        { format!("R: {} / I: {}", thread.reply_count, thread.image_count) }
+                                                    </span>
+                                                    <Link<BaseRoute> classes="catalog-thread-topic" to={to}>{ thread.topic.clone() }</Link<BaseRoute>>
+                                                    <span class="catalog-thread-excerpt">{ thread.excerpt.clone() }</span>
+                                                </div>
+                                            }
+                                        })
+                                    }
+                                </div>
+                            </>
+                        }
+                    }).unwrap_or_else(|e| {
+                        if let Some(window) = web_sys::window() {
+                            if let Some(document) = window.document() {
+                                document.set_title(&format!("{}Error", crate::PREFIX));
+                            }
+                        }
+                        match nav {
+                            Some(nav) => {
+                                nav.replace(&BaseRoute::NotFound);
+                            }
+                            None => {
+                                gloo::console::error!("Failed to navigate to /404");
+                            }
+                        }
+                        html! {
+                            <div class={"board-page-error"}>
+                                <h1>{"Error"}</h1>
+                                <p>{format!("{}", *e)}</p>
+                            </div>
+                        }
+                    })
+                }
+                <Footer />
+            </div>
+        </ContextProvider<SuccessfulPostContext>>
+    }
+}
+
+// new threads arrive with their posts, the catalog only needs the summary
+fn from_lazy(thread: &ThreadWithLazyPosts) -> CatalogThread {
+    CatalogThread {
+        thread_post_number: thread.thread_post.post_number,
+        topic: thread.topic.clone(),
+        excerpt: common::excerpt(&thread.thread_post.content, common::CATALOG_EXCERPT_LENGTH),
+        file: thread.thread_post.file.clone(),
+        reply_count: thread.post_count,
+        image_count: thread.thread_post.file.iter().count() as i64
+            + thread.posts.iter().filter(|p| p.file.is_some()).count() as i64,
+        created_at: thread.thread_post.timestamp,
+        last_bump: thread
+            .posts
+            .last()
+            .map(|p| p.timestamp)
+            .unwrap_or(thread.thread_post.timestamp),
+    }
+}
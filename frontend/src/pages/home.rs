@@ -58,6 +58,8 @@ pub fn Home() -> Html {
                                 <>
                                     <div class="home-title">
                                         <h1>{"PChan"}</h1>
+                                        <Link<BaseRoute> classes="home-watching-link" to={BaseRoute::Watching}>{"Watched threads"}</Link<BaseRoute>>
+                                        <Link<BaseRoute> classes="home-watching-link" to={BaseRoute::Search}>{"Search"}</Link<BaseRoute>>
                                     </div>
                                     <div class="home-board-list">
                                         {for boards.iter().enumerate().map(|(i, board)| html! {
@@ -1,16 +1,22 @@
 mod board;
+mod catalog;
 mod home;
 mod not_found;
 mod redeem;
+mod search;
 mod settings;
 mod thread;
+mod watching;
 
 pub use board::BoardPage;
+pub use catalog::CatalogPage;
 pub use home::Home;
 pub use not_found::NotFound;
 pub use redeem::Redeem;
+pub use search::Search;
 pub use settings::Settings;
 pub use thread::ThreadPage;
+pub use watching::Watching;
 
 // #[derive(Clone, PartialEq, Debug)]
 // pub struct BoardContext {
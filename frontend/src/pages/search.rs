@@ -0,0 +1,297 @@
+use common::structs::{SafeBoard, SearchResults};
+use yew::prelude::*;
+use yew_hooks::use_effect_once;
+use yew_router::prelude::*;
+
+use crate::{
+    api::{ApiState, SearchParams},
+    components::{File, RichText},
+    helpers::{on_change_select_element, on_change_to_string, on_input_to_string},
+    ApiContext, BaseRoute,
+};
+
+#[function_component]
+pub fn Search() -> Html {
+    if let Some(window) = web_sys::window() {
+        if let Some(document) = window.document() {
+            document.set_title(&format!("{}Search", crate::PREFIX));
+        }
+    }
+    let api_ctx = use_context::<Option<ApiContext>>().flatten();
+    let timezone = use_context::<UseStateHandle<chrono_tz::Tz>>();
+
+    let boards = use_state(Vec::<SafeBoard>::new);
+    let params = use_state(SearchParams::default);
+    // what the shown results were searched with, so editing the form doesn't change what "more" loads
+    let searched = use_state(SearchParams::default);
+    let results = use_state(|| ApiState::Pending::<SearchResults>);
+    let loading_more = use_state(|| false);
+
+    {
+        let boards = boards.clone();
+        let api_ctx = api_ctx.clone();
+        use_effect_once(move || {
+            if let Some(Ok(api)) = api_ctx.map(|ctx| ctx.api) {
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Ok(mut theseboards) = api.get_boards(false).await {
+                        theseboards.sort_by(|a, b| {
+                            a.discriminator
+                                .to_lowercase()
+                                .cmp(&b.discriminator.to_lowercase())
+                        });
+                        boards.set(theseboards);
+                    }
+                });
+            }
+            || {}
+        });
+    }
+
+    // the first page replaces the results, later ones are added to them
+    let run = {
+        let results = results.clone();
+        let searched = searched.clone();
+        let loading_more = loading_more.clone();
+        Callback::from(move |(search, page): (SearchParams, i64)| {
+            let api = match api_ctx {
+                Some(ref api_ctx) => match api_ctx.api.clone() {
+                    Ok(api) => api,
+                    Err(e) => {
+                        results.set(ApiState::Error(e));
+                        return;
+                    }
+                },
+                None => {
+                    results.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                    return;
+                }
+            };
+            let previous = match &*results {
+                ApiState::Loaded(previous) if page > 0 => Some(previous.clone()),
+                _ => None,
+            };
+            if previous.is_some() {
+                loading_more.set(true);
+            } else {
+                results.set(ApiState::Loading);
+            }
+            searched.set(search.clone());
+
+            let results = results.clone();
+            let loading_more = loading_more.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match api.search(&search, page).await {
+                    Ok(mut found) => {
+                        if let Some(mut previous) = previous {
+                            previous.results.append(&mut found.results);
+                            found.results = previous.results;
+                        }
+                        results.set(ApiState::Loaded(found));
+                    }
+                    Err(e) => {
+                        results.set(ApiState::Error(e));
+                    }
+                }
+                loading_more.set(false);
+            });
+        })
+    };
+
+    let on_submit = {
+        let params = params.clone();
+        let run = run.clone();
+        Callback::from(move |e: SubmitEvent| {
+            e.prevent_default();
+            if !params.q.trim().is_empty() {
+                run.emit(((*params).clone(), 0));
+            }
+        })
+    };
+
+    let on_more = {
+        let searched = searched.clone();
+        let results = results.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            if let ApiState::Loaded(found) = &*results {
+                run.emit(((*searched).clone(), found.page + 1));
+            }
+        })
+    };
+
+    let on_query = {
+        let params = params.clone();
+        Callback::from(move |e: InputEvent| {
+            if let Some(input) = on_input_to_string(e) {
+                let mut cloned = (*params).clone();
+                cloned.q = input.value();
+                params.set(cloned);
+            }
+        })
+    };
+    let on_author = {
+        let params = params.clone();
+        Callback::from(move |e: InputEvent| {
+            if let Some(input) = on_input_to_string(e) {
+                let mut cloned = (*params).clone();
+                cloned.author = Some(input.value().trim().to_owned()).filter(|a| !a.is_empty());
+                params.set(cloned);
+            }
+        })
+    };
+    let on_board = {
+        let params = params.clone();
+        Callback::from(move |e: Event| {
+            if let Some(select) = on_change_select_element(e) {
+                let mut cloned = (*params).clone();
+                cloned.board = Some(select.value()).filter(|b| !b.is_empty());
+                params.set(cloned);
+            }
+        })
+    };
+    let on_has_file = {
+        let params = params.clone();
+        Callback::from(move |e: Event| {
+            if let Some(select) = on_change_select_element(e) {
+                let mut cloned = (*params).clone();
+                cloned.has_file = match select.value().as_str() {
+                    "yes" => Some(true),
+                    "no" => Some(false),
+                    _ => None,
+                };
+                params.set(cloned);
+            }
+        })
+    };
+    // dates are taken as utc days, the end date is included
+    let on_after = {
+        let params = params.clone();
+        Callback::from(move |e: Event| {
+            if let Some(input) = on_change_to_string(e) {
+                let mut cloned = (*params).clone();
+                cloned.after = day_start(&input.value());
+                params.set(cloned);
+            }
+        })
+    };
+    let on_before = {
+        let params = params.clone();
+        Callback::from(move |e: Event| {
+            if let Some(input) = on_change_to_string(e) {
+                let mut cloned = (*params).clone();
+                cloned.before = day_start(&input.value()).map(|t| t + 60 * 60 * 24);
+                params.set(cloned);
+            }
+        })
+    };
+    let on_threads_only = {
+        let params = params.clone();
+        Callback::from(move |e: Event| {
+            if let Some(input) = on_change_to_string(e) {
+                let mut cloned = (*params).clone();
+                cloned.threads_only = input.checked();
+                params.set(cloned);
+            }
+        })
+    };
+
+    html! {
+        <div class="valign">
+            <div class="halign">
+                <div class="search">
+                    <div class="search-title">
+                        <h1>{"Search"}</h1>
+                    </div>
+                    <form class="search-form" onsubmit={on_submit}>
+                        <input type="text" class="search-query" placeholder="Search posts and topics" value={params.q.clone()} oninput={on_query} />
+                        <div class="search-filters">
+                            <select onchange={on_board}>
+                                <option value="" selected={params.board.is_none()}>{"All boards"}</option>
+                                {
+                                    for boards.iter().map(|board| html! {
+                                        <option value={board.discriminator.clone()} selected={params.board.as_ref() == Some(&board.discriminator)}>
+                                            { format!("/{}/ - {}", board.discriminator, board.name) }
+                                        </option>
+                                    })
+                                }
+                            </select>
+                            <input type="text" placeholder="Author" value={params.author.clone().unwrap_or_default()} oninput={on_author} />
+                            <label>{"From"}<input type="date" onchange={on_after} /></label>
+                            <label>{"To"}<input type="date" onchange={on_before} /></label>
+                            <select onchange={on_has_file}>
+                                <option value="" selected={params.has_file.is_none()}>{"With or without files"}</option>
+                                <option value="yes" selected={params.has_file == Some(true)}>{"With a file"}</option>
+                                <option value="no" selected={params.has_file == Some(false)}>{"Without a file"}</option>
+                            </select>
+                            <label><input type="checkbox" checked={params.threads_only} onchange={on_threads_only} />{"Threads only"}</label>
+                        </div>
+                        <button type="submit">{"Search"}</button>
+                    </form>
+                    {
+                        match results.standard_html("Search", |found| {
+                            if found.results.is_empty() {
+                                return html! {
+                                    <span class="search-empty">{"Nothing matched"}</span>
+                                };
+                            }
+                            let highlight = found.terms.iter().map(|t| AttrValue::from(t.clone())).collect::<Vec<_>>();
+                            html! {
+                                <>
+                                    <div class="search-results">
+                                        {
+                                            for found.results.iter().map(|result| {
+                                                let post = &result.post;
+                                                let timestamp = match &timezone {
+                                                    Some(timezone) => post.timestamp.with_timezone(&**timezone).format(env!("TIMESTAMP_FORMAT")).to_string(),
+                                                    None => post.timestamp.format(env!("TIMESTAMP_FORMAT")).to_string(),
+                                                };
+                                                html! {
+                                                    <div class="search-result">
+                                                        <div class="search-result-header">
+                                                            <Link<BaseRoute> classes="search-result-link" to={BaseRoute::ThreadPage { board_discriminator: post.board_discriminator.clone(), thread_id: post.thread_post_number.to_string() }}>
+                                                                { format!("/{}/ {}", post.board_discriminator, result.topic) }
+                                                            </Link<BaseRoute>>
+                                                            <span class="search-result-info">
+                                                                { format!("{} - {} - No. {}{}", post.author, timestamp.replace("  ", " ").replace(" at 0", " at "), post.post_number, if result.is_thread { " (OP)" } else { "" }) }
+                                                            </span>
+                                                        </div>
+                                                        <div class="search-result-body">
+                                                            if let Some(file) = post.file.as_ref() {
+                                                                <div class="search-result-file">
+                                                                    <File file={file.clone()} />
+                                                                </div>
+                                                            }
+                                                            <RichText board={post.board_discriminator.clone()} content={post.content.clone()} thread_post_number={post.thread_post_number.to_string()} invert={false} highlight={highlight.clone()} />
+                                                        </div>
+                                                    </div>
+                                                }
+                                            })
+                                        }
+                                    </div>
+                                    if found.has_more {
+                                        if *loading_more {
+                                            <crate::components::Spinner />
+                                        } else {
+                                            <span class="search-more" onclick={on_more.clone()}>{"Load more results"}</span>
+                                        }
+                                    }
+                                </>
+                            }
+                        }) {
+                            Ok(v) => v,
+                            Err(e) => html! {
+                                <span class="search-empty">{ format!("Error: {}", *e) }</span>
+                            },
+                        }
+                    }
+                </div>
+            </div>
+        </div>
+    }
+}
+
+// the value of a date input, in seconds
+fn day_start(value: &str) -> Option<i64> {
+    let millis = js_sys::Date::parse(value);
+    (!millis.is_nan()).then(|| (millis / 1000.0) as i64)
+}
@@ -1,7 +1,9 @@
 use yew::prelude::*;
 use yew_hooks::use_local_storage;
 
-use crate::components::{InviteTools, PoweredBy, ThemeEditor, TimezoneEditor};
+use crate::components::{
+    InviteTools, NotificationSettings, PoweredBy, ShareLinks, ThemeEditor, TimezoneEditor,
+};
 
 #[function_component]
 pub fn Settings() -> Html {
@@ -31,7 +33,9 @@ pub fn Settings() -> Html {
                 <div class="settings">
                     <ThemeEditor />
                     <TimezoneEditor />
+                    <NotificationSettings />
                     <InviteTools expandable={true} />
+                    <ShareLinks />
                     {
                         if token.is_some() {
                             html! {
@@ -1,4 +1,4 @@
-use common::structs::{PushMessage, SafePost, ThreadWithPosts};
+use common::structs::{PushMessage, PushStream, SafePost, ThreadStatus, ThreadWithPosts};
 use yew::prelude::*;
 // use yew_hooks::use_interval;
 use yew_router::prelude::*;
@@ -28,54 +28,89 @@ pub fn ThreadPage() -> Html {
     let mut handle = {
         let route_ctx = route_ctx.clone();
         use_server_sent_event(
-            route_ctx.and_then(|r| {
-                if let BaseRoute::ThreadPage {
-                    board_discriminator,
-                    thread_id,
-                } = r
-                {
-                    if let Ok(thread_id) = thread_id.parse::<i64>() {
-                        Some((board_discriminator, thread_id))
+            route_ctx
+                .and_then(|r| {
+                    if let BaseRoute::ThreadPage {
+                        board_discriminator,
+                        thread_id,
+                    } = r
+                    {
+                        if let Ok(thread_id) = thread_id.parse::<i64>() {
+                            Some(PushStream::Thread(board_discriminator, thread_id))
+                        } else {
+                            None
+                        }
                     } else {
                         None
                     }
-                } else {
-                    None
-                }
-            }),
-            vec!["new_post"],
+                })
+                .unwrap_or(PushStream::Member),
+            vec!["new_post", "post_deleted", "thread_status"],
         )
     };
 
     {
         #[cfg(feature = "cache-post")]
         let api_ctx = api_ctx.clone();
-        if let Some(PushMessage::NewPost(post)) = handle.get() {
-            if route_ctx
-                == Some(BaseRoute::ThreadPage {
-                    board_discriminator: post.board_discriminator.clone(),
-                    thread_id: post.thread_post_number.to_string(),
-                })
-            {
-                let window =
-                    web_sys::window().map(|w| (w.clone(), w.scroll_x().ok(), w.scroll_y().ok()));
-                if let Some(favicon) = favicon {
-                    favicon.favicon.set("/res/unread.ico".to_string());
-                };
-                changed.set(window);
-                // append post to thread somehow D:
-                let tthread = (*thread).clone();
-                if let ApiState::Loaded(mut tthread) = tthread {
-                    tthread.posts.push((*post).clone());
-                    #[cfg(feature = "cache-post")]
-                    if let Some(api) = api_ctx.flatten().and_then(|a| a.api.ok()) {
-                        api.insert_post_to_cache((*post).clone());
-                        api.insert_thread_to_cache(tthread.clone());
-                    }
+        match handle.get() {
+            Some(PushMessage::NewPost(post)) => {
+                if route_ctx
+                    == Some(BaseRoute::ThreadPage {
+                        board_discriminator: post.board_discriminator.clone(),
+                        thread_id: post.thread_post_number.to_string(),
+                    })
+                {
+                    let window = web_sys::window()
+                        .map(|w| (w.clone(), w.scroll_x().ok(), w.scroll_y().ok()));
+                    if let Some(favicon) = favicon {
+                        favicon.favicon.set("/res/unread.ico".to_string());
+                    };
+                    changed.set(window);
+                    // append post to thread somehow D:
+                    let tthread = (*thread).clone();
+                    if let ApiState::Loaded(mut tthread) = tthread {
+                        tthread.posts.push((*post).clone());
+                        #[cfg(feature = "cache-post")]
+                        if let Some(api) = api_ctx.flatten().and_then(|a| a.api.ok()) {
+                            api.insert_post_to_cache((*post).clone());
+                            api.insert_thread_to_cache(tthread.clone());
+                        }
 
-                    thread.set(ApiState::Loaded(tthread));
+                        thread.set(ApiState::Loaded(tthread));
+                    }
+                }
+            }
+            Some(PushMessage::PostDeleted(deleted)) => {
+                if let ApiState::Loaded(mut tthread) = (*thread).clone() {
+                    let before = tthread.posts.len();
+                    tthread
+                        .posts
+                        .retain(|p| p.post_number != deleted.post_number);
+                    if tthread.posts.len() != before {
+                        tthread.post_count -= 1;
+                        thread.set(ApiState::Loaded(tthread));
+                    }
                 }
             }
+            Some(PushMessage::ThreadStatusChanged(change)) => match change.status {
+                ThreadStatus::Deleted => {
+                    if let Some(nav) = &nav {
+                        nav.replace(&BaseRoute::BoardPage {
+                            board_discriminator: change.board_discriminator.clone(),
+                        });
+                    }
+                }
+                // archived threads stay readable, only replying goes away
+                ThreadStatus::Archived => {
+                    if let ApiState::Loaded(mut tthread) = (*thread).clone() {
+                        if !tthread.archived {
+                            tthread.archived = true;
+                            thread.set(ApiState::Loaded(tthread));
+                        }
+                    }
+                }
+            },
+            _ => {}
         }
     }
 
@@ -170,7 +205,7 @@ pub fn ThreadPage() -> Html {
     {
         let thread = thread.clone();
         let scroll_to_bottom = scroll_to_bottom.clone();
-        let api_ctx = api_ctx;
+        let api_ctx = api_ctx.clone();
         use_effect_with(route_ctx, |route_ctx| {
             thread.set(ApiState::Loading);
             let route_ctx = route_ctx.clone();
@@ -240,6 +275,22 @@ pub fn ThreadPage() -> Html {
         });
     }
 
+    // reading the thread, including posts that arrive while it's open, counts as having seen them
+    {
+        let api = api_ctx.clone().flatten().and_then(|ctx| ctx.api.ok());
+        use_effect_with((*thread).clone(), move |thread| {
+            if let (ApiState::Loaded(thread), Some(api)) = (thread, api) {
+                let board = thread.thread_post.board_discriminator.clone();
+                let post = thread.thread_post.post_number;
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Err(e) = api.mark_thread_seen(&board, post).await {
+                        gloo::console::error!(format!("failed to mark thread seen: {:?}", e));
+                    }
+                });
+            }
+        });
+    }
+
     let on_successful_post = {
         // let manual_refresh_callback = manual_refresh_callback.clone();
         let scroll_to_bottom = scroll_to_bottom;
@@ -265,6 +316,9 @@ pub fn ThreadPage() -> Html {
                         html! {
                             <div class={"thread-page-threads"}>
                                 <Thread scroll_on_load={true} thread={thread.clone()} refresh={changed.clone()} />
+                                <div class="thread-export">
+                                    <ExportButton board_discriminator={thread.thread_post.board_discriminator.clone()} thread_post_number={Some(thread.thread_post.post_number)} />
+                                </div>
                                 // <div class="thread-reload-button">
                                 //     <span
                                 //     // onclick={
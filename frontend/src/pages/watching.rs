@@ -0,0 +1,163 @@
+use std::collections::HashSet;
+
+use common::structs::{ThreadRef, WatchedThread};
+use yew::prelude::*;
+use yew_hooks::use_effect_once;
+use yew_router::prelude::*;
+
+use crate::{api::ApiState, helpers::on_change_to_string, ApiContext, BaseRoute};
+
+#[function_component]
+pub fn Watching() -> Html {
+    if let Some(window) = web_sys::window() {
+        if let Some(document) = window.document() {
+            document.set_title(&format!("{}Watching", crate::PREFIX));
+        }
+    }
+    let state = use_state(|| ApiState::Pending::<Vec<WatchedThread>>);
+    let selected = use_state(HashSet::<ThreadRef>::new);
+
+    let api_ctx = use_context::<Option<ApiContext>>().flatten();
+
+    {
+        let state = state.clone();
+        let api_ctx = api_ctx.clone();
+        use_effect_once(move || {
+            match api_ctx {
+                Some(api_ctx) => match api_ctx.api {
+                    Ok(api) => {
+                        state.set(ApiState::Loading);
+                        wasm_bindgen_futures::spawn_local(async move {
+                            match api.get_watched_threads().await {
+                                Ok(v) => {
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+            || {}
+        })
+    }
+
+    let on_unwatch = {
+        let state = state.clone();
+        let selected = selected.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            if selected.is_empty() {
+                return;
+            }
+            match api_ctx {
+                Some(ref api_ctx) => match api_ctx.api.clone() {
+                    Ok(api) => {
+                        let state = state.clone();
+                        let selected = selected.clone();
+                        wasm_bindgen_futures::spawn_local(async move {
+                            let threads = selected.iter().cloned().collect::<Vec<_>>();
+                            match api.unwatch_threads(&threads).await {
+                                Ok(v) => {
+                                    selected.set(HashSet::new());
+                                    state.set(ApiState::Loaded(v));
+                                }
+                                Err(e) => {
+                                    state.set(ApiState::Error(e));
+                                }
+                            };
+                        });
+                    }
+                    Err(e) => {
+                        state.set(ApiState::Error(e));
+                    }
+                },
+                None => {
+                    state.set(ApiState::ContextError(AttrValue::from("ApiContext")));
+                }
+            }
+        })
+    };
+
+    let on_select = {
+        let selected = selected.clone();
+        Callback::from(move |(thread, checked): (ThreadRef, bool)| {
+            let mut cloned = (*selected).clone();
+            if checked {
+                cloned.insert(thread);
+            } else {
+                cloned.remove(&thread);
+            }
+            selected.set(cloned);
+        })
+    };
+
+    html! {
+        <div class="valign">
+            <div class="halign">
+                <div class="watching">
+                    <div class="watching-title">
+                        <h1>{"Watching"}</h1>
+                    </div>
+                    {
+                        match state.standard_html("Watching", |threads| {
+                            if threads.is_empty() {
+                                return html! {
+                                    <span class="watching-empty">{"You aren't watching any threads"}</span>
+                                };
+                            }
+                            html! {
+                                <>
+                                    <div class="watching-list">
+                                        {
+                                            for threads.iter().map(|thread| {
+                                                let thread_ref = ThreadRef {
+                                                    board_discriminator: thread.board_discriminator.clone(),
+                                                    thread_post_number: thread.thread_post_number,
+                                                };
+                                                let checked = selected.contains(&thread_ref);
+                                                let onchange = on_select.reform(move |e: Event| {
+                                                    (thread_ref.clone(), on_change_to_string(e).is_some_and(|input| input.checked()))
+                                                });
+                                                html! {
+                                                    <div class="watching-thread">
+                                                        <input type="checkbox" {checked} {onchange} />
+                                                        if let Some(thumbnail) = thread.thumbnail.as_ref() {
+                                                            <img class="watching-thumbnail" src={thumbnail.clone()} />
+                                                        }
+                                                        <Link<BaseRoute> classes="watching-link" to={BaseRoute::ThreadPage { board_discriminator: thread.board_discriminator.clone(), thread_id: thread.thread_post_number.to_string() }}>
+                                                            { format!("/{}/ {}", thread.board_discriminator, thread.topic) }
+                                                        </Link<BaseRoute>>
+                                                        if thread.unseen > 0 {
+                                                            <span class="watching-unseen">{ format!("{} new", thread.unseen) }</span>
+                                                        }
+                                                    </div>
+                                                }
+                                            })
+                                        }
+                                    </div>
+                                    <span class="watching-unwatch" onclick={on_unwatch.clone()}>
+                                        { format!("Unwatch selected ({})", selected.len()) }
+                                    </span>
+                                </>
+                            }
+                        }) {
+                            Ok(v) => v,
+                            Err(e) => html! {
+                                <span class="watching-empty">{ format!("Error: {}", *e) }</span>
+                            },
+                        }
+                    }
+                </div>
+            </div>
+        </div>
+    }
+}